@@ -0,0 +1,603 @@
+use crate::number_theory;
+use crate::ops::{Op, Operand, Settings, holds, pair, single};
+use crate::parser::Number;
+use std::{
+    cmp::Ordering,
+    ops::{
+        Add, Sub, Mul, Div, Rem, BitAnd, BitOr, BitXor, Neg
+    },
+    fmt::{
+        self, Display
+    }
+};
+
+/// Shifts are limited to that many bits
+const MAX_SHIFT: i128 = 1 << 20;
+/// Factorials and other products are limited to that many factors
+const MAX_FACTORS: u128 = 10_000;
+
+/// An arbitrary-precision integer, stored as a sign and the 32-bit limbs of
+/// its magnitude (least significant first, without trailing zero limbs)
+/// Zero is never negative
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BigInt {
+    neg: bool,
+    mag: Vec<u32>
+}
+
+fn trim(mut mag: Vec<u32>) -> Vec<u32> {
+    while mag.last() == Some(&0) { mag.pop(); };
+    mag
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut result = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, &limb) in long.iter().enumerate() {
+        let sum = limb as u64 + *short.get(i).unwrap_or(&0) as u64 + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    };
+    result.push(carry as u32);
+    trim(result)
+}
+
+/// `a - b`, where `a` is at least `b`
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &limb) in a.iter().enumerate() {
+        let diff = limb as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        result.push(diff as u32);
+        borrow = (diff < 0) as i64;
+    };
+    trim(result)
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let prod = x as u64 * y as u64 + result[i + j] as u64 + carry;
+            result[i + j] = prod as u32;
+            carry = prod >> 32;
+        };
+        result[i + b.len()] = carry as u32;
+    };
+    trim(result)
+}
+
+fn shl_mag(a: &[u32], bits: usize) -> Vec<u32> {
+    if a.is_empty() { return Vec::new() };
+    let (limbs, bits) = (bits / 32, bits % 32);
+    let mut result = vec![0u32; limbs];
+    let mut carry = 0;
+    for &limb in a {
+        result.push((limb << bits) | carry);
+        carry = match bits {
+            0 => 0,
+            _ => limb >> (32 - bits)
+        };
+    };
+    result.push(carry);
+    trim(result)
+}
+
+fn shr_mag(a: &[u32], bits: usize) -> Vec<u32> {
+    let (limbs, bits) = (bits / 32, bits % 32);
+    if limbs >= a.len() { return Vec::new() };
+    let a = &a[limbs..];
+    let result = (0..a.len()).map(|i| {
+        let high = match (bits, a.get(i + 1)) {
+            (0, _) | (_, None) => 0,
+            (_, Some(&next)) => next << (32 - bits)
+        };
+        (a[i] >> bits) | high
+    }).collect();
+    trim(result)
+}
+
+fn divrem_small(a: &[u32], d: u32) -> (Vec<u32>, u32) {
+    let mut quotient = vec![0u32; a.len()];
+    let mut rem = 0u64;
+    for i in (0..a.len()).rev() {
+        let cur = (rem << 32) | a[i] as u64;
+        quotient[i] = (cur / d as u64) as u32;
+        rem = cur % d as u64;
+    };
+    (trim(quotient), rem as u32)
+}
+
+/// Long division of magnitudes (Knuth's algorithm D), returning the quotient and remainder
+fn divrem_mag(u: &[u32], v: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_mag(u, v) == Ordering::Less { return (Vec::new(), u.to_vec()) };
+    if v.len() == 1 {
+        let (quotient, rem) = divrem_small(u, v[0]);
+        return (quotient, trim(vec![rem]))
+    };
+    // normalize so that the top limb of the divisor has its high bit set
+    let shift = v.last().unwrap().leading_zeros() as usize;
+    let v = shl_mag(v, shift);
+    let mut u = shl_mag(u, shift);
+    u.resize(u.len().max(v.len()) + 1, 0);
+    let n = v.len();
+    let m = u.len() - n - 1;
+    let mut quotient = vec![0u32; m + 1];
+    for j in (0..=m).rev() {
+        let top = ((u[j + n] as u64) << 32) | u[j + n - 1] as u64;
+        let mut qhat = top / v[n - 1] as u64;
+        let mut rhat = top % v[n - 1] as u64;
+        while qhat >> 32 != 0 || qhat * v[n - 2] as u64 > ((rhat << 32) | u[j + n - 2] as u64) {
+            qhat -= 1;
+            rhat += v[n - 1] as u64;
+            if rhat >> 32 != 0 { break };
+        };
+        let mut borrow = 0i64;
+        for i in 0..n {
+            let prod = qhat * v[i] as u64;
+            let diff = u[i + j] as i64 - borrow - (prod & 0xFFFF_FFFF) as i64;
+            u[i + j] = diff as u32;
+            borrow = (prod >> 32) as i64 - (diff >> 32);
+        };
+        let diff = u[j + n] as i64 - borrow;
+        u[j + n] = diff as u32;
+        quotient[j] = qhat as u32;
+        if diff < 0 {
+            // qhat was one too large: add the divisor back
+            quotient[j] -= 1;
+            let mut carry = 0u64;
+            for i in 0..n {
+                let sum = u[i + j] as u64 + v[i] as u64 + carry;
+                u[i + j] = sum as u32;
+                carry = sum >> 32;
+            };
+            u[j + n] = u[j + n].wrapping_add(carry as u32);
+        };
+    };
+    u.truncate(n);
+    (trim(quotient), shr_mag(&trim(u), shift))
+}
+
+#[allow(unused)]
+impl BigInt {
+    fn from_parts(neg: bool, mag: Vec<u32>) -> Self {
+        let mag = trim(mag);
+        BigInt { neg: neg && !mag.is_empty(), mag }
+    }
+    pub fn zero() -> Self { BigInt::default() }
+    pub fn one() -> Self { BigInt::from(1u32) }
+    pub fn is_zero(&self) -> bool { self.mag.is_empty() }
+    pub fn is_negative(&self) -> bool { self.neg }
+    pub fn is_odd(&self) -> bool { self.mag.first().is_some_and(|limb| limb & 1 == 1) }
+    pub fn abs(&self) -> Self { BigInt::from_parts(false, self.mag.clone()) }
+
+    /// Number of bits of the magnitude
+    pub fn bits(&self) -> usize {
+        match self.mag.last() {
+            None => 0,
+            Some(top) => 32 * self.mag.len() - top.leading_zeros() as usize
+        }
+    }
+
+    /// Builds a non-negative integer out of digits in the given base, most significant first
+    pub fn from_digits(digits: impl IntoIterator<Item = u32>, base: u32) -> Self {
+        let mut mag = Vec::new();
+        for digit in digits {
+            let mut carry = digit as u64;
+            for limb in mag.iter_mut() {
+                let cur = *limb as u64 * base as u64 + carry;
+                *limb = cur as u32;
+                carry = cur >> 32;
+            };
+            if carry != 0 { mag.push(carry as u32) };
+        };
+        BigInt::from_parts(false, mag)
+    }
+
+    pub fn to_u128(&self) -> Option<u128> {
+        match self.mag.len() > 4 || self.neg {
+            true => None,
+            false => Some(self.mag.iter().rev().fold(0, |acc, &limb| (acc << 32) | limb as u128))
+        }
+    }
+
+    pub fn to_i128(&self) -> Option<i128> {
+        let abs = BigInt::from_parts(false, self.mag.clone()).to_u128()?;
+        match self.neg {
+            false => i128::try_from(abs).ok(),
+            true => (abs <= 1 << 127).then(|| (abs as i128).wrapping_neg())
+        }
+    }
+
+    pub fn pow(&self, mut exp: u32) -> Self {
+        let mut result = BigInt::one();
+        let mut base = self.clone();
+        while exp > 0 {
+            if exp & 1 == 1 { result = &result * &base };
+            exp >>= 1;
+            if exp > 0 { base = &base * &base };
+        };
+        result
+    }
+
+    /// Quotient and remainder of the division truncated toward zero
+    /// Panics when `rhs` is zero
+    pub fn divrem(&self, rhs: &BigInt) -> (BigInt, BigInt) {
+        if rhs.is_zero() { panic!("attempt to divide by zero") };
+        let (quotient, rem) = divrem_mag(&self.mag, &rhs.mag);
+        (BigInt::from_parts(self.neg != rhs.neg, quotient), BigInt::from_parts(self.neg, rem))
+    }
+
+    /// Quotient and remainder of the division rounded toward negative infinity
+    /// The remainder has the sign of `rhs`
+    pub fn divrem_floor(&self, rhs: &BigInt) -> (BigInt, BigInt) {
+        let (quotient, rem) = self.divrem(rhs);
+        match !rem.is_zero() && rem.neg != rhs.neg {
+            true => (quotient - BigInt::one(), rem + rhs.clone()),
+            false => (quotient, rem)
+        }
+    }
+
+    /// Quotient and remainder of the Euclidean division
+    /// The remainder is never negative
+    pub fn divrem_euclid(&self, rhs: &BigInt) -> (BigInt, BigInt) {
+        let (quotient, rem) = self.divrem(rhs);
+        match (rem.neg, rhs.neg) {
+            (false, _) => (quotient, rem),
+            (true, false) => (quotient - BigInt::one(), rem + rhs.clone()),
+            (true, true) => (quotient + BigInt::one(), rem - rhs.clone())
+        }
+    }
+
+    pub fn gcd(&self, rhs: &BigInt) -> BigInt {
+        let (mut a, mut b) = (self.abs(), rhs.abs());
+        while !b.is_zero() {
+            let rem = a.divrem(&b).1;
+            a = b;
+            b = rem;
+        };
+        a
+    }
+
+    pub fn shl(&self, bits: usize) -> BigInt {
+        BigInt::from_parts(self.neg, shl_mag(&self.mag, bits))
+    }
+
+    /// Arithmetic shift right, rounding toward negative infinity
+    pub fn shr(&self, bits: usize) -> BigInt {
+        match self.neg {
+            false => BigInt::from_parts(false, shr_mag(&self.mag, bits)),
+            true => {
+                let below = sub_mag(&self.mag, &[1]);
+                BigInt::from_parts(true, add_mag(&shr_mag(&below, bits), &[1]))
+            }
+        }
+    }
+
+    /// The two's complement representation, sign-extended to `len` limbs
+    fn to_twos_complement(&self, len: usize) -> Vec<u32> {
+        let mut limbs = match self.neg {
+            false => self.mag.clone(),
+            true => sub_mag(&self.mag, &[1]).iter().map(|limb| !limb).collect()
+        };
+        let fill = if self.neg { u32::MAX } else { 0 };
+        limbs.resize(len, fill);
+        limbs
+    }
+
+    fn from_twos_complement(limbs: Vec<u32>) -> BigInt {
+        match limbs.last().is_some_and(|top| top >> 31 == 1) {
+            false => BigInt::from_parts(false, limbs),
+            true => {
+                let inverted: Vec<u32> = limbs.iter().map(|limb| !limb).collect();
+                BigInt::from_parts(true, add_mag(&trim(inverted), &[1]))
+            }
+        }
+    }
+
+    fn bitwise(&self, rhs: &BigInt, func: fn(u32, u32) -> u32) -> BigInt {
+        let len = self.mag.len().max(rhs.mag.len()) + 1;
+        let a = self.to_twos_complement(len);
+        let b = rhs.to_twos_complement(len);
+        BigInt::from_twos_complement(a.iter().zip(&b).map(|(&x, &y)| func(x, y)).collect())
+    }
+
+    /// The value written in the given base (2 to 36), with a prefix for bases 2, 8 and 16
+    pub fn fmt_radix(&self, radix: u32) -> String {
+        match radix {
+            2 => format!("{:#b}", self),
+            8 => format!("{:#o}", self),
+            16 => format!("{:#x}", self),
+            _ => format!("{}{}", if self.neg { "-" } else { "" }, self.to_str_radix(radix))
+        }
+    }
+
+    /// The digits of the absolute value in the given base (2 to 36), without prefix
+    pub fn to_str_radix(&self, radix: u32) -> String {
+        if self.is_zero() { return String::from("0") };
+        // divide by the largest power of the radix that fits in a limb
+        let mut chunk_digits = 1;
+        let mut chunk = radix;
+        while let Some(next) = chunk.checked_mul(radix) {
+            chunk = next;
+            chunk_digits += 1;
+        };
+        let mut digits = Vec::new();
+        let mut mag = self.mag.clone();
+        while !mag.is_empty() {
+            let (quotient, mut rem) = divrem_small(&mag, chunk);
+            for _ in 0..chunk_digits {
+                digits.push(char::from_digit(rem % radix, radix).unwrap());
+                rem /= radix;
+            };
+            mag = quotient;
+        };
+        while digits.len() > 1 && digits.last() == Some(&'0') { digits.pop(); };
+        digits.iter().rev().collect()
+    }
+}
+
+impl From<u32> for BigInt {
+    fn from(x: u32) -> Self {
+        BigInt::from_parts(false, vec![x])
+    }
+}
+
+impl From<u128> for BigInt {
+    fn from(x: u128) -> Self {
+        BigInt::from_parts(false, (0..4).map(|i| (x >> (32 * i)) as u32).collect())
+    }
+}
+
+impl From<i128> for BigInt {
+    fn from(x: i128) -> Self {
+        let abs = BigInt::from(x.unsigned_abs());
+        BigInt::from_parts(x < 0, abs.mag)
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.neg, other.neg) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.mag, &other.mag),
+            (true, true) => cmp_mag(&other.mag, &self.mag)
+        }
+    }
+}
+
+impl Neg for BigInt {
+    type Output = BigInt;
+    fn neg(self) -> BigInt {
+        BigInt::from_parts(!self.neg, self.mag)
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+    fn add(self, rhs: Self) -> BigInt {
+        match (self.neg == rhs.neg, cmp_mag(&self.mag, &rhs.mag)) {
+            (true, _) => BigInt::from_parts(self.neg, add_mag(&self.mag, &rhs.mag)),
+            (false, Ordering::Less) => BigInt::from_parts(rhs.neg, sub_mag(&rhs.mag, &self.mag)),
+            (false, _) => BigInt::from_parts(self.neg, sub_mag(&self.mag, &rhs.mag))
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+    fn sub(self, rhs: Self) -> BigInt {
+        self + &-rhs.clone()
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+    fn mul(self, rhs: Self) -> BigInt {
+        BigInt::from_parts(self.neg != rhs.neg, mul_mag(&self.mag, &rhs.mag))
+    }
+}
+
+impl Div for &BigInt {
+    type Output = BigInt;
+    fn div(self, rhs: Self) -> BigInt {
+        self.divrem(rhs).0
+    }
+}
+
+impl Rem for &BigInt {
+    type Output = BigInt;
+    fn rem(self, rhs: Self) -> BigInt {
+        self.divrem(rhs).1
+    }
+}
+
+impl BitAnd for &BigInt {
+    type Output = BigInt;
+    fn bitand(self, rhs: Self) -> BigInt {
+        self.bitwise(rhs, |a, b| a & b)
+    }
+}
+
+impl BitOr for &BigInt {
+    type Output = BigInt;
+    fn bitor(self, rhs: Self) -> BigInt {
+        self.bitwise(rhs, |a, b| a | b)
+    }
+}
+
+impl BitXor for &BigInt {
+    type Output = BigInt;
+    fn bitxor(self, rhs: Self) -> BigInt {
+        self.bitwise(rhs, |a, b| a ^ b)
+    }
+}
+
+macro_rules! impl_owned_ops {
+    ($(impl $trait:tt (fn $fnname:tt)),+) => {
+        $(
+            impl $trait for BigInt {
+                type Output = BigInt;
+                fn $fnname(self, rhs: Self) -> BigInt {
+                    (&self).$fnname(&rhs)
+                }
+            }
+        )+
+    };
+}
+
+impl_owned_ops!(
+    impl Add (fn add),
+    impl Sub (fn sub),
+    impl Mul (fn mul),
+    impl Div (fn div),
+    impl Rem (fn rem),
+    impl BitAnd (fn bitand),
+    impl BitOr (fn bitor),
+    impl BitXor (fn bitxor)
+);
+
+impl Operand for BigInt {
+    fn from_number(num: Number) -> Result<Self, &'static str> {
+        let (numer, denom) = num.into_fraction()?;
+        let (quotient, rem) = numer.divrem(&denom);
+        match rem.is_zero() {
+            true => Ok(quotient),
+            false => Err("Not an integer")
+        }
+    }
+
+    fn is_true(&self) -> Result<bool, &'static str> {
+        Ok(!self.is_zero())
+    }
+
+    fn apply(op: &Op, a: Self, b: Self, settings: &Settings) -> Result<Self, &'static str> {
+        let shift = || match b.to_i128() {
+            Some(amount) if (0..=MAX_SHIFT).contains(&amount) => Ok(amount as usize),
+            _ => Err("Shift amount out of range")
+        };
+        let result = match op {
+            Op::Add => a + b,
+            Op::Sub => a - b,
+            Op::Mul | Op::ImplicitMul => a * b,
+            Op::Pow if b.is_negative() => return Err("Negative exponent"),
+            // 0, 1 and -1 only depend on the parity of the exponent
+            Op::Pow if a.bits() <= 1 => a.pow(match (b.is_zero(), b.is_odd()) {
+                (true, _) => 0,
+                (false, true) => 1,
+                (false, false) => 2
+            }),
+            Op::Pow => match b.to_u128() {
+                Some(n) if n.saturating_mul(a.bits() as u128) <= MAX_SHIFT as u128 => a.pow(n as u32),
+                _ => return Err("Result out of range")
+            },
+            Op::Lt | Op::Le | Op::Gt | Op::Ge | Op::Eq | Op::Ne => BigInt::from(holds(op, a.cmp(&b)) as u32),
+            Op::Div | Op::Mod | Op::FloorDiv | Op::EuclidMod if b.is_zero() => return Err("Division by zero"),
+            Op::Div | Op::Mod => {
+                let (quotient, rem) = match settings.floor_division {
+                    false => a.divrem(&b),
+                    true => a.divrem_floor(&b)
+                };
+                match op {
+                    Op::Div => quotient,
+                    _ => rem
+                }
+            },
+            Op::FloorDiv => a.divrem_floor(&b).0,
+            Op::EuclidMod => a.divrem_euclid(&b).1,
+            Op::And => a & b,
+            Op::Or => a | b,
+            Op::Xor => a ^ b,
+            Op::Shl => a.shl(shift()?),
+            Op::Shr => a.shr(shift()?),
+            Op::Rotl | Op::Rotr => return Err("Rotation needs a fixed width"),
+            Op::PlusMinus => return Err("Uncertainties need the uncertain mode")
+        };
+        Ok(result)
+    }
+
+    fn call(name: &str, args: Vec<Self>, _settings: &Settings) -> Result<Self, &'static str> {
+        let natural = |x: &BigInt| match (x.is_negative(), x.to_u128()) {
+            (true, _) => Err("Negative argument"),
+            (false, Some(n)) => Ok(n),
+            (false, None) => Err("Argument out of range")
+        };
+        let product = |range: std::ops::RangeInclusive<u128>| match range.end() - range.start() < MAX_FACTORS {
+            true => Ok(range.fold(BigInt::one(), |acc, k| acc * BigInt::from(k))),
+            false => Err("Argument out of range")
+        };
+        match name {
+            "abs" => Ok(single(args)?.abs()),
+            "factorial" => product(1..=natural(&single(args)?)?),
+            "nCr" | "nPr" => {
+                let (n, k) = pair(args)?;
+                let (n, k) = (natural(&n)?, natural(&k)?);
+                match (name, k > n) {
+                    (_, true) => Ok(BigInt::zero()),
+                    ("nPr", false) => product(n - k + 1..=n),
+                    (_, false) => {
+                        let k = k.min(n - k);
+                        if k >= MAX_FACTORS { return Err("Argument out of range") };
+                        Ok((0..k).fold(BigInt::one(), |acc, i| &(acc * BigInt::from(n - i)) / &BigInt::from(i + 1)))
+                    }
+                }
+            },
+            "gcd" | "lcm" => {
+                if args.len() < 2 { return Err("Wrong number of arguments") };
+                let mut args = args.into_iter();
+                let first = args.next().unwrap().abs();
+                Ok(args.fold(first, |acc, x| match (name, acc.gcd(&x)) {
+                    ("gcd", d) => d,
+                    (_, d) if d.is_zero() => d,
+                    (_, d) => &(&acc / &d) * &x.abs()
+                }))
+            },
+            "isprime" | "factor" => {
+                let n = u64::try_from(natural(&single(args)?)?).map_err(|_| "Argument out of range")?;
+                match (name, n) {
+                    ("isprime", n) => Ok(BigInt::from(number_theory::is_prime(n) as u32)),
+                    (_, 0 | 1) => Err("No prime factor"),
+                    (_, n) => Ok(BigInt::from(number_theory::prime_factors(n)[0] as u128))
+                }
+            },
+            _ => Err("Unknown function")
+        }
+    }
+}
+
+impl Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad_integral(!self.neg, "", &self.to_str_radix(10))
+    }
+}
+
+macro_rules! impl_radix_fmt {
+    ($(impl $trait:tt with $radix:literal and $prefix:literal),+) => {
+        $(
+            impl fmt::$trait for BigInt {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    f.pad_integral(!self.neg, $prefix, &self.to_str_radix($radix))
+                }
+            }
+        )+
+    };
+}
+
+impl_radix_fmt!(
+    impl Binary with 2 and "0b",
+    impl Octal with 8 and "0o",
+    impl LowerHex with 16 and "0x"
+);
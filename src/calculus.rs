@@ -0,0 +1,123 @@
+use crate::bigint::BigInt;
+use crate::fixed::{Fixed, Repr};
+use crate::ops::{Op, Operand, Settings};
+use crate::rational::Rational;
+
+/// Integration fails after this many values of the function, as the integral doesn't converge
+const MAX_EVALUATIONS: usize = 100_000;
+
+/// Intervals are halved at most this many times
+const MAX_DEPTH: u32 = 50;
+
+/// Integrals are found to within about this many of the smallest step of the format,
+/// times the width of the interval when it is larger than 1
+const TOLERANCE: u128 = 4;
+
+/// A function of a fixed-point number, given by an expression of a variable
+pub type Function<'a, const FRAC: u32, T> = &'a mut dyn FnMut(Fixed<FRAC, T>) -> Result<Fixed<FRAC, T>, &'static str>;
+
+/// The step of central differences around `x`, about the cube root of the smallest step of
+/// the format (2^-10 for Q32.32), relative to `x` when it is larger than 1
+/// It balances the rounding of the values of the function, which grows as the step gets smaller,
+/// against the curvature, which grows as it gets larger
+fn step<const FRAC: u32, T: Repr>(x: Fixed<FRAC, T>) -> Fixed<FRAC, T> {
+    let step = (1 << (FRAC - FRAC / 3)).max(x.as_raw().unsigned_abs() >> (FRAC / 3));
+    Fixed::from_raw(T::from_magnitude(false, step).unwrap_or(T::MAX))
+}
+
+/// The derivative of `f` at `x`, by central differences
+pub fn derivative<const FRAC: u32, T: Repr>(
+    f: Function<FRAC, T>, x: Fixed<FRAC, T>, settings: &Settings
+) -> Result<Fixed<FRAC, T>, &'static str> {
+    let h = step(x);
+    let (before, after) = (x.checked_sub(h), x.checked_add(h));
+    let (before, after) = before.zip(after).ok_or("Result out of range")?;
+    let difference = f(after)?.checked_sub(f(before)?).ok_or("Result out of range")?;
+    difference.checked_div_round(h.checked_add(h).ok_or("Result out of range")?, settings.rounding).ok_or("Result out of range")
+}
+
+/// The integral of `f` over an interval, with Simpson's rule, which is exact for cubics
+/// The areas are exact, so that only the values of `f` and the integral are rounded, and not
+/// each of the many pieces the integral is the sum of
+struct Simpson<'a, 'f, const FRAC: u32, T: Repr> {
+    f: Function<'f, FRAC, T>,
+    settings: &'a Settings,
+    evaluations: usize
+}
+
+impl<const FRAC: u32, T: Repr> Simpson<'_, '_, FRAC, T> {
+    fn eval(&mut self, x: Fixed<FRAC, T>) -> Result<Fixed<FRAC, T>, &'static str> {
+        if self.evaluations == MAX_EVALUATIONS { return Err("No convergence") };
+        self.evaluations += 1;
+        (self.f)(x)
+    }
+
+    fn exact(&self, op: Op, a: Rational, b: Rational) -> Result<Rational, &'static str> {
+        Rational::apply(&op, a, b, self.settings)
+    }
+
+    /// Simpson's rule over `a..b`, from the values of `f` at `a`, the middle and `b`
+    fn rule(&self, a: Fixed<FRAC, T>, b: Fixed<FRAC, T>, values: [Fixed<FRAC, T>; 3]) -> Result<Rational, &'static str> {
+        let [fa, fm, fb] = values.map(Rational::from_fixed);
+        let four = self.exact(Op::Mul, integer(4), fm)?;
+        let sum = self.exact(Op::Add, self.exact(Op::Add, fa, four)?, fb)?;
+        let width = self.exact(Op::Sub, Rational::from_fixed(b), Rational::from_fixed(a))?;
+        self.exact(Op::Div, self.exact(Op::Mul, width, sum)?, integer(6))
+    }
+
+    /// The integral over `a..b`, where Simpson's rule gives `whole`, by halving the interval
+    /// until the halves add up to it, to within `tolerance`
+    fn adapt(
+        &mut self, a: Fixed<FRAC, T>, b: Fixed<FRAC, T>, values: [Fixed<FRAC, T>; 3], whole: Rational,
+        tolerance: Fixed<FRAC, T>, depth: u32
+    ) -> Result<Rational, &'static str> {
+        let [fa, fm, fb] = values;
+        let m = midpoint(a, b)?;
+        let (left_m, right_m) = (midpoint(a, m)?, midpoint(m, b)?);
+        let (f_left, f_right) = (self.eval(left_m)?, self.eval(right_m)?);
+        let left = self.rule(a, m, [fa, f_left, fm])?;
+        let right = self.rule(m, b, [fm, f_right, fb])?;
+        let halves = self.exact(Op::Add, left.clone(), right.clone())?;
+        let difference = self.exact(Op::Sub, halves.clone(), whole)?;
+        // The error of the halves is about 15 times smaller than their difference with the whole,
+        // which corrects them, but only about twice smaller next to a singularity, as of `sqrt(x)` at 0,
+        // so that the difference itself has to be within the tolerance
+        let size = Rational::new(difference.numer().abs(), difference.denom().clone());
+        let correction = self.exact(Op::Div, difference, integer(15))?;
+        let narrow = left_m == a || right_m == m;
+        match self.exact(Op::Le, size, Rational::from_fixed(tolerance))?.is_true()? {
+            true => self.exact(Op::Add, halves, correction),
+            false if depth == MAX_DEPTH || narrow => Err("No convergence"),
+            false => {
+                let tolerance = tolerance.shr_bits(1).max(Fixed::EPSILON);
+                let left = self.adapt(a, m, [fa, f_left, fm], left, tolerance, depth + 1)?;
+                let right = self.adapt(m, b, [fm, f_right, fb], right, tolerance, depth + 1)?;
+                self.exact(Op::Add, left, right)
+            }
+        }
+    }
+}
+
+fn integer(n: u32) -> Rational {
+    Rational::from_int(BigInt::from(n))
+}
+
+fn midpoint<const FRAC: u32, T: Repr>(a: Fixed<FRAC, T>, b: Fixed<FRAC, T>) -> Result<Fixed<FRAC, T>, &'static str> {
+    a.checked_add(b.checked_sub(a).ok_or("Result out of range")?.shr_bits(1)).ok_or("Result out of range")
+}
+
+/// The integral of `f` from `a` to `b`, by adaptive Simpson's rule
+pub fn integral<const FRAC: u32, T: Repr>(
+    f: Function<FRAC, T>, a: Fixed<FRAC, T>, b: Fixed<FRAC, T>, settings: &Settings
+) -> Result<Fixed<FRAC, T>, &'static str> {
+    if a == b { return Ok(Fixed::ZERO) };
+    let mut simpson = Simpson { f, settings, evaluations: 0 };
+    let m = midpoint(a, b)?;
+    let values = [simpson.eval(a)?, simpson.eval(m)?, simpson.eval(b)?];
+    let whole = simpson.rule(a, b, values)?;
+    // The values of `f` are rounded, so that the error can't be smaller than the width
+    // times the smallest step
+    let width = b.checked_sub(a).ok_or("Result out of range")?.as_raw().unsigned_abs() >> FRAC;
+    let tolerance = Fixed::from_raw(T::from_magnitude(false, TOLERANCE * width.max(1)).unwrap_or(T::MAX));
+    simpson.adapt(a, b, values, whole, tolerance, 0)?.to_fixed(settings.rounding).ok_or("Result out of range")
+}
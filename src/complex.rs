@@ -0,0 +1,195 @@
+use crate::fixed::{Fixed, Repr, RoundingMode};
+use crate::ops::{FixedOp, Op, Operand, Settings, INTEGER_FUNCTIONS, power, single, truth};
+use crate::parser::Number;
+use std::fmt::{
+    self, Display, LowerExp
+};
+
+/// A complex number, made of two real parts of type `F`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Complex<F = Fixed> {
+    re: F,
+    im: F
+}
+
+#[allow(unused)]
+impl<F: Copy> Complex<F> {
+    pub fn new(re: F, im: F) -> Self {
+        Complex { re, im }
+    }
+    pub fn re(&self) -> F { self.re }
+    pub fn im(&self) -> F { self.im }
+}
+
+#[allow(unused)]
+impl<const FRAC: u32, T: Repr> Complex<Fixed<FRAC, T>> {
+    pub fn is_real(&self) -> bool { self.im == Fixed::ZERO }
+
+    /// The modulus, rounded with `mode`, if it is in range
+    pub fn abs(&self, mode: RoundingMode) -> Option<Fixed<FRAC, T>> {
+        self.re.hypot(self.im, mode)
+    }
+
+    /// The argument in radians, between -pi and pi, rounded with `mode`, if it is in range
+    pub fn arg(&self, mode: RoundingMode) -> Option<Fixed<FRAC, T>> {
+        self.im.atan2(self.re, mode)
+    }
+
+    /// The modulus and the argument, if they are in range
+    pub fn polar(&self, mode: RoundingMode) -> Option<(Fixed<FRAC, T>, Fixed<FRAC, T>)> {
+        Some((self.abs(mode)?, self.arg(mode)?))
+    }
+
+    /// The principal square root, whose real part is never negative
+    fn sqrt(&self, settings: &Settings) -> Result<Self, &'static str> {
+        let apply = |op: &Op, a, b| Fixed::apply(op, a, b, settings);
+        let modulus = self.abs(settings.rounding).ok_or("Result out of range")?;
+        // sqrt((|z| + |re|) / 2) is the larger part of the root, and has no cancellation
+        let large = apply(&Op::Add, modulus, self.re.abs())?.shr_bits(1);
+        let large = large.sqrt(settings.rounding).ok_or("Result out of range")?;
+        if large == Fixed::ZERO { return Ok(*self) };
+        let small = apply(&Op::Div, self.im.abs(), apply(&Op::Add, large, large)?)?;
+        let (re, im) = match self.re >= Fixed::ZERO {
+            true => (large, small),
+            false => (small, large)
+        };
+        match self.im < Fixed::ZERO {
+            true => Ok(Complex { re, im: Fixed::ZERO - im }),
+            false => Ok(Complex { re, im })
+        }
+    }
+
+    /// Applies a binary operator, with `fixed` applying the operators on the parts
+    fn operate(op: &Op, a: Self, b: Self, settings: &Settings, fixed: FixedOp<FRAC, T>) -> Result<Self, &'static str> {
+        let apply = |op: &Op, x, y| fixed(op, x, y, settings);
+        // the parts of products and quotients fail out of range, instead of wrapping to a wrong value
+        let checked = |op: &Op, x, y| Fixed::checked(op, x, y, settings);
+        let (add, sub, mul, div) = (&Op::Add, &Op::Sub, &Op::Mul, &Op::Div);
+        let result = match op {
+            Op::Add | Op::Sub => Complex { re: apply(op, a.re, b.re)?, im: apply(op, a.im, b.im)? },
+            Op::Mul | Op::ImplicitMul => Complex {
+                re: checked(sub, checked(mul, a.re, b.re)?, checked(mul, a.im, b.im)?)?,
+                im: checked(add, checked(mul, a.re, b.im)?, checked(mul, a.im, b.re)?)?
+            },
+            Op::Div if b.re == Fixed::ZERO && b.im == Fixed::ZERO => return Err("Division by zero"),
+            // Smith's algorithm, which avoids squaring the parts of the divisor
+            Op::Div if b.re.abs() >= b.im.abs() => {
+                let ratio = checked(div, b.im, b.re)?;
+                let denom = checked(add, b.re, checked(mul, b.im, ratio)?)?;
+                Complex {
+                    re: checked(div, checked(add, a.re, checked(mul, a.im, ratio)?)?, denom)?,
+                    im: checked(div, checked(sub, a.im, checked(mul, a.re, ratio)?)?, denom)?
+                }
+            },
+            Op::Div => {
+                let ratio = checked(div, b.re, b.im)?;
+                let denom = checked(add, checked(mul, b.re, ratio)?, b.im)?;
+                Complex {
+                    re: checked(div, checked(add, checked(mul, a.re, ratio)?, a.im)?, denom)?,
+                    im: checked(div, checked(sub, checked(mul, a.im, ratio)?, a.re)?, denom)?
+                }
+            },
+            _ if a.is_real() && b.is_real() => Complex { re: apply(op, a.re, b.re)?, im: Fixed::ZERO },
+            Op::Pow if !b.is_real() => return Err("Complex exponent"),
+            Op::Pow if !b.re.is_integer() => return Err("Fractional exponent"),
+            Op::Pow => {
+                let (neg, n) = b.re.whole_magnitude();
+                let one = Complex { re: Fixed::one().ok_or("Result out of range")?, im: Fixed::ZERO };
+                let result = power(a, n, |x, y| Complex::apply(mul, x, y, settings))?.unwrap_or(one);
+                match neg {
+                    false => result,
+                    true => Complex::apply(div, one, result, settings)?
+                }
+            },
+            Op::Eq => Complex { re: truth(a == b)?, im: Fixed::ZERO },
+            Op::Ne => Complex { re: truth(a != b)?, im: Fixed::ZERO },
+            Op::Lt | Op::Le | Op::Gt | Op::Ge => return Err("Ordering of complex numbers"),
+            Op::Mod | Op::FloorDiv | Op::EuclidMod => return Err("Integer division of a complex number"),
+            Op::PlusMinus => return Err("Uncertainties need the uncertain mode"),
+            Op::And | Op::Or | Op::Xor | Op::Shl | Op::Shr | Op::Rotl | Op::Rotr => {
+                return Err("Bitwise operation on a complex number")
+            }
+        };
+        Ok(result)
+    }
+}
+
+/// Complex numbers are made of two `Fixed` of the chosen format,
+/// and only real ones support bitwise operations, integer divisions and ordering
+impl<const FRAC: u32, T: Repr> Operand for Complex<Fixed<FRAC, T>> {
+    fn from_number(num: Number) -> Result<Self, &'static str> {
+        Ok(Complex { re: num.into_fixed()?, im: Fixed::ZERO })
+    }
+
+    fn is_true(&self) -> Result<bool, &'static str> {
+        Ok(self.re != Fixed::ZERO || self.im != Fixed::ZERO)
+    }
+
+    fn from_imaginary(num: Number) -> Result<Self, &'static str> {
+        Ok(Complex { re: Fixed::ZERO, im: num.into_fixed()? })
+    }
+
+    fn apply(op: &Op, a: Self, b: Self, settings: &Settings) -> Result<Self, &'static str> {
+        Complex::operate(op, a, b, settings, Fixed::apply)
+    }
+
+    fn checked(op: &Op, a: Self, b: Self, settings: &Settings) -> Result<Self, &'static str> {
+        Complex::operate(op, a, b, settings, Fixed::checked)
+    }
+
+    /// Sums the real and imaginary parts apart, so that only the result has to be in range
+    fn sum_over(items: Vec<Self>, divisor: u32, settings: &Settings) -> Result<Self, &'static str> {
+        let (re, im) = items.into_iter().map(|z| (z.re, z.im)).unzip();
+        Ok(Complex { re: Fixed::sum_over(re, divisor, settings)?, im: Fixed::sum_over(im, divisor, settings)? })
+    }
+
+    fn call(name: &str, args: Vec<Self>, settings: &Settings) -> Result<Self, &'static str> {
+        if INTEGER_FUNCTIONS.contains(&name) {
+            if !args.iter().all(Complex::is_real) { return Err("Integer function of a complex number") };
+            let re = Fixed::call(name, args.iter().map(|z| z.re).collect(), settings)?;
+            return Ok(Complex { re, im: Fixed::ZERO })
+        };
+        let z = single(args)?;
+        let real = |x: Option<Fixed<FRAC, T>>| Ok(Complex { re: x.ok_or("Result out of range")?, im: Fixed::ZERO });
+        match name {
+            "abs" => real(z.abs(settings.rounding)),
+            "arg" => real(z.arg(settings.rounding)),
+            "re" => real(Some(z.re)),
+            "im" => real(Some(z.im)),
+            "conj" if !T::SIGNED && z.im != Fixed::ZERO => Err("Conjugate in an unsigned format"),
+            "conj" => Ok(Complex { re: z.re, im: Fixed::ZERO - z.im }),
+            "sqrt" => z.sqrt(settings),
+            _ => Err("Unknown function")
+        }
+    }
+
+    fn constant(name: &str, settings: &Settings) -> Result<Self, &'static str> {
+        Ok(Complex { re: Fixed::constant(name, settings)?, im: Fixed::ZERO })
+    }
+}
+
+impl<const FRAC: u32, T: Repr> Complex<Fixed<FRAC, T>> {
+    /// Writes the number as `a+bi`, leaving out the parts that are zero,
+    /// each part being written by `fmt_part`
+    fn fmt_with(&self, f: &mut fmt::Formatter<'_>, fmt_part: fn(&Fixed<FRAC, T>) -> String) -> fmt::Result {
+        let (re, im) = (fmt_part(&self.re), fmt_part(&self.im));
+        match (self.re == Fixed::ZERO, self.im == Fixed::ZERO) {
+            (_, true) => write!(f, "{re}"),
+            (true, false) => write!(f, "{im}i"),
+            (false, false) if im.starts_with('-') => write!(f, "{re}{im}i"),
+            (false, false) => write!(f, "{re}+{im}i")
+        }
+    }
+}
+
+impl<const FRAC: u32, T: Repr> Display for Complex<Fixed<FRAC, T>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(f, |x| format!("{x}"))
+    }
+}
+
+impl<const FRAC: u32, T: Repr> LowerExp for Complex<Fixed<FRAC, T>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(f, |x| format!("{x:e}"))
+    }
+}
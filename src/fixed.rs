@@ -1,21 +1,472 @@
+use crate::ops::{self, Op, Settings};
 use std::{
+    cmp::Ordering,
+    str::FromStr,
     ops::{
         Add, Sub, Mul, Div, Rem, BitAnd, BitOr, BitXor, Shl, Shr
     },
     fmt::{
-        self, Display
+        self, Display, LowerExp, UpperExp
     }
 };
 const U32MAX_FLOAT: f64 = 4_294_967_296_f64;
 
+/// Integer types that can hold the raw content of a `Fixed`
+pub trait Repr: Copy + Eq + Ord + fmt::Debug
+    + Add<Output = Self> + Sub<Output = Self> + Rem<Output = Self>
+    + BitAnd<Output = Self> + BitOr<Output = Self> + BitXor<Output = Self>
+    + Shl<u32, Output = Self> + Shr<u32, Output = Self>
+{
+    const BITS: u32;
+    const SIGNED: bool;
+    const MIN: Self;
+    const MAX: Self;
+    const ZERO: Self;
+    const ONE: Self;
+    fn is_negative(self) -> bool;
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+    fn checked_sub(self, rhs: Self) -> Option<Self>;
+    /// Quotient of the Euclidean division, wrapping around on overflow
+    fn wrapping_div_euclid(self, rhs: Self) -> Self;
+    /// Remainder of the Euclidean division, never negative
+    fn wrapping_rem_euclid(self, rhs: Self) -> Self;
+    fn unsigned_abs(self) -> u128;
+    fn rotate_left(self, bits: u32) -> Self;
+    fn rotate_right(self, bits: u32) -> Self;
+    /// The value with the given sign and absolute value, if it can be represented
+    fn from_magnitude(neg: bool, abs: u128) -> Option<Self>;
+    /// The value with the given sign and absolute value, wrapped around to the range of the type
+    fn wrapping_from_magnitude(neg: bool, abs: u128) -> Self;
+}
+
+macro_rules! impl_repr_signed {
+    ($($int:ty),+) => {
+        $(
+            impl Repr for $int {
+                const BITS: u32 = <$int>::BITS;
+                const SIGNED: bool = true;
+                const MIN: $int = <$int>::MIN;
+                const MAX: $int = <$int>::MAX;
+                const ZERO: $int = 0;
+                const ONE: $int = 1;
+                fn is_negative(self) -> bool { self < 0 }
+                fn checked_add(self, rhs: Self) -> Option<Self> { self.checked_add(rhs) }
+                fn checked_sub(self, rhs: Self) -> Option<Self> { self.checked_sub(rhs) }
+                fn wrapping_div_euclid(self, rhs: Self) -> Self { self.wrapping_div_euclid(rhs) }
+                fn wrapping_rem_euclid(self, rhs: Self) -> Self { self.wrapping_rem_euclid(rhs) }
+                fn unsigned_abs(self) -> u128 { self.unsigned_abs() as u128 }
+                fn rotate_left(self, bits: u32) -> Self { self.rotate_left(bits) }
+                fn rotate_right(self, bits: u32) -> Self { self.rotate_right(bits) }
+                fn from_magnitude(neg: bool, abs: u128) -> Option<Self> {
+                    let limit = 1u128 << (<$int>::BITS - 1);
+                    match neg {
+                        false => (abs < limit).then(|| abs as $int),
+                        true => (abs <= limit).then(|| (abs as i128).wrapping_neg() as $int)
+                    }
+                }
+                fn wrapping_from_magnitude(neg: bool, abs: u128) -> Self {
+                    match neg {
+                        false => abs as $int,
+                        true => (abs as $int).wrapping_neg()
+                    }
+                }
+            }
+        )+
+    };
+}
+
+macro_rules! impl_repr_unsigned {
+    ($($int:ty),+) => {
+        $(
+            impl Repr for $int {
+                const BITS: u32 = <$int>::BITS;
+                const SIGNED: bool = false;
+                const MIN: $int = <$int>::MIN;
+                const MAX: $int = <$int>::MAX;
+                const ZERO: $int = 0;
+                const ONE: $int = 1;
+                fn is_negative(self) -> bool { false }
+                fn checked_add(self, rhs: Self) -> Option<Self> { self.checked_add(rhs) }
+                fn checked_sub(self, rhs: Self) -> Option<Self> { self.checked_sub(rhs) }
+                fn wrapping_div_euclid(self, rhs: Self) -> Self { self.wrapping_div_euclid(rhs) }
+                fn wrapping_rem_euclid(self, rhs: Self) -> Self { self.wrapping_rem_euclid(rhs) }
+                fn unsigned_abs(self) -> u128 { self as u128 }
+                fn rotate_left(self, bits: u32) -> Self { self.rotate_left(bits) }
+                fn rotate_right(self, bits: u32) -> Self { self.rotate_right(bits) }
+                fn from_magnitude(neg: bool, abs: u128) -> Option<Self> {
+                    match neg && abs != 0 {
+                        false => <$int>::try_from(abs).ok(),
+                        true => None
+                    }
+                }
+                fn wrapping_from_magnitude(neg: bool, abs: u128) -> Self {
+                    match neg {
+                        false => abs as $int,
+                        true => (abs as $int).wrapping_neg()
+                    }
+                }
+            }
+        )+
+    };
+}
+
+impl_repr_signed!(i32, i64, i128);
+impl_repr_unsigned!(u32, u64, u128);
+
+/// Full product of two u128, as its (high, low) halves
+fn widening_mul(a: u128, b: u128) -> (u128, u128) {
+    const LOW: u128 = u64::MAX as u128;
+    let (a_hi, a_lo) = (a >> 64, a & LOW);
+    let (b_hi, b_lo) = (b >> 64, b & LOW);
+    let (mid, mid_carry) = (a_lo * b_hi).overflowing_add(a_hi * b_lo);
+    let (low, low_carry) = (a_lo * b_lo).overflowing_add(mid << 64);
+    let high = a_hi * b_hi + (mid >> 64) + ((mid_carry as u128) << 64) + low_carry as u128;
+    (high, low)
+}
+
+/// Lowest 128 bits of the quotient of a (high, low) number by a u128, and the remainder
+fn wide_divrem(high: u128, low: u128, rhs: u128) -> (u128, u128) {
+    if rhs == 0 { panic!("attempt to divide by zero") };
+    let mut quotient = 0;
+    let mut remainder = 0u128;
+    for i in (0..256).rev() {
+        let bit = match i >= 128 {
+            true => (high >> (i - 128)) & 1,
+            false => (low >> i) & 1
+        };
+        let overflow = remainder >> 127 == 1;
+        remainder = (remainder << 1) | bit;
+        quotient <<= 1;
+        if overflow || remainder >= rhs {
+            remainder = remainder.wrapping_sub(rhs);
+            quotient |= 1;
+        };
+    };
+    (quotient, remainder)
+}
+
+/// `(high, low) >> shift`, truncated to 128 bits
+fn wide_shr(high: u128, low: u128, shift: u32) -> u128 {
+    match shift {
+        0 => low,
+        _ => (low >> shift) | (high << (128 - shift))
+    }
+}
+
+/// `abs << shift`, as its (high, low) halves
+fn wide_shl(abs: u128, shift: u32) -> (u128, u128) {
+    match shift {
+        0 => (0, abs),
+        _ => (abs >> (128 - shift), abs << shift)
+    }
+}
+
+/// `(a * b) >> shift` for magnitudes, rounded with `mode` and truncated to 128 bits,
+/// and whether it was truncated
+/// `neg` is the sign of the product
+fn mul_shr_round(a: u128, b: u128, shift: u32, neg: bool, mode: RoundingMode) -> (u128, bool) {
+    let (high, low) = widening_mul(a, b);
+    let quotient = wide_shr(high, low, shift);
+    let (half, inexact) = match shift {
+        0 => (Ordering::Less, false),
+        _ => {
+            let lost_bits = low & ((1 << shift) - 1);
+            (lost_bits.cmp(&(1 << (shift - 1))), lost_bits != 0)
+        }
+    };
+    let (rounded, carry) = quotient.overflowing_add(mode.round_up(neg, quotient & 1 == 1, half, inexact) as u128);
+    (rounded, carry || high >> shift != 0)
+}
+
+/// `(a << shift) / b` for magnitudes, rounded with `mode` and truncated to 128 bits,
+/// and whether it was truncated
+/// `neg` is the sign of the quotient
+fn shl_div_round(a: u128, b: u128, shift: u32, neg: bool, mode: RoundingMode) -> (u128, bool) {
+    let (high, low) = wide_shl(a, shift);
+    let (quotient, remainder) = wide_divrem(high, low, b);
+    let half = remainder.cmp(&(b - remainder));
+    let (rounded, carry) = quotient.overflowing_add(mode.round_up(neg, quotient & 1 == 1, half, remainder != 0) as u128);
+    (rounded, carry || high >= b)
+}
+
+/// Square root of a (high, low) number, rounded with `mode`
+/// The number must be below 2^255
+fn wide_sqrt_round(high: u128, low: u128, mode: RoundingMode) -> u128 {
+    if high == 0 && low == 0 { return 0 };
+    let bits = match high {
+        0 => 128 - low.leading_zeros(),
+        _ => 256 - high.leading_zeros()
+    };
+    // Newton's method, from above the root
+    let mut root = match bits >= 255 {
+        true => u128::MAX,
+        false => 1 << bits.div_ceil(2)
+    };
+    loop {
+        let (quotient, _) = wide_divrem(high, low, root);
+        if quotient >= root { break };
+        root = (root >> 1) + (quotient >> 1) + (root & quotient & 1);
+    };
+    let (square_high, square_low) = widening_mul(root, root);
+    let (rem_low, borrow) = low.overflowing_sub(square_low);
+    let rem_high = high - square_high - borrow as u128;
+    // a square root is never halfway between two integers
+    let half = match rem_high == 0 && rem_low <= root {
+        true => Ordering::Less,
+        false => Ordering::Greater
+    };
+    root + mode.round_up(false, root & 1 == 1, half, rem_high != 0 || rem_low != 0) as u128
+}
+
+/// Working format of `atan2`, with 3 bits of whole part for angles up to pi
+type Angle = Fixed<124, i128>;
+
+/// Arctangent of a small `t`, with its Taylor series
+fn atan_series(t: Angle) -> Angle {
+    let square = t * t;
+    let (mut term, mut sum, mut n) = (t, t, 1);
+    while term.0 != 0 {
+        term = Fixed(0) - term * square;
+        sum = sum + Fixed(term.0 / (2 * n + 1));
+        n += 1;
+    };
+    sum
+}
+
+/// Arctangent of `t` between 0 and 1
+fn atan_unit(mut t: Angle) -> Angle {
+    let one = Fixed(1 << 124);
+    // atan(t) = 2 * atan(t / (1 + sqrt(1 + t^2))), three times
+    for _ in 0..3 {
+        let root = (one + t * t).sqrt(RoundingMode::NearestEven).unwrap();
+        t = t / (one + root);
+    };
+    Fixed(atan_series(t).0 << 3)
+}
+
+/// pi, by Machin's formula pi = 16 * atan(1/5) - 4 * atan(1/239)
+fn pi() -> Angle {
+    let atan_inverse = |n: i128| atan_series(Fixed((1 << 124) / n));
+    Fixed((atan_inverse(5).0 << 4) - (atan_inverse(239).0 << 2))
+}
+
+/// How a value is rounded when it falls between two representable numbers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RoundingMode {
+    #[default]
+    NearestEven,
+    NearestAway,
+    TowardZero,
+    Floor,
+    Ceil
+}
+
+impl RoundingMode {
+    /// Whether a magnitude that was truncated toward zero must be incremented
+    /// `half` compares the discarded part to one half of the last unit kept,
+    /// `inexact` tells whether the discarded part is nonzero
+    pub fn round_up(self, neg: bool, odd: bool, half: Ordering, inexact: bool) -> bool {
+        match self {
+            RoundingMode::NearestEven => half == Ordering::Greater || (half == Ordering::Equal && odd),
+            RoundingMode::NearestAway => half != Ordering::Less,
+            RoundingMode::TowardZero => false,
+            RoundingMode::Floor => inexact && neg,
+            RoundingMode::Ceil => inexact && !neg
+        }
+    }
+}
+
+impl FromStr for RoundingMode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "nearest-even" => Ok(RoundingMode::NearestEven),
+            "nearest-away" => Ok(RoundingMode::NearestAway),
+            "toward-zero" => Ok(RoundingMode::TowardZero),
+            "floor" => Ok(RoundingMode::Floor),
+            "ceil" => Ok(RoundingMode::Ceil),
+            other => Err(format!("Unknown rounding mode '{other}'"))
+        }
+    }
+}
+
+/// A fixed-point number stored in the integer `T`, with `FRAC` bits of decimal part
+/// `FRAC` must be lower than the number of bits of `T`, and at most 124
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub struct Fixed(i64);
+pub struct Fixed<const FRAC: u32 = 32, T = i64>(T);
+
+/// The unsigned counterpart of `Fixed`, by default stored in a u64 with 32 bits of decimal part
+#[allow(unused)]
+pub type UFixed<const FRAC: u32 = 32, T = u64> = Fixed<FRAC, T>;
+
+#[allow(unused)]
+impl<const FRAC: u32, T: Repr> Fixed<FRAC, T> {
+    pub const MIN: Self = Fixed(T::MIN);
+    pub const MAX: Self = Fixed(T::MAX);
+    pub const EPSILON: Self = Fixed(T::ONE);
+    pub const ZERO: Self = Fixed(T::ZERO);
+    pub fn from_raw(x: T) -> Self {
+        Fixed(x)
+    }
+    pub fn as_raw(&self) -> T { self.0 }
+
+    /// One, if it is in range
+    pub fn one() -> Option<Self> {
+        T::from_magnitude(false, 1 << FRAC).map(Fixed)
+    }
+
+    /// The product, rounded to the format with `mode`
+    pub fn mul_round(self, rhs: Self, mode: RoundingMode) -> Self {
+        let neg = self.0.is_negative() != rhs.0.is_negative();
+        let (abs, _) = mul_shr_round(self.0.unsigned_abs(), rhs.0.unsigned_abs(), FRAC, neg, mode);
+        Fixed(T::wrapping_from_magnitude(neg, abs))
+    }
+
+    /// The product, rounded to the format with `mode`, if it is in range
+    pub fn checked_mul_round(self, rhs: Self, mode: RoundingMode) -> Option<Self> {
+        let neg = self.0.is_negative() != rhs.0.is_negative();
+        match mul_shr_round(self.0.unsigned_abs(), rhs.0.unsigned_abs(), FRAC, neg, mode) {
+            (_, true) => None,
+            (abs, false) => T::from_magnitude(neg, abs).map(Fixed)
+        }
+    }
+
+    /// The integer `q` such that `self = rhs * q + r`, with `0 <= r < |rhs|`
+    /// Panics when `rhs` is zero
+    pub fn div_euclid(self, rhs: Self) -> Self {
+        Fixed(self.0.wrapping_div_euclid(rhs.0) << FRAC)
+    }
+
+    /// The remainder of `div_euclid`, which is never negative
+    /// Panics when `rhs` is zero
+    pub fn rem_euclid(self, rhs: Self) -> Self {
+        Fixed(self.0.wrapping_rem_euclid(rhs.0))
+    }
+
+    /// The quotient rounded toward negative infinity, as done by `//`
+    /// Panics when `rhs` is zero
+    pub fn div_floor(self, rhs: Self) -> Self {
+        let quotient = self.0.wrapping_div_euclid(rhs.0);
+        match rhs.0.is_negative() && self.0.wrapping_rem_euclid(rhs.0) != T::ZERO {
+            true => Fixed((quotient - T::ONE) << FRAC),
+            false => Fixed(quotient << FRAC)
+        }
+    }
+
+    /// The absolute value, wrapping around for the minimum of signed formats
+    pub fn abs(self) -> Self {
+        Fixed(T::wrapping_from_magnitude(false, self.0.unsigned_abs()))
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.0.unsigned_abs() & ((1 << FRAC) - 1) == 0
+    }
+
+    /// The sign and the absolute value of the whole part
+    pub fn whole_magnitude(&self) -> (bool, u128) {
+        (self.0.is_negative(), self.0.unsigned_abs() >> FRAC)
+    }
+
+    /// The integer in the direction given by `mode`
+    pub fn round(self, mode: RoundingMode) -> Self {
+        let (neg, abs) = (self.0.is_negative(), self.0.unsigned_abs());
+        let (whole, lost_bits) = (abs >> FRAC, abs & ((1 << FRAC) - 1));
+        let half = lost_bits.cmp(&(1 << (FRAC - 1)));
+        let whole = whole + mode.round_up(neg, whole & 1 == 1, half, lost_bits != 0) as u128;
+        Fixed(T::wrapping_from_magnitude(neg, whole << FRAC))
+    }
+
+    /// Shifts the raw content left, every bit being shifted out from `T::BITS` on
+    pub fn shl_bits(self, bits: u32) -> Self {
+        match bits < T::BITS {
+            true => Fixed(self.0 << bits),
+            false => Fixed(T::ZERO)
+        }
+    }
+
+    /// Shifts the raw content right, keeping the sign,
+    /// so that only the sign is left from `T::BITS` on
+    pub fn shr_bits(self, bits: u32) -> Self {
+        match bits < T::BITS {
+            true => Fixed(self.0 >> bits),
+            false => Fixed(self.0 >> (T::BITS - 1) >> 1)
+        }
+    }
+
+    /// The square root, rounded with `mode`, if `self` is not negative
+    pub fn sqrt(self, mode: RoundingMode) -> Option<Self> {
+        if self.0.is_negative() { return None };
+        let (high, low) = wide_shl(self.0.unsigned_abs(), FRAC);
+        T::from_magnitude(false, wide_sqrt_round(high, low, mode)).map(Fixed)
+    }
+
+    /// `sqrt(self^2 + rhs^2)`, rounded with `mode`, if it is in range
+    pub fn hypot(self, rhs: Self, mode: RoundingMode) -> Option<Self> {
+        let (a_high, a_low) = widening_mul(self.0.unsigned_abs(), self.0.unsigned_abs());
+        let (b_high, b_low) = widening_mul(rhs.0.unsigned_abs(), rhs.0.unsigned_abs());
+        let (low, carry) = a_low.overflowing_add(b_low);
+        let root = wide_sqrt_round(a_high + b_high + carry as u128, low, mode);
+        T::from_magnitude(false, root).map(Fixed)
+    }
+
+    /// Pi, rounded with `mode`, if it is in range
+    pub fn pi(mode: RoundingMode) -> Option<Self> {
+        let (abs, _) = mul_shr_round(pi().0 as u128, 1, 124 - FRAC, false, mode);
+        T::from_magnitude(false, abs).map(Fixed)
+    }
+
+    /// The angle of the point (x, y) = (rhs, self) in radians, between -pi and pi,
+    /// rounded with `mode`, if it is in range
+    pub fn atan2(self, rhs: Self, mode: RoundingMode) -> Option<Self> {
+        let (y, x) = (self.0.unsigned_abs(), rhs.0.unsigned_abs());
+        if x == 0 && y == 0 { return Some(Fixed(T::ZERO)) };
+        let (ratio, _) = shl_div_round(x.min(y), x.max(y), 124, false, RoundingMode::NearestEven);
+        let mut angle = atan_unit(Fixed(ratio as i128));
+        let half_pi = Fixed(pi().0 >> 1);
+        if y > x { angle = half_pi - angle };
+        if rhs.0.is_negative() { angle = half_pi + half_pi - angle };
+        let neg = self.0.is_negative();
+        let (abs, _) = mul_shr_round(angle.0 as u128, 1, 124 - FRAC, neg, mode);
+        T::from_magnitude(neg, abs).map(Fixed)
+    }
+
+    pub fn rotate_left(self, bits: u32) -> Self {
+        Fixed(self.0.rotate_left(bits))
+    }
+
+    pub fn rotate_right(self, bits: u32) -> Self {
+        Fixed(self.0.rotate_right(bits))
+    }
+
+    /// The quotient, rounded to the format with `mode`
+    /// Panics when `rhs` is zero
+    pub fn div_round(self, rhs: Self, mode: RoundingMode) -> Self {
+        let neg = self.0.is_negative() != rhs.0.is_negative();
+        let (abs, _) = shl_div_round(self.0.unsigned_abs(), rhs.0.unsigned_abs(), FRAC, neg, mode);
+        Fixed(T::wrapping_from_magnitude(neg, abs))
+    }
+
+    /// The quotient, rounded to the format with `mode`, if it is in range
+    /// Panics when `rhs` is zero
+    pub fn checked_div_round(self, rhs: Self, mode: RoundingMode) -> Option<Self> {
+        let neg = self.0.is_negative() != rhs.0.is_negative();
+        match shl_div_round(self.0.unsigned_abs(), rhs.0.unsigned_abs(), FRAC, neg, mode) {
+            (_, true) => None,
+            (abs, false) => T::from_magnitude(neg, abs).map(Fixed)
+        }
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Fixed)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(Fixed)
+    }
+}
 
 #[allow(unused)]
 impl Fixed {
-    pub const MIN: Fixed = Fixed(i64::MIN);
-    pub const MAX: Fixed = Fixed(i64::MAX);
-    pub const EPSILON: Fixed = Fixed(1);
     pub fn new(x: i32) -> Self {
         Fixed((x as i64) << 32)
     }
@@ -38,9 +489,9 @@ impl Fixed {
 macro_rules! impl_easy_traits {
     ($(impl $trait:tt (fn $fnname:tt) with $op:tt),+) => {
         $(
-            impl $trait for Fixed {
-                type Output = Fixed;
-                fn $fnname(self, rhs: Self) -> Fixed {
+            impl<const FRAC: u32, T: Repr> $trait for Fixed<FRAC, T> {
+                type Output = Self;
+                fn $fnname(self, rhs: Self) -> Self {
                     Fixed(self.0 $op rhs.0)
                 }
             }
@@ -57,50 +508,157 @@ impl_easy_traits!(
     impl BitXor (fn bitxor) with ^
 );
 
-impl Mul for Fixed {
-    type Output = Fixed;
-    fn mul(self, rhs: Self) -> Fixed {
-        let a = i128::from(self.0);
-        let b = i128::from(rhs.0);
-        Fixed((a*b >> 32) as i64)
+/// Rounds to the nearest value (ties to even)
+impl<const FRAC: u32, T: Repr> Mul for Fixed<FRAC, T> {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        self.mul_round(rhs, RoundingMode::NearestEven)
     }
 }
 
-impl Div for Fixed {
-    type Output = Fixed;
-    fn div(self, rhs: Self) -> Fixed {
-        let a = i128::from(self.0) << 32;
-        let b = i128::from(rhs.0);
-        Fixed((a/b) as i64)
+/// Rounds to the nearest value (ties to even)
+impl<const FRAC: u32, T: Repr> Div for Fixed<FRAC, T> {
+    type Output = Self;
+    fn div(self, rhs: Self) -> Self {
+        self.div_round(rhs, RoundingMode::NearestEven)
     }
 }
 
-impl Shl for Fixed {
-    type Output = Fixed;
-    fn shl(self, rhs: Self) -> Fixed {
-        Fixed(self.0 << (rhs.0 >> 32))
+/// Shifts as the evaluator does with the default settings,
+/// failing on a negative, fractional or too large amount
+impl<const FRAC: u32, T: Repr> Shl for Fixed<FRAC, T> {
+    type Output = Result<Self, &'static str>;
+    fn shl(self, rhs: Self) -> Self::Output {
+        ops::shift(&Op::Shl, self, rhs, &Settings::default())
     }
 }
 
-impl Shr for Fixed {
-    type Output = Fixed;
-    fn shr(self, rhs: Self) -> Fixed {
-        Fixed(self.0 >> (rhs.0 >> 32))
+/// Shifts as the evaluator does with the default settings,
+/// failing on a negative, fractional or too large amount
+impl<const FRAC: u32, T: Repr> Shr for Fixed<FRAC, T> {
+    type Output = Result<Self, &'static str>;
+    fn shr(self, rhs: Self) -> Self::Output {
+        ops::shift(&Op::Shr, self, rhs, &Settings::default())
     }
 }
 
-const BILLION: f64 = 1_000_000_000.0;
-impl Display for Fixed {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        const DECIMAL_RATIO: f64 = (1u64 << 32) as f64;
 
-        let whole = (self.0.abs() >> 32).abs().to_string();
-        let decimal = f64::from(self.0.abs() as u32) / DECIMAL_RATIO;
-        let decimal = match f.precision() {
-            Some(prec) => format!("{:.*}", prec, decimal),
-            None => format!("{}", (decimal * BILLION).round() / BILLION)
+/// Rounds a string of decimal digits to its first `len` digits (half away from zero)
+/// Returns true when the rounding carries out of the first digit
+fn round_digits(digits: &mut Vec<u8>, len: usize) -> bool {
+    let round_up = digits.get(len).is_some_and(|&d| d >= 5);
+    digits.resize(len, 0);
+    if !round_up { return false };
+    for d in digits.iter_mut().rev() {
+        match *d {
+            9 => *d = 0,
+            _ => { *d += 1; return false }
+        };
+    };
+    true
+}
+
+fn digits_to_string(digits: &[u8]) -> String {
+    digits.iter().map(|&d| char::from(b'0' + d)).collect()
+}
+
+impl<const FRAC: u32, T: Repr> Fixed<FRAC, T> {
+    /// Number of decimals shown when no precision is given: as many as
+    /// the decimal part can distinguish, floor(FRAC * log10(2))
+    pub const DEFAULT_DECIMALS: usize = FRAC as usize * 30103 / 100_000;
+
+    /// Exact decimal expansion of the absolute value, as the digits of the whole part
+    /// and the `FRAC` digits of the decimal part
+    fn decimal_digits(&self) -> (Vec<u8>, Vec<u8>) {
+        let abs = self.0.unsigned_abs();
+        let mask = (1u128 << FRAC) - 1;
+        let whole = (abs >> FRAC).to_string().bytes().map(|b| b - b'0').collect();
+        let mut decimal = abs & mask;
+        let decimal = (0..FRAC).map(|_| {
+            decimal *= 10;
+            let digit = (decimal >> FRAC) as u8;
+            decimal &= mask;
+            digit
+        }).collect();
+        (whole, decimal)
+    }
+
+    /// Decimal digits as they are displayed, with the position of the point
+    /// With no precision, trailing zeros of the decimal part are removed
+    fn displayed_digits(&self, precision: Option<usize>) -> (Vec<u8>, usize) {
+        let (whole, decimal) = self.decimal_digits();
+        let mut point = whole.len();
+        let mut digits = [whole, decimal].concat();
+        if round_digits(&mut digits, point + precision.unwrap_or(Self::DEFAULT_DECIMALS)) {
+            digits.insert(0, 1);
+            point += 1;
+        };
+        if precision.is_none() {
+            while digits.len() > point && digits.last() == Some(&0) { digits.pop(); };
         };
+        (digits, point)
+    }
 
-        f.pad_integral(self.0 >= 0, "", &(whole + &decimal[1..]))
+    /// Formats the number as `mantissa e exponent`, with the same digits as `Display`
+    /// when no precision is given, or exactly `precision` decimals in the mantissa
+    fn fmt_scientific(&self, f: &mut fmt::Formatter<'_>, exp_char: char) -> fmt::Result {
+        let (digits, point) = match f.precision() {
+            None => self.displayed_digits(None),
+            Some(_) => {
+                let (whole, decimal) = self.decimal_digits();
+                let point = whole.len();
+                ([whole, decimal].concat(), point)
+            }
+        };
+        let (mantissa, exponent) = match digits.iter().position(|&d| d != 0) {
+            None => (vec![0; 1 + f.precision().unwrap_or(0)], 0),
+            Some(first) => {
+                let mut exponent = point as i32 - first as i32 - 1;
+                let mut mantissa = digits[first..].to_vec();
+                match f.precision() {
+                    Some(prec) => if round_digits(&mut mantissa, prec + 1) {
+                        mantissa.insert(0, 1);
+                        mantissa.pop();
+                        exponent += 1;
+                    },
+                    None => while mantissa.len() > 1 && mantissa.last() == Some(&0) {
+                        mantissa.pop();
+                    }
+                };
+                (mantissa, exponent)
+            }
+        };
+        let mut repr = digits_to_string(&mantissa[..1]);
+        if mantissa.len() > 1 {
+            repr.push('.');
+            repr += &digits_to_string(&mantissa[1..]);
+        };
+        repr.push(exp_char);
+        repr += &exponent.to_string();
+        f.pad_integral(!self.0.is_negative(), "", &repr)
+    }
+}
+
+impl<const FRAC: u32, T: Repr> Display for Fixed<FRAC, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (digits, point) = self.displayed_digits(f.precision());
+        let mut repr = digits_to_string(&digits[..point]);
+        if digits.len() > point {
+            repr.push('.');
+            repr += &digits_to_string(&digits[point..]);
+        };
+        f.pad_integral(!self.0.is_negative(), "", &repr)
+    }
+}
+
+impl<const FRAC: u32, T: Repr> LowerExp for Fixed<FRAC, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_scientific(f, 'e')
+    }
+}
+
+impl<const FRAC: u32, T: Repr> UpperExp for Fixed<FRAC, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_scientific(f, 'E')
     }
 }
@@ -0,0 +1,149 @@
+use crate::parser::{continues_unit, is_name_char};
+
+/// Math glyphs accepted in expressions, with their ASCII spelling
+pub const ALIASES: [(char, &str); 9] = [
+    ('×', "*"), ('·', "*"), ('÷', "/"), ('−', "-"),
+    ('≤', "<="), ('≥', ">="), ('≠', "!="),
+    ('√', "sqrt"), ('π', "pi")
+];
+/// Superscript digits and signs, in the order of `SUPERSCRIPT_ASCII`
+const SUPERSCRIPTS: [char; 12] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹', '⁺', '⁻'];
+const SUPERSCRIPT_ASCII: [char; 12] = ['0', '1', '2', '3', '4', '5', '6', '7', '8', '9', '+', '-'];
+
+/// The character written in superscript by `c`, such as `2` for `²`
+pub fn superscript(c: char) -> Option<char> {
+    SUPERSCRIPTS.iter().position(|&s| s == c).map(|i| SUPERSCRIPT_ASCII[i])
+}
+
+/// Whether `c` is the first character of the ASCII spelling of `glyph`
+pub fn spells(glyph: char, c: char) -> bool {
+    ALIASES.iter().any(|&(alias, ascii)| alias == glyph && ascii.starts_with(c))
+}
+
+/// The index right after the closing parenthesis matching the one at `start`
+fn group_end(chars: &[char], start: usize) -> usize {
+    let mut depth = 0;
+    for (i, &c) in chars.iter().enumerate().skip(start) {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 1 => return i + 1,
+            ')' => depth -= 1,
+            _ => ()
+        };
+    };
+    chars.len()
+}
+
+/// The index right after the operand of a `√` starting at `start`: a literal, a constant,
+/// a function call, a group of parentheses or another root, with its signs
+fn operand_end(chars: &[char], start: usize) -> usize {
+    let mut i = start;
+    while chars.get(i).is_some_and(|&c| matches!(c, '+' | '-' | '−') || c.is_whitespace()) { i += 1 };
+    match chars.get(i) {
+        Some('√') => operand_end(chars, i + 1),
+        Some('π') => i + 1,
+        Some('(') => group_end(chars, i),
+        Some(&c) if is_name_char(c) || c == '.' => {
+            let literal = c.is_ascii_digit() || c == '.';
+            let exponent_markers = match chars.get(i..i + 2) {
+                Some(['0', 'b' | 'o' | 'x']) => ['p', 'P'],
+                _ => ['e', 'E']
+            };
+            i += 1;
+            while let Some(&c) = chars.get(i) {
+                let exponent_sign = literal && matches!(c, '+' | '-' | '−') && exponent_markers.contains(&chars[i - 1]);
+                if !(is_name_char(c) || c == '.' || exponent_sign) { break };
+                i += 1;
+            };
+            match !literal && chars.get(i) == Some(&'(') {
+                true => group_end(chars, i),
+                false => i
+            }
+        },
+        _ => i
+    }
+}
+
+/// Writes `text` after `ascii`, with a space between them when they would otherwise
+/// read as a single word, as `2` and `pi` in `0x2 pi`
+fn push_word(ascii: &mut String, text: &str) {
+    if ascii.ends_with(|c: char| is_name_char(c) || c == '.') { ascii.push(' ') };
+    ascii.push_str(text);
+}
+
+/// Rewrites an expression with ASCII operators, functions and constants, which evaluates
+/// to the same value: `√` takes parentheses, `·` becomes a space between units,
+/// and superscripts become `**n`, or `^n` in units as in `m/s^2`
+/// A word written right after an operand is taken for a unit, as in `3 m²`
+pub fn to_ascii(expr: &str) -> String {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut ascii = String::new();
+    // Whether the last thing written is an operand, after which a word is a unit
+    let mut after_operand = false;
+    // Whether the next word is a unit, written after `to`
+    let mut unit_next = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '√' {
+            let start = (i + 1..chars.len()).find(|&j| !chars[j].is_whitespace()).unwrap_or(chars.len());
+            push_word(&mut ascii, "sqrt");
+            if chars.get(start) == Some(&'(') {
+                i = start;
+                after_operand = false;
+                continue;
+            };
+            let end = operand_end(&chars, start);
+            let operand: String = chars[start..end].iter().collect();
+            ascii.push_str(&format!("({})", to_ascii(&operand)));
+            i = end;
+            after_operand = true;
+        } else if c == 'π' {
+            push_word(&mut ascii, "pi");
+            if chars.get(i + 1).is_some_and(|&next| is_name_char(next)) { ascii.push(' ') };
+            i += 1;
+            after_operand = true;
+        } else if superscript(c).is_some() {
+            ascii.push_str("**");
+            while let Some(digit) = chars.get(i).and_then(|&c| superscript(c)) {
+                ascii.push(digit);
+                i += 1;
+            };
+            after_operand = true;
+        } else if is_name_char(c) || c == '.' {
+            let start = i;
+            while chars.get(i).is_some_and(|&c| is_name_char(c) || c == '.') { i += 1 };
+            let word: String = chars[start..i].iter().collect();
+            let keyword = word == "as" || word == "to";
+            let is_unit = unit_next || (after_operand && c.is_ascii_alphabetic() && !keyword);
+            ascii.push_str(&word);
+            if is_unit {
+                while let Some(&c) = chars.get(i) {
+                    let prev = ascii.chars().last();
+                    match (superscript(c), c) {
+                        (Some(digit), _) => {
+                            if superscript(chars[i - 1]).is_none() { ascii.push('^') };
+                            ascii.push(digit);
+                        },
+                        (None, '·') if chars.get(i + 1).is_some_and(|c| c.is_ascii_alphabetic()) => ascii.push(' '),
+                        (None, c) if continues_unit(c, prev, chars.get(i + 1)) => ascii.push(c),
+                        _ => break
+                    };
+                    i += 1;
+                };
+            };
+            unit_next = word == "to";
+            after_operand = !keyword;
+        } else {
+            match ALIASES.iter().find(|&&(glyph, _)| glyph == c) {
+                Some((_, spelling)) => ascii.push_str(spelling),
+                None => ascii.push(c)
+            };
+            if !c.is_whitespace() {
+                after_operand = c == ')' || (c == '!' && chars.get(i + 1) != Some(&'='));
+            };
+            i += 1;
+        };
+    };
+    ascii
+}
@@ -0,0 +1,184 @@
+use crate::bigint::BigInt;
+use crate::ops::{Op, Operand, Settings, INTEGER_FUNCTIONS, single};
+use crate::parser::Number;
+use std::{
+    str::FromStr,
+    fmt::{
+        self, Display
+    }
+};
+
+/// One of the fixed-width integer types i8, u8, i16, ..., u128
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IntType {
+    bits: u32,
+    signed: bool
+}
+
+impl IntType {
+    /// The value of the type with the same lowest bits, as a cast does
+    pub fn wrap(&self, value: BigInt) -> BigInt {
+        let modulus = BigInt::one().shl(self.bits as usize);
+        let low_bits = value & (&modulus - &BigInt::one());
+        match self.signed && low_bits.bits() as u32 == self.bits {
+            true => low_bits - modulus,
+            false => low_bits
+        }
+    }
+
+    /// Rotates the bits of a value of the type by an amount between 0 and the width
+    fn rotate_left(&self, value: BigInt, bits: &BigInt) -> BigInt {
+        let bits = bits.to_u128().unwrap() as usize % self.bits as usize;
+        let pattern = IntType { signed: false, ..*self }.wrap(value);
+        self.wrap(pattern.shl(bits) | pattern.shr(self.bits as usize - bits))
+    }
+}
+
+impl FromStr for IntType {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let (signed, bits) = match s.split_at_checked(1) {
+            Some(("i", bits)) => (true, bits),
+            Some(("u", bits)) => (false, bits),
+            _ => return Err(())
+        };
+        match bits {
+            "8" | "16" | "32" | "64" | "128" => Ok(IntType { bits: bits.parse().unwrap(), signed }),
+            _ => Err(())
+        }
+    }
+}
+
+impl Display for IntType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", if self.signed { 'i' } else { 'u' }, self.bits)
+    }
+}
+
+/// An integer, either of a fixed-width type or unbounded
+/// Literals are unbounded until they meet a typed value or are cast,
+/// and operations between two of them use the type of `Settings::int_type`
+/// Unbounded values wrap around to the type they are given, as a cast does
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Int {
+    value: BigInt,
+    ty: Option<IntType>
+}
+
+#[allow(unused)]
+impl Int {
+    pub fn value(&self) -> &BigInt { &self.value }
+    pub fn ty(&self) -> Option<IntType> { self.ty }
+
+    /// Gives unbounded values the type `ty`, wrapping around as a cast does
+    pub fn with_default_type(self, ty: Option<IntType>) -> Self {
+        match (self.ty, ty) {
+            (None, Some(ty)) => Int { value: ty.wrap(self.value), ty: Some(ty) },
+            _ => self
+        }
+    }
+
+    /// The value written in the given base (2 to 36)
+    /// Negative values of a fixed-width type are written as their bits in bases 2, 8 and 16
+    pub fn fmt_radix(&self, radix: u32) -> String {
+        match (self.ty, radix) {
+            (Some(ty), 2 | 8 | 16) => IntType { signed: false, ..ty }.wrap(self.value.clone()).fmt_radix(radix),
+            _ => self.value.fmt_radix(radix)
+        }
+    }
+}
+
+impl Operand for Int {
+    fn from_number(num: Number) -> Result<Self, &'static str> {
+        Ok(Int { value: BigInt::from_number(num)?, ty: None })
+    }
+
+    fn is_true(&self) -> Result<bool, &'static str> {
+        Ok(!self.value.is_zero())
+    }
+
+    /// Both operands must have the same type, except for exponents and the amount of shifts
+    /// and rotations
+    /// Shift amounts follow the rules of fixed-point shifts, from the width of the shifted value,
+    /// and rotation amounts are taken modulo that width
+    fn apply(op: &Op, a: Self, b: Self, settings: &Settings) -> Result<Self, &'static str> {
+        let is_shift = matches!(op, Op::Shl | Op::Shr | Op::Rotl | Op::Rotr);
+        let is_amount = is_shift || matches!(op, Op::Pow);
+        let ty = match (a.ty, b.ty) {
+            (Some(x), Some(y)) if x != y && !is_amount => return Err("Mismatched types"),
+            (Some(ty), _) => Some(ty),
+            (None, Some(ty)) if !is_amount => Some(ty),
+            (None, _) => settings.int_type
+        };
+        let ty = match ty {
+            None => return Ok(Int { value: BigInt::apply(op, a.value, b.value, settings)?, ty: None }),
+            Some(ty) => ty
+        };
+        let lhs = ty.wrap(a.value);
+        let (op, rhs) = match op {
+            Op::Shl | Op::Shr => shift_amount(op, b.value, ty, settings)?,
+            Op::Rotl | Op::Rotr => (op, b.value & BigInt::from(ty.bits - 1)),
+            Op::Pow => (op, b.value),
+            _ => (op, ty.wrap(b.value))
+        };
+        let value = match op {
+            Op::Rotl => ty.rotate_left(lhs, &rhs),
+            Op::Rotr => ty.rotate_left(lhs, &(BigInt::from(ty.bits) - rhs)),
+            _ => ty.wrap(BigInt::apply(op, lhs, rhs, settings)?)
+        };
+        Ok(Int { value, ty: Some(ty) })
+    }
+
+    /// `abs` wraps around for the minimum of signed types,
+    /// and integer functions wrap around to the type of their first typed argument,
+    /// or to the type of `Settings::int_type`
+    fn call(name: &str, args: Vec<Self>, settings: &Settings) -> Result<Self, &'static str> {
+        match name {
+            "abs" => {
+                let x = single(args)?;
+                let value = match x.ty {
+                    Some(ty) => ty.wrap(x.value.abs()),
+                    None => x.value.abs()
+                };
+                Ok(Int { value, ty: x.ty })
+            },
+            _ if INTEGER_FUNCTIONS.contains(&name) => {
+                let ty = args.iter().find_map(|x| x.ty).or(settings.int_type);
+                let value = BigInt::call(name, args.into_iter().map(|x| x.value).collect(), settings)?;
+                match ty {
+                    Some(ty) => Ok(Int { value: ty.wrap(value), ty: Some(ty) }),
+                    None => Ok(Int { value, ty: None })
+                }
+            },
+            _ => Err("Unknown function")
+        }
+    }
+
+    fn cast(self, ty: IntType) -> Result<Self, &'static str> {
+        Ok(Int { value: ty.wrap(self.value), ty: Some(ty) })
+    }
+}
+
+/// The direction and amount of a shift of a value of type `ty`, which fail when the amount is
+/// negative or not below the width, unless `Settings::reverse_negative_shifts` reverses the
+/// direction, and `Settings::saturate_shifts` shifts every bit out
+fn shift_amount<'a>(op: &'a Op, amount: BigInt, ty: IntType, settings: &Settings) -> Result<(&'a Op, BigInt), &'static str> {
+    let (op, amount) = match (amount.is_negative(), op) {
+        (false, _) => (op, amount),
+        (true, _) if !settings.reverse_negative_shifts => return Err("Negative shift amount"),
+        (true, Op::Shl) => (&Op::Shr, -amount),
+        (true, _) => (&Op::Shl, -amount)
+    };
+    let width = BigInt::from(ty.bits);
+    match amount >= width {
+        true if !settings.saturate_shifts => Err("Shift amount out of range"),
+        true => Ok((op, width)),
+        false => Ok((op, amount))
+    }
+}
+
+impl Display for Int {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.value, f)
+    }
+}
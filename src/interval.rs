@@ -0,0 +1,215 @@
+use crate::bigint::BigInt;
+use crate::fixed::{Fixed, Repr, RoundingMode};
+use crate::ops::{Op, Operand, Settings, INTEGER_FUNCTIONS, fixed_power, holds, single, truth};
+use std::cmp::Ordering;
+use crate::parser::Number;
+use crate::rational::Rational;
+use std::fmt::{
+    self, Display
+};
+
+const OUT_OF_RANGE: &str = "Result out of range";
+
+/// An operation rounding its result in the given direction, failing when it is out of range
+type Rounded<F> = fn(F, F, RoundingMode) -> Option<F>;
+
+/// A range `[lo, hi]` of values of type `F`, known to contain the exact result
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Interval<F = Fixed> {
+    lo: F,
+    hi: F
+}
+
+#[allow(unused)]
+impl<F: Copy + PartialOrd> Interval<F> {
+    /// Fails when `lo` is greater than `hi`
+    pub fn new(lo: F, hi: F) -> Result<Self, &'static str> {
+        match lo > hi {
+            true => Err("Empty interval"),
+            false => Ok(Interval { lo, hi })
+        }
+    }
+    pub fn point(x: F) -> Self {
+        Interval { lo: x, hi: x }
+    }
+    pub fn lo(&self) -> F { self.lo }
+    pub fn hi(&self) -> F { self.hi }
+    pub fn is_point(&self) -> bool { self.lo == self.hi }
+}
+
+impl<const FRAC: u32, T: Repr> Interval<Fixed<FRAC, T>> {
+    pub fn contains(&self, x: Fixed<FRAC, T>) -> bool {
+        self.lo <= x && x <= self.hi
+    }
+
+    /// The smallest interval containing `f(x, y, mode)` for the four pairs of bounds,
+    /// with `f` rounding its result in the direction of `mode`
+    fn corners(a: Self, b: Self, f: Rounded<Fixed<FRAC, T>>) -> Result<Self, &'static str> {
+        let pairs = [(a.lo, b.lo), (a.lo, b.hi), (a.hi, b.lo), (a.hi, b.hi)];
+        let mut lo = Fixed::MAX;
+        let mut hi = Fixed::MIN;
+        for (x, y) in pairs {
+            lo = lo.min(f(x, y, RoundingMode::Floor).ok_or(OUT_OF_RANGE)?);
+            hi = hi.max(f(x, y, RoundingMode::Ceil).ok_or(OUT_OF_RANGE)?);
+        }
+        Ok(Interval { lo, hi })
+    }
+
+    /// The interval to the whole power `n`, which is monotonic on the magnitudes
+    /// of even powers and on the values of odd ones
+    fn power(&self, n: Fixed<FRAC, T>) -> Result<Self, &'static str> {
+        let neg = |x: Fixed<FRAC, T>| Fixed::ZERO.checked_sub(x).ok_or(OUT_OF_RANGE);
+        let base = match n.whole_magnitude().1 % 2 {
+            0 => self.abs()?,
+            _ => *self
+        };
+        // Rounding the power of the magnitude of a negative bound the other way
+        let bound = |x: Fixed<FRAC, T>, mode, opposite| match x < Fixed::ZERO {
+            false => fixed_power(x, n.abs(), mode),
+            true => neg(fixed_power(neg(x)?, n.abs(), opposite)?)
+        };
+        let result = Interval {
+            lo: bound(base.lo, RoundingMode::Floor, RoundingMode::Ceil)?,
+            hi: bound(base.hi, RoundingMode::Ceil, RoundingMode::Floor)?
+        };
+        match n < Fixed::ZERO {
+            false => Ok(result),
+            true if result.contains(Fixed::ZERO) => Err("Division by an interval containing zero"),
+            true => Interval::corners(Interval::point(Fixed::one().ok_or(OUT_OF_RANGE)?), result, Fixed::checked_div_round)
+        }
+    }
+
+    /// 1 when the comparison holds for all the values of the operands,
+    /// 0 when it holds for none, and an error otherwise
+    fn compare(op: &Op, a: Self, b: Self) -> Result<Self, &'static str> {
+        let possible = [
+            (Ordering::Less, a.lo < b.hi),
+            (Ordering::Equal, a.lo <= b.hi && b.lo <= a.hi),
+            (Ordering::Greater, a.hi > b.lo)
+        ];
+        let mut outcomes = possible.iter().filter(|(_, possible)| *possible).map(|&(ordering, _)| holds(op, ordering));
+        let first = outcomes.next().unwrap();
+        match outcomes.all(|outcome| outcome == first) {
+            true => Ok(Interval::point(truth(first)?)),
+            false => Err("Undecided comparison")
+        }
+    }
+
+    fn abs(&self) -> Result<Self, &'static str> {
+        let neg = |x: Fixed<FRAC, T>| Fixed::ZERO.checked_sub(x).ok_or(OUT_OF_RANGE);
+        match (self.lo >= Fixed::ZERO, self.hi <= Fixed::ZERO) {
+            (true, _) => Ok(*self),
+            (false, true) => Ok(Interval { lo: neg(self.hi)?, hi: neg(self.lo)? }),
+            (false, false) => Ok(Interval { lo: Fixed::ZERO, hi: neg(self.lo)?.max(self.hi) })
+        }
+    }
+
+    fn sqrt(&self) -> Result<Self, &'static str> {
+        let root = |x: Fixed<FRAC, T>, mode| x.sqrt(mode).ok_or("Square root of a negative number");
+        Ok(Interval { lo: root(self.lo, RoundingMode::Floor)?, hi: root(self.hi, RoundingMode::Ceil)? })
+    }
+
+    /// The middle of the interval and the distance from it to both bounds,
+    /// written with `decimals` digits after the point at most,
+    /// the distance being rounded up to still cover the interval
+    pub fn midpoint_radius(&self, decimals: usize) -> (String, String) {
+        let sub = |a, b| Rational::apply(&Op::Sub, a, b, &Settings::default()).unwrap();
+        let (lo, hi) = (Rational::from_fixed(self.lo), Rational::from_fixed(self.hi));
+        let sum = Rational::apply(&Op::Add, lo.clone(), hi.clone(), &Settings::default()).unwrap();
+        let mid = Rational::new(sum.numer().clone(), sum.denom() * &BigInt::from(2u32));
+        let mid = mid.round_decimal(decimals, RoundingMode::NearestEven);
+        let (above, below) = (sub(hi, mid.clone()), sub(mid.clone(), lo));
+        let radius = match sub(above.clone(), below.clone()).numer().is_negative() {
+            true => below,
+            false => above
+        };
+        (
+            mid.to_decimal_string(decimals, RoundingMode::NearestEven),
+            radius.to_decimal_string(decimals, RoundingMode::Ceil)
+        )
+    }
+}
+
+/// Literals are rounded outward, and so is the result of each operation,
+/// which only fails when a bound goes out of range
+/// Operations other than `+ - * / ±` need exact operands, and `a ± b` is `[a - b, a + b]`
+impl<const FRAC: u32, T: Repr> Operand for Interval<Fixed<FRAC, T>> {
+    fn from_number(num: Number) -> Result<Self, &'static str> {
+        let (numer, denom) = num.into_fraction()?;
+        let exact = Rational::new(numer, denom);
+        Ok(Interval {
+            lo: exact.to_fixed(RoundingMode::Floor).ok_or(OUT_OF_RANGE)?,
+            hi: exact.to_fixed(RoundingMode::Ceil).ok_or(OUT_OF_RANGE)?
+        })
+    }
+
+    fn is_true(&self) -> Result<bool, &'static str> {
+        match (self.lo > Fixed::ZERO || self.hi < Fixed::ZERO, self.lo == self.hi) {
+            (true, _) => Ok(true),
+            (false, true) => Ok(false),
+            (false, false) => Err("Undecided condition")
+        }
+    }
+
+    fn apply(op: &Op, a: Self, b: Self, settings: &Settings) -> Result<Self, &'static str> {
+        match op {
+            Op::Add => Ok(Interval {
+                lo: a.lo.checked_add(b.lo).ok_or(OUT_OF_RANGE)?,
+                hi: a.hi.checked_add(b.hi).ok_or(OUT_OF_RANGE)?
+            }),
+            Op::Sub => Ok(Interval {
+                lo: a.lo.checked_sub(b.hi).ok_or(OUT_OF_RANGE)?,
+                hi: a.hi.checked_sub(b.lo).ok_or(OUT_OF_RANGE)?
+            }),
+            Op::PlusMinus if b.lo < Fixed::ZERO => Err("Negative uncertainty"),
+            Op::PlusMinus => Ok(Interval {
+                lo: a.lo.checked_sub(b.hi).ok_or(OUT_OF_RANGE)?,
+                hi: a.hi.checked_add(b.hi).ok_or(OUT_OF_RANGE)?
+            }),
+            Op::Mul | Op::ImplicitMul => Interval::corners(a, b, Fixed::checked_mul_round),
+            Op::Div if b.contains(Fixed::ZERO) => Err("Division by an interval containing zero"),
+            Op::Div => Interval::corners(a, b, Fixed::checked_div_round),
+            Op::Pow if !b.is_point() => Err("Inexact operand"),
+            Op::Pow => a.power(b.lo),
+            Op::Lt | Op::Le | Op::Gt | Op::Ge | Op::Eq | Op::Ne => Interval::compare(op, a, b),
+            _ if a.is_point() && b.is_point() => Ok(Interval::point(Fixed::apply(op, a.lo, b.lo, settings)?)),
+            _ => Err("Inexact operand")
+        }
+    }
+
+    fn call(name: &str, args: Vec<Self>, settings: &Settings) -> Result<Self, &'static str> {
+        if INTEGER_FUNCTIONS.contains(&name) {
+            if !args.iter().all(Interval::is_point) { return Err("Inexact operand") };
+            return Ok(Interval::point(Fixed::call(name, args.iter().map(|x| x.lo).collect(), settings)?))
+        };
+        let x = single(args)?;
+        match name {
+            "abs" => x.abs(),
+            "sqrt" => x.sqrt(),
+            "lo" => Ok(Interval::point(x.lo)),
+            "hi" => Ok(Interval::point(x.hi)),
+            _ => Err("Unknown function")
+        }
+    }
+
+    /// Irrational constants are rounded outward
+    fn constant(name: &str, _settings: &Settings) -> Result<Self, &'static str> {
+        let bound = |mode: RoundingMode| Fixed::constant(name, &Settings { rounding: mode, ..Settings::default() });
+        Ok(Interval { lo: bound(RoundingMode::Floor)?, hi: bound(RoundingMode::Ceil)? })
+    }
+}
+
+/// Writes `[lo, hi]` with the bounds rounded outward, or a single number when both round to it
+/// A point interval is exact, and is written as its `Fixed` is
+impl<const FRAC: u32, T: Repr> Display for Interval<Fixed<FRAC, T>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_point() { return write!(f, "{}", self.lo) };
+        let decimals = Fixed::<FRAC, T>::DEFAULT_DECIMALS;
+        let lo = Rational::from_fixed(self.lo).to_decimal_string(decimals, RoundingMode::Floor);
+        let hi = Rational::from_fixed(self.hi).to_decimal_string(decimals, RoundingMode::Ceil);
+        match lo == hi {
+            true => write!(f, "{lo}"),
+            false => write!(f, "[{lo}, {hi}]")
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use crate::int::IntType;
+use crate::matrix;
+use crate::ops::{Op, Operand, Settings, pair, single};
+use crate::parser::Number;
+use crate::stats;
+use std::fmt::{self, Display};
+
+/// Lists made by `range` are limited, so that they stay reasonably small
+const MAX_RANGE: usize = 1_000_000;
+
+/// A value of type `V`, or a list of values, as `[1, 2, 3]`
+/// Operators between a list and a value apply to each item, and between two lists to their
+/// items pairwise, as do functions of one argument, except `*` with a matrix, which is a matrix product
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value<V> {
+    Single(V),
+    List(Vec<Value<V>>)
+}
+
+#[allow(unused)]
+impl<V: Operand> Value<V> {
+    /// Applies `f` to each value of a list, or to a single value
+    pub fn map(self, f: &mut impl FnMut(V) -> Result<V, &'static str>) -> Result<Self, &'static str> {
+        match self {
+            Value::Single(x) => Ok(Value::Single(f(x)?)),
+            Value::List(items) => items.into_iter().map(|item| item.map(f)).collect::<Result<_, _>>().map(Value::List)
+        }
+    }
+
+    /// The items of the only argument when it is a list, and else the arguments
+    fn items(mut args: Vec<Self>) -> Vec<Self> {
+        match args.as_slice() {
+            [Value::List(_)] => match args.pop() {
+                Some(Value::List(items)) => items,
+                _ => unreachable!()
+            },
+            _ => args
+        }
+    }
+
+    /// The arguments as single values, failing on a list
+    fn singles(args: Vec<Self>) -> Result<Vec<V>, &'static str> {
+        args.into_iter().map(|arg| match arg {
+            Value::Single(x) => Ok(x),
+            Value::List(_) => Err("List as an argument")
+        }).collect()
+    }
+
+    /// The numbers of a list argument
+    fn numbers(value: Self) -> Result<Vec<V>, &'static str> {
+        Self::singles(Self::items(vec![value]))
+    }
+
+    fn count(n: usize) -> Result<Self, &'static str> {
+        let n = u32::try_from(n).map_err(|_| "Result out of range")?;
+        Ok(Value::Single(V::from_number(Number::whole(n, (' ', 0)))?))
+    }
+
+    /// Whether `a` is below `b`
+    fn below(a: &Self, b: &Self, settings: &Settings) -> Result<bool, &'static str> {
+        Self::apply(&Op::Lt, a.clone(), b.clone(), settings)?.is_true()
+    }
+
+    /// Combines the items with `op`, from the first one, failing when a result is out of range
+    fn fold(op: Op, items: Vec<Self>, settings: &Settings) -> Result<Self, &'static str> {
+        let mut items = items.into_iter();
+        let first = items.next().ok_or("Empty list")?;
+        items.try_fold(first, |acc, item| Self::checked(&op, acc, item, settings))
+    }
+
+    /// Applies a binary operator, with `apply` applying it to single values
+    fn operate(
+        op: &Op, a: Self, b: Self, settings: &Settings, apply: fn(&Op, V, V, &Settings) -> Result<V, &'static str>
+    ) -> Result<Self, &'static str> {
+        let operate = |a, b| Self::operate(op, a, b, settings, apply);
+        match (a, b) {
+            (a, b) if matches!(op, Op::Mul) && matrix::multiplies(&a, &b) => matrix::product(&a, &b, settings),
+            (Value::Single(a), Value::Single(b)) => Ok(Value::Single(apply(op, a, b, settings)?)),
+            (Value::List(a), Value::List(b)) if a.len() != b.len() => Err("Mismatched lengths"),
+            (Value::List(a), Value::List(b)) => {
+                a.into_iter().zip(b).map(|(a, b)| operate(a, b)).collect::<Result<_, _>>().map(Value::List)
+            },
+            (Value::List(a), b) => {
+                a.into_iter().map(|a| operate(a, b.clone())).collect::<Result<_, _>>().map(Value::List)
+            },
+            (a, Value::List(b)) => {
+                b.into_iter().map(|b| operate(a.clone(), b)).collect::<Result<_, _>>().map(Value::List)
+            }
+        }
+    }
+
+    /// The numbers from `start` by steps of `step`, up to `end` excluded
+    fn range(start: V, end: V, step: V, settings: &Settings) -> Result<Self, &'static str> {
+        let (start, end, step) = (Value::Single(start), Value::Single(end), Value::Single(step));
+        let zero = Self::apply(&Op::Sub, step.clone(), step.clone(), settings)?;
+        let ascending = match (Self::below(&zero, &step, settings)?, Self::below(&step, &zero, settings)?) {
+            (false, false) => return Err("Zero step"),
+            (ascending, _) => ascending
+        };
+        let mut items = Vec::new();
+        let mut x = start;
+        while match ascending {
+            true => Self::below(&x, &end, settings)?,
+            false => Self::below(&end, &x, settings)?
+        } {
+            if items.len() == MAX_RANGE { return Err("List too long") };
+            items.push(x.clone());
+            x = Self::apply(&Op::Add, x, step.clone(), settings)?;
+        };
+        Ok(Value::List(items))
+    }
+}
+
+impl<V: Operand> Operand for Value<V> {
+    fn from_number(num: Number) -> Result<Self, &'static str> {
+        Ok(Value::Single(V::from_number(num)?))
+    }
+
+    fn is_true(&self) -> Result<bool, &'static str> {
+        match self {
+            Value::Single(x) => x.is_true(),
+            Value::List(_) => Err("List as a condition")
+        }
+    }
+
+    fn apply(op: &Op, a: Self, b: Self, settings: &Settings) -> Result<Self, &'static str> {
+        Self::operate(op, a, b, settings, V::apply)
+    }
+
+    fn checked(op: &Op, a: Self, b: Self, settings: &Settings) -> Result<Self, &'static str> {
+        Self::operate(op, a, b, settings, V::checked)
+    }
+
+    fn from_imaginary(num: Number) -> Result<Self, &'static str> {
+        Ok(Value::Single(V::from_imaginary(num)?))
+    }
+
+    fn call(name: &str, args: Vec<Self>, settings: &Settings) -> Result<Self, &'static str> {
+        match name {
+            "len" => Self::count(Self::items(args).len()),
+            "sum" | "mean" => {
+                let items = Self::items(args);
+                let count = Self::count(items.len())?;
+                match (items.iter().all(|item| matches!(item, Value::Single(_))), name) {
+                    // Numbers are summed by their type, which can do it exactly
+                    (true, "sum") => Ok(Value::Single(V::sum_over(Self::singles(items)?, 1, settings)?)),
+                    (true, _) => Ok(Value::Single(stats::mean(Self::singles(items)?, settings)?)),
+                    (false, "sum") => Self::fold(Op::Add, items, settings),
+                    (false, _) => Self::apply(&Op::Div, Self::fold(Op::Add, items, settings)?, count, settings)
+                }
+            },
+            "product" => Self::fold(Op::Mul, Self::items(args), settings),
+            "median" | "mode" | "variance" | "pvariance" | "stddev" | "pstddev" => {
+                let xs = Self::singles(Self::items(args))?;
+                Ok(Value::Single(match name {
+                    "median" => stats::median(xs, settings)?,
+                    "mode" => stats::mode(xs, settings)?,
+                    "variance" => stats::variance(xs, true, settings)?,
+                    "pvariance" => stats::variance(xs, false, settings)?,
+                    "stddev" => stats::stddev(xs, true, settings)?,
+                    _ => stats::stddev(xs, false, settings)?
+                }))
+            },
+            "percentile" => {
+                let (xs, p) = pair(args)?;
+                Ok(Value::Single(stats::percentile(Self::numbers(xs)?, single(Self::singles(vec![p])?)?, settings)?))
+            },
+            "covariance" | "correlation" | "linreg" => {
+                let (xs, ys) = pair(args)?;
+                let (xs, ys) = (Self::numbers(xs)?, Self::numbers(ys)?);
+                match name {
+                    "covariance" => Ok(Value::Single(stats::covariance(xs, ys, settings)?)),
+                    "correlation" => Ok(Value::Single(stats::correlation(xs, ys, settings)?)),
+                    _ => {
+                        let (slope, intercept) = stats::linreg(xs, ys, settings)?;
+                        Ok(Value::List(vec![Value::Single(slope), Value::Single(intercept)]))
+                    }
+                }
+            },
+            "min" | "max" => {
+                let mut items = Self::items(args).into_iter();
+                let first = items.next().ok_or("Empty list")?;
+                items.try_fold(first, |best, item| {
+                    let better = match name {
+                        "min" => Self::below(&item, &best, settings)?,
+                        _ => Self::below(&best, &item, settings)?
+                    };
+                    Ok(if better { item } else { best })
+                })
+            },
+            "transpose" => matrix::transpose(&single(args)?),
+            "det" => Ok(Value::Single(matrix::determinant(&single(args)?, settings)?)),
+            "inverse" => matrix::inverse(&single(args)?, settings),
+            "identity" => matrix::identity(single(Self::singles(args)?)?, settings),
+            "solve" => {
+                let (a, b) = pair(args)?;
+                matrix::solve(&a, &b, settings)
+            },
+            "range" => {
+                let args = Self::singles(args)?;
+                let one = || V::from_number(Number::whole(1, (' ', 0)));
+                match <[V; 3]>::try_from(args) {
+                    Ok([start, end, step]) => Self::range(start, end, step, settings),
+                    Err(args) => match <[V; 2]>::try_from(args) {
+                        Ok([start, end]) => Self::range(start, end, one()?, settings),
+                        Err(args) => {
+                            let end = single(args)?;
+                            Self::range(V::from_number(Number::whole(0, (' ', 0)))?, end, one()?, settings)
+                        }
+                    }
+                }
+            },
+            _ => match <[Self; 1]>::try_from(args) {
+                // Functions of one argument apply to each item of a list
+                Ok([x]) => x.map(&mut |x| V::call(name, vec![x], settings)),
+                Err(args) => Ok(Value::Single(V::call(name, Self::singles(args)?, settings)?))
+            }
+        }
+    }
+
+    fn call_with(
+        name: &str, f: &mut dyn FnMut(Self) -> Result<Self, &'static str>, args: Vec<Self>, settings: &Settings
+    ) -> Result<Self, &'static str> {
+        match (name, args.len()) {
+            // The expression is evaluated for each item of the list
+            ("map", 1) => match single(args)? {
+                Value::List(items) => items.into_iter().map(f).collect::<Result<_, _>>().map(Value::List),
+                x => f(x)
+            },
+            _ => {
+                // The expression of a function of numbers has to give a number
+                let mut f = |x| match f(Value::Single(x))? {
+                    Value::Single(y) => Ok(y),
+                    Value::List(_) => Err("List as a result")
+                };
+                Ok(Value::Single(V::call_with(name, &mut f, Self::singles(args)?, settings)?))
+            }
+        }
+    }
+
+    fn list(items: Vec<Self>) -> Result<Self, &'static str> {
+        Ok(Value::List(items))
+    }
+
+    fn cast(self, ty: IntType) -> Result<Self, &'static str> {
+        self.map(&mut |x| x.cast(ty))
+    }
+
+    fn constant(name: &str, settings: &Settings) -> Result<Self, &'static str> {
+        Ok(Value::Single(V::constant(name, settings)?))
+    }
+
+    fn unit(self, unit: &str, settings: &Settings) -> Result<Self, &'static str> {
+        self.map(&mut |x| x.unit(unit, settings))
+    }
+
+    fn convert(self, unit: &str, settings: &Settings) -> Result<Self, &'static str> {
+        self.map(&mut |x| x.convert(unit, settings))
+    }
+}
+
+impl<V: Display> Display for Value<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Single(x) => write!(f, "{x}"),
+            Value::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 { write!(f, ", ")? };
+                    write!(f, "{item}")?;
+                };
+                write!(f, "]")
+            }
+        }
+    }
+}
@@ -1,18 +1,320 @@
 #[cfg(test)]
 mod tests;
 
+mod bigint;
+mod calculus;
+mod complex;
 mod fixed;
+mod glyphs;
+mod int;
+mod interval;
+mod list;
+mod matrix;
+mod number_theory;
 mod ops;
 mod parser;
+mod rational;
+mod roots;
+mod script;
+mod stats;
+mod uncertain;
+mod units;
+
+use complex::Complex;
+use fixed::{Fixed, Repr, RoundingMode};
+use int::Int;
+use interval::Interval;
+use list::Value;
+use ops::{Juxtaposition, Operand, Settings};
+use rational::Rational;
+use uncertain::Uncertain;
+use units::Quantity;
+
+const USAGE: &str = "USAGE: calcul.exe [OPTIONS] [expr | script.calc]
+    --ascii             print the expression with ASCII operators, functions and constants
+                        instead of evaluating it (× · ÷ − ≤ ≥ ≠ √ π and superscripts are accepted)
+    --sci               print fixed-point results in scientific notation
+    --polar             print complex results as modulus and argument (in radians)
+    --radius            print interval results as midpoint ± radius
+    --print STATEMENTS  last (default), each: which statement values are printed, when the expression
+                        is a script of statements separated by ; or line breaks
+                        (comments run from # to the end of the line, or from /* to */; // is floor division)
+    --steps COUNT       statements and loop conditions a script may run (default 1000000)
+    --strict-percent    % is always the remainder, instead of a percentage when no operand follows it
+                        (200 + 15% is 230, 200 * 15% is 30, 15% is 0.15)
+    --implicit PRECEDENCE
+                        product, tight: juxtaposed operands are multiplied, as 2(3+4), (1+2)(3+4)
+                        or 3pi, with the precedence of * (6/2(1+2) is 9) or before any operator (it is 1)
+                        letters that can continue a literal are part of it, so 0x1f(2) is 0x1f * 2
+    --format FORMAT     q32.32 (default), q15.16, q1.31, q16.48, q64.64
+                        uq32.32, uq16.16, uq1.31, uq16.48, uq64.64 (unsigned)
+    --mode MODE         fixed (default),
+                        rational (exact, printed as a fraction unless --round is given),
+                        complex (fixed-point parts, with the i suffix and abs, arg, sqrt, re, im, conj),
+                        interval (fixed-point bounds rounded outward, with abs, sqrt, lo, hi),
+                        uncertain (values written x ± sigma or x +/- sigma, with abs, sqrt, value, sigma),
+                        units (values with SI or imperial units, as 9.81 m/s^2, converted with 72 km/h to mph),
+                        int (unbounded integers),
+                        i8, u8, i16, u16, i32, u32, i64, u64, i128, u128 (wrapping integers, literals included)
+                        integers can be cast with (u8)x or x as u8
+    --unit NAME=DEF     adds a unit to the units mode, defined by a number and earlier units,
+                        as furlong=\"201.168 m\" (can be repeated)
+    --round ROUNDING    nearest-even (default), nearest-away, toward-zero, floor, ceil:
+                        rounding of fixed-point products and quotients
+    --div DIVISION      trunc (default), floor: rounding of integer division
+    --base BASE         base of integer results, from 2 to 36 (default 10)
+    --shifts RULES      comma-separated rules for fixed-point and fixed-width integer shifts, which fail otherwise:
+                        reverse (negative amounts shift the other way),
+                        saturate (amounts past the width shift out every bit),
+                        round (fractional amounts are rounded)
+                        <<< and >>> rotate
+OPERATORS: + - * / % // %% (floor division and remainder) & | ^ << >> <<< >>> ± +/-
+           ** (power, with a whole exponent), < <= > >= == != (1 when true, 0 when false)
+CONSTANTS: pi (not in the rational and integer modes)
+FUNCTIONS: abs, sqrt, factorial (also n!), nCr, nPr, gcd, lcm, isprime, factor (smallest prime factor)
+LISTS:     [1, 2, 3], with operators and functions of one argument applied to each item ([1, 2] * 2 is [2, 4]),
+           [] (empty), sum, product, mean, min, max, len (of a list, or of their arguments),
+           range(end), range(start, end) and range(start, end, step) (end excluded),
+           map(expr, x, list) (expr evaluated for x set to each item, as map(x**2, x, [1, 2, 3]))
+MATRICES:  [[1, 2], [3, 4]], with * as the matrix product (a list of numbers is a column on the right,
+           and a row on the left), transpose, det, inverse, identity(n), solve(a, b) (x such that a * x = b)
+EQUATIONS: solve(lhs = rhs, x, guess) (Newton's method), root(expr, x, a, b) (a root between a and b,
+           where expr changes sign, by Brent's method), in fixed-point modes
+           powers are written ** (^ is XOR, and fails on the variable), as solve(x**2 - 2 = 0, x, 1)
+CALCULUS:  integrate(expr, x, a, b) (adaptive Simpson's rule), deriv(expr, x, at) (central differences),
+           in fixed-point modes
+STATISTICS: median, mode, variance, stddev (of a sample), pvariance, pstddev (of a population),
+           percentile(list, p), covariance(xs, ys), correlation(xs, ys), linreg(xs, ys) (as [slope, intercept])
+SCRIPTS:   x = expr (also += -= *= /=), if cond { } else if cond { } else { }, while cond { },
+           for i in start..end { } (end excluded), return expr, { } (a block of its own)
+           conditions hold when they aren't 0, and variables assigned in a block are local to it";
+
+enum Mode {
+    Fixed,
+    Complex,
+    Interval,
+    Uncertain,
+    Units,
+    Rational,
+    Int
+}
+
+struct Options {
+    ascii: bool,
+    scientific: bool,
+    polar: bool,
+    radius: bool,
+    print_each: bool,
+    mode: Mode,
+    round: Option<RoundingMode>,
+    base: u32,
+    settings: Settings
+}
+
+fn fmt_fixed<const FRAC: u32, T: Repr>(n: Fixed<FRAC, T>, options: &Options) -> String {
+    match options.scientific {
+        true => format!("{:e}", n),
+        false => format!("{}", n)
+    }
+}
+
+/// The position of the character at `index`, as a line and a column from 1 when the script
+/// has several lines
+fn position(expr: &str, index: usize) -> String {
+    let before: Vec<char> = expr.chars().take(index).collect();
+    match expr.contains('\n') {
+        false => format!("{index}"),
+        true => {
+            let line = before.iter().filter(|&&c| c == '\n').count() + 1;
+            let column = before.iter().rev().take_while(|&&c| c != '\n').count() + 1;
+            format!("line {line}, column {column}")
+        }
+    }
+}
+
+/// The values of the statements to print, which fails as `parser::exec` does when only the last
+/// one is printed and there is none
+fn results<V: Operand>(expr: &str, options: &Options) -> Result<Vec<V>, String> {
+    let error = |(s, c, i)| format!("{s} '{c}' at {}", position(expr, i));
+    let mut values = script::exec_script(expr, &options.settings).map_err(error)?;
+    match options.print_each {
+        true => Ok(values),
+        false => values.pop().map(|last| vec![last]).ok_or_else(|| error(("No value", '\'', expr.chars().count())))
+    }
+}
+
+/// Writes a value, with `fmt` writing each number of its lists
+fn show<V>(value: Value<V>, fmt: &impl Fn(V) -> Result<String, String>) -> Result<String, String> {
+    match value {
+        Value::Single(x) => fmt(x),
+        Value::List(items) => {
+            let items = items.into_iter().map(|item| show(item, fmt)).collect::<Result<Vec<_>, _>>()?;
+            Ok(format!("[{}]", items.join(", ")))
+        }
+    }
+}
+
+/// Prints the values of the statements to print, with `fmt` writing each number
+fn print<V: Operand>(expr: &str, options: &Options, fmt: impl Fn(V) -> Result<String, String>) -> Result<(), String> {
+    for value in results::<Value<V>>(expr, options)? {
+        println!("{}", show(value, &fmt)?);
+    };
+    Ok(())
+}
+
+fn run<const FRAC: u32, T: Repr>(expr: &str, options: &Options) -> Result<(), String> {
+    match options.mode {
+        Mode::Fixed => print(expr, options, |n: Fixed<FRAC, T>| Ok(fmt_fixed(n, options))),
+        Mode::Complex => print(expr, options, |z: Complex<Fixed<FRAC, T>>| {
+            match (options.polar, z.polar(options.settings.rounding)) {
+                (false, _) => match options.scientific {
+                    true => Ok(format!("{:e}", z)),
+                    false => Ok(format!("{}", z))
+                },
+                (true, Some((modulus, arg))) => Ok(format!("{} ∠ {}", modulus, fmt_fixed(arg, options))),
+                (true, None) => Err(format!("Polar form of {z} out of range"))
+            }
+        }),
+        Mode::Interval => print(expr, options, |x: Interval<Fixed<FRAC, T>>| {
+            match options.radius {
+                true => {
+                    let (mid, radius) = x.midpoint_radius(Fixed::<FRAC, T>::DEFAULT_DECIMALS);
+                    Ok(format!("{mid} ± {radius}"))
+                },
+                false => Ok(format!("{}", x))
+            }
+        }),
+        Mode::Uncertain => print(expr, options, |x: Uncertain<Fixed<FRAC, T>>| Ok(format!("{}", x))),
+        Mode::Units => print(expr, options, |x: Quantity<Fixed<FRAC, T>>| Ok(format!("{}", x))),
+        Mode::Int => print(expr, options, |n: Int| {
+            let n = n.with_default_type(options.settings.int_type);
+            Ok(n.fmt_radix(options.base))
+        }),
+        Mode::Rational => print(expr, options, |r: Rational| {
+            match options.round {
+                None => Ok(format!("{}", r)),
+                Some(mode) => match r.to_fixed::<FRAC, T>(mode) {
+                    Some(n) => Ok(fmt_fixed(n, options)),
+                    None => Err(format!("Result {r} out of range"))
+                }
+            }
+        })
+    }
+}
 
 fn main() -> Result<(), String> {
-    let expr = match std::env::args().skip(1).next() {
+    let mut options = Options {
+        ascii: false, scientific: false, polar: false, radius: false, print_each: false, mode: Mode::Fixed, round: None, base: 10, settings: Settings::default()
+    };
+    let mut format = String::from("q32.32");
+    let mut expr = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ascii" => options.ascii = true,
+            "--sci" => options.scientific = true,
+            "--polar" => options.polar = true,
+            "--radius" => options.radius = true,
+            "--strict-percent" => options.settings.strict_percent = true,
+            "--unit" => {
+                let unit = args.next().ok_or(format!("No value given for {arg}.  {USAGE}"))?;
+                let (name, def) = match unit.split_once('=') {
+                    Some((name, def)) if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphabetic()) => (name, def),
+                    _ => return Err(format!("Invalid unit '{unit}'.  {USAGE}"))
+                };
+                let units = &mut options.settings.units;
+                units.push((String::from(name), String::from(def)));
+                if let Err(e) = units::parse_unit(name, units) {
+                    return Err(format!("Invalid unit '{name}': {e}"))
+                };
+            },
+            "--format" | "--mode" | "--round" | "--div" | "--base" | "--shifts" | "--implicit" | "--print" | "--steps" => {
+                let value = match args.next() {
+                    Some(value) => value.to_ascii_lowercase(),
+                    None => return Err(format!("No value given for {arg}.  {USAGE}"))
+                };
+                match arg.as_str() {
+                    "--format" => format = value,
+                    "--mode" => options.mode = match value.as_str() {
+                        "fixed" => Mode::Fixed,
+                        "complex" => Mode::Complex,
+                        "interval" => Mode::Interval,
+                        "uncertain" => Mode::Uncertain,
+                        "units" => Mode::Units,
+                        "rational" => Mode::Rational,
+                        "int" => Mode::Int,
+                        other => match other.parse() {
+                            Ok(ty) => {
+                                options.settings.int_type = Some(ty);
+                                Mode::Int
+                            },
+                            Err(()) => return Err(format!("Unknown mode '{other}'.  {USAGE}"))
+                        }
+                    },
+                    "--div" => options.settings.floor_division = match value.as_str() {
+                        "trunc" => false,
+                        "floor" => true,
+                        other => return Err(format!("Unknown division '{other}'.  {USAGE}"))
+                    },
+                    "--implicit" => options.settings.implicit_multiplication = match value.as_str() {
+                        "product" => Some(Juxtaposition::Product),
+                        "tight" => Some(Juxtaposition::Tight),
+                        other => return Err(format!("Unknown precedence '{other}'.  {USAGE}"))
+                    },
+                    "--print" => options.print_each = match value.as_str() {
+                        "last" => false,
+                        "each" => true,
+                        other => return Err(format!("Unknown statements '{other}'.  {USAGE}"))
+                    },
+                    "--steps" => options.settings.max_steps = match value.parse() {
+                        Ok(steps) => Some(steps),
+                        Err(_) => return Err(format!("Invalid step count '{value}'.  {USAGE}"))
+                    },
+                    "--base" => options.base = match value.parse() {
+                        Ok(base @ 2..=36) => base,
+                        _ => return Err(format!("Invalid base '{value}'.  {USAGE}"))
+                    },
+                    "--shifts" => for rule in value.split(',') {
+                        match rule {
+                            "reverse" => options.settings.reverse_negative_shifts = true,
+                            "saturate" => options.settings.saturate_shifts = true,
+                            "round" => options.settings.round_shift_amounts = true,
+                            other => return Err(format!("Unknown shift rule '{other}'.  {USAGE}"))
+                        };
+                    },
+                    _ => {
+                        let mode = value.parse()?;
+                        options.round = Some(mode);
+                        options.settings.rounding = mode;
+                    }
+                };
+            },
+            _ => expr = Some(arg)
+        };
+    };
+    let expr = match expr {
+        Some(path) if path.ends_with(".calc") => match std::fs::read_to_string(&path) {
+            Ok(script) => script,
+            Err(e) => return Err(format!("Can't read '{path}': {e}"))
+        },
         Some(s) => s,
-        None => return Err("No argument given.  USAGE: calcul.exe [expr]".to_string())
+        None => return Err(format!("No argument given.  {USAGE}"))
     };
-    match parser::exec(&expr) {
-        Ok(n) => println!("{}", n),
-        Err((s, c, i)) => return Err(format!("{s} '{c}' at {i}"))
+    if options.ascii {
+        println!("{}", glyphs::to_ascii(&expr));
+        return Ok(())
     };
-    Ok(())
+    match format.as_str() {
+        "q32.32" => run::<32, i64>(&expr, &options),
+        "q15.16" => run::<16, i32>(&expr, &options),
+        "q1.31" => run::<31, i32>(&expr, &options),
+        "q16.48" => run::<48, i64>(&expr, &options),
+        "q64.64" => run::<64, i128>(&expr, &options),
+        "uq32.32" => run::<32, u64>(&expr, &options),
+        "uq16.16" => run::<16, u32>(&expr, &options),
+        "uq1.31" => run::<31, u32>(&expr, &options),
+        "uq16.48" => run::<48, u64>(&expr, &options),
+        "uq64.64" => run::<64, u128>(&expr, &options),
+        other => Err(format!("Unknown format '{other}'.  {USAGE}"))
+    }
 }
@@ -0,0 +1,209 @@
+use crate::list::Value;
+use crate::ops::{Op, Operand, Settings};
+use crate::parser::Number;
+
+/// Matrices made by `identity` are limited, so that they stay reasonably small
+const MAX_SIZE: usize = 1000;
+
+/// Rows of numbers of the same length
+type Rows<V> = Vec<Vec<V>>;
+
+/// The numbers of a list of numbers, as `[1, 2]`
+fn vector<V: Clone>(value: &Value<V>) -> Option<Vec<V>> {
+    match value {
+        Value::List(items) if !items.is_empty() => items.iter().map(|item| match item {
+            Value::Single(x) => Some(x.clone()),
+            Value::List(_) => None
+        }).collect(),
+        _ => None
+    }
+}
+
+/// The rows of a matrix, written as a list of lists of numbers of the same length,
+/// as `[[1, 2], [3, 4]]`
+fn rows<V: Clone>(value: &Value<V>) -> Option<Rows<V>> {
+    let rows = match value {
+        Value::List(items) if !items.is_empty() => items.iter().map(vector).collect::<Option<Rows<V>>>()?,
+        _ => return None
+    };
+    rows.iter().all(|row| row.len() == rows[0].len()).then_some(rows)
+}
+
+/// The rows of a square matrix
+fn square<V: Clone>(value: &Value<V>, error: &'static str) -> Result<Rows<V>, &'static str> {
+    rows(value).filter(|rows| rows.len() == rows[0].len()).ok_or(error)
+}
+
+fn from_rows<V>(rows: Rows<V>) -> Value<V> {
+    Value::List(rows.into_iter().map(|row| Value::List(row.into_iter().map(Value::Single).collect())).collect())
+}
+
+fn whole<V: Operand>(n: u32) -> Result<V, &'static str> {
+    V::from_number(Number::whole(n, (' ', 0)))
+}
+
+/// The sum of the products of the numbers of `a` and `b`, pairwise
+fn dot<V: Operand>(a: &[V], b: impl Iterator<Item = V>, settings: &Settings) -> Result<V, &'static str> {
+    let mut products = a.iter().zip(b).map(|(x, y)| V::apply(&Op::Mul, x.clone(), y, settings));
+    let first = products.next().unwrap()?;
+    products.try_fold(first, |sum, product| V::apply(&Op::Add, sum, product?, settings))
+}
+
+/// Whether `*` between `a` and `b` is a matrix product, rather than a product of their items:
+/// when one is a matrix and the other a list
+pub fn multiplies<V: Clone>(a: &Value<V>, b: &Value<V>) -> bool {
+    match (a, b) {
+        (Value::List(_), Value::List(_)) => rows(a).is_some() || rows(b).is_some(),
+        _ => false
+    }
+}
+
+/// The matrix product of `a` and `b`, where a list of numbers is a column on the right,
+/// and a row on the left, and gives a list of numbers
+pub fn product<V: Operand>(a: &Value<V>, b: &Value<V>, settings: &Settings) -> Result<Value<V>, &'static str> {
+    let (left, left_vector) = match rows(a) {
+        Some(rows) => (rows, false),
+        None => (vec![vector(a).ok_or("Left operand not a matrix")?], true)
+    };
+    let (right, right_vector) = match rows(b) {
+        Some(rows) => (rows, false),
+        None => (vector(b).ok_or("Right operand not a matrix")?.into_iter().map(|x| vec![x]).collect(), true)
+    };
+    if right.len() != left[0].len() {
+        return Err("Right operand rows don't match left operand columns")
+    };
+    let result = left.iter().map(|row| {
+        (0..right[0].len()).map(|j| dot(row, right.iter().map(|r| r[j].clone()), settings)).collect()
+    }).collect::<Result<Rows<V>, _>>()?;
+    match (left_vector, right_vector) {
+        (false, false) => Ok(from_rows(result)),
+        (true, _) => Ok(Value::List(result.into_iter().next().unwrap().into_iter().map(Value::Single).collect())),
+        (false, true) => Ok(Value::List(result.into_iter().map(|row| Value::Single(row[0].clone())).collect()))
+    }
+}
+
+/// The transpose of a matrix, where a list of numbers is a row
+pub fn transpose<V: Operand>(value: &Value<V>) -> Result<Value<V>, &'static str> {
+    let rows = match (rows(value), vector(value)) {
+        (Some(rows), _) => rows,
+        (None, Some(row)) => vec![row],
+        (None, None) => return Err("Argument not a matrix")
+    };
+    Ok(from_rows((0..rows[0].len()).map(|j| rows.iter().map(|row| row[j].clone()).collect()).collect()))
+}
+
+/// The identity matrix of size `n`
+pub fn identity<V: Operand>(n: V, settings: &Settings) -> Result<Value<V>, &'static str> {
+    let (zero, one) = (whole::<V>(0)?, whole::<V>(1)?);
+    let mut size = 0;
+    let mut k = zero.clone();
+    while V::apply(&Op::Lt, k.clone(), n.clone(), settings)?.is_true()? {
+        if size == MAX_SIZE { return Err("Matrix too large") };
+        size += 1;
+        k = V::apply(&Op::Add, k, one.clone(), settings)?;
+    };
+    if size == 0 || !V::apply(&Op::Eq, k, n, settings)?.is_true()? {
+        return Err("Argument not a size")
+    };
+    Ok(from_rows((0..size).map(|i| (0..size).map(|j| match i == j {
+        true => one.clone(),
+        false => zero.clone()
+    }).collect()).collect()))
+}
+
+/// Whether `a` has a larger magnitude than `b`, when it can be told
+fn larger<V: Operand>(a: &V, b: &V, settings: &Settings) -> bool {
+    let abs = |x: &V| V::call("abs", vec![x.clone()], settings);
+    match (abs(a), abs(b)) {
+        (Ok(a), Ok(b)) => V::apply(&Op::Gt, a, b, settings).and_then(|larger| larger.is_true()).unwrap_or(false),
+        _ => false
+    }
+}
+
+/// The row from `k` with the pivot of column `k` largest in magnitude, or none when they are all zero
+fn pivot<V: Operand>(rows: &Rows<V>, k: usize, settings: &Settings) -> Result<Option<usize>, &'static str> {
+    let mut best = None;
+    for i in k..rows.len() {
+        if rows[i][k].is_true()? && best.is_none_or(|best: usize| larger(&rows[i][k], &rows[best][k], settings)) {
+            best = Some(i);
+        };
+    };
+    Ok(best)
+}
+
+/// The determinant, by fraction-free elimination (Bareiss), whose divisions are exact
+/// for whole entries
+pub fn determinant<V: Operand>(value: &Value<V>, settings: &Settings) -> Result<V, &'static str> {
+    let mut rows = square(value, "Argument not a square matrix")?;
+    let n = rows.len();
+    let mut negative = false;
+    let mut previous = whole::<V>(1)?;
+    for k in 0..n - 1 {
+        match pivot(&rows, k, settings)? {
+            None => return whole(0),
+            Some(p) if p != k => {
+                rows.swap(p, k);
+                negative = !negative;
+            },
+            Some(_) => ()
+        };
+        for i in k + 1..n {
+            for j in k + 1..n {
+                let a = V::apply(&Op::Mul, rows[i][j].clone(), rows[k][k].clone(), settings)?;
+                let b = V::apply(&Op::Mul, rows[i][k].clone(), rows[k][j].clone(), settings)?;
+                rows[i][j] = V::apply(&Op::Div, V::apply(&Op::Sub, a, b, settings)?, previous.clone(), settings)?;
+            };
+        };
+        previous = rows[k][k].clone();
+    };
+    let det = rows[n - 1][n - 1].clone();
+    match negative {
+        true => V::apply(&Op::Sub, whole(0)?, det, settings),
+        false => Ok(det)
+    }
+}
+
+/// Reduces the square matrix on the left of `rows` to the identity, by Gauss-Jordan elimination
+/// with partial pivoting, applying the same operations to the columns on its right
+fn eliminate<V: Operand>(rows: &mut Rows<V>, settings: &Settings) -> Result<(), &'static str> {
+    let n = rows.len();
+    for k in 0..n {
+        let p = pivot(rows, k, settings)?.ok_or("Singular matrix")?;
+        rows.swap(p, k);
+        let pivot = rows[k][k].clone();
+        for x in rows[k].iter_mut() {
+            *x = V::apply(&Op::Div, x.clone(), pivot.clone(), settings)?;
+        };
+        for i in (0..n).filter(|&i| i != k) {
+            let factor = rows[i][k].clone();
+            for j in 0..rows[i].len() {
+                let product = V::apply(&Op::Mul, factor.clone(), rows[k][j].clone(), settings)?;
+                rows[i][j] = V::apply(&Op::Sub, rows[i][j].clone(), product, settings)?;
+            };
+        };
+    };
+    Ok(())
+}
+
+/// The inverse of a square matrix
+pub fn inverse<V: Operand>(value: &Value<V>, settings: &Settings) -> Result<Value<V>, &'static str> {
+    let mut rows = square(value, "Argument not a square matrix")?;
+    let n = rows.len();
+    let (zero, one) = (whole::<V>(0)?, whole::<V>(1)?);
+    for (i, row) in rows.iter_mut().enumerate() {
+        row.extend((0..n).map(|j| if i == j { one.clone() } else { zero.clone() }));
+    };
+    eliminate(&mut rows, settings)?;
+    Ok(from_rows(rows.into_iter().map(|row| row[n..].to_vec()).collect()))
+}
+
+/// The solution `x` of `a * x = b`, for a square matrix `a` and a list of numbers `b`
+pub fn solve<V: Operand>(a: &Value<V>, b: &Value<V>, settings: &Settings) -> Result<Value<V>, &'static str> {
+    let mut rows = square(a, "First argument not a square matrix")?;
+    let b = vector(b).filter(|b| b.len() == rows.len()).ok_or("Second argument not a list of the matrix size")?;
+    for (row, x) in rows.iter_mut().zip(b) {
+        row.push(x);
+    };
+    eliminate(&mut rows, settings)?;
+    Ok(Value::List(rows.into_iter().map(|row| Value::Single(row.last().unwrap().clone())).collect()))
+}
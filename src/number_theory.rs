@@ -0,0 +1,111 @@
+/// The primes tried by trial division before Pollard's rho, which are also
+/// enough witnesses for Miller-Rabin to be exact below 2^64
+const SMALL_PRIMES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+/// n!, if it fits in 128 bits
+pub fn factorial(n: u128) -> Option<u128> {
+    (2..=n).try_fold(1u128, |acc, k| acc.checked_mul(k))
+}
+
+/// The number of ordered choices of k items among n, if it fits in 128 bits
+pub fn permutations(n: u128, k: u128) -> Option<u128> {
+    if k > n { return Some(0) };
+    (n - k + 1..=n).try_fold(1u128, |acc, x| acc.checked_mul(x))
+}
+
+/// The number of choices of k items among n, if it fits in 128 bits
+pub fn binomial(n: u128, k: u128) -> Option<u128> {
+    if k > n { return Some(0) };
+    // C(n, i) * (n - i) is a multiple of i + 1, being (i + 1) * C(n, i + 1),
+    // so what i + 1 doesn't share with C(n, i) divides n - i
+    (0..k.min(n - k)).try_fold(1u128, |acc, i| {
+        let d = gcd(acc, i + 1);
+        (acc / d).checked_mul((n - i) / ((i + 1) / d))
+    })
+}
+
+pub fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    };
+    a
+}
+
+/// The least common multiple, if it fits in 128 bits
+pub fn lcm(a: u128, b: u128) -> Option<u128> {
+    match gcd(a, b) {
+        0 => Some(0),
+        d => (a / d).checked_mul(b)
+    }
+}
+
+fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    (a as u128 * b as u128 % m as u128) as u64
+}
+
+fn pow_mod(mut base: u64, mut exp: u64, m: u64) -> u64 {
+    let mut result = 1;
+    while exp > 0 {
+        if exp & 1 == 1 { result = mul_mod(result, base, m) };
+        base = mul_mod(base, base, m);
+        exp >>= 1;
+    };
+    result
+}
+
+/// Miller-Rabin test, exact for every 64-bit number
+pub fn is_prime(n: u64) -> bool {
+    if n < 2 { return false };
+    if let Some(&p) = SMALL_PRIMES.iter().find(|&&p| n.is_multiple_of(p)) { return n == p };
+    let shift = (n - 1).trailing_zeros();
+    let odd = (n - 1) >> shift;
+    'witness: for a in SMALL_PRIMES {
+        let mut x = pow_mod(a, odd, n);
+        if x == 1 || x == n - 1 { continue };
+        for _ in 1..shift {
+            x = mul_mod(x, x, n);
+            if x == n - 1 { continue 'witness };
+        };
+        return false
+    };
+    true
+}
+
+/// A non-trivial divisor of a composite number without small factors, by Pollard's rho
+fn divisor(n: u64) -> u64 {
+    for c in 1.. {
+        let next = |x| ((mul_mod(x, x, n) as u128 + c as u128) % n as u128) as u64;
+        let (mut x, mut y, mut d) = (2, 2, 1);
+        while d == 1 {
+            x = next(x);
+            y = next(next(y));
+            d = gcd(x.abs_diff(y) as u128, n as u128) as u64;
+        };
+        if d != n { return d };
+    };
+    unreachable!()
+}
+
+/// The prime factors of n, in increasing order and repeated as many times as they divide it
+pub fn prime_factors(mut n: u64) -> Vec<u64> {
+    let mut factors = Vec::new();
+    for p in SMALL_PRIMES {
+        while n.is_multiple_of(p) && n > 1 {
+            factors.push(p);
+            n /= p;
+        };
+    };
+    let mut composites = vec![n];
+    while let Some(n) = composites.pop() {
+        match n {
+            1 => (),
+            n if is_prime(n) => factors.push(n),
+            n => {
+                let d = divisor(n);
+                composites.extend([d, n / d]);
+            }
+        };
+    };
+    factors.sort();
+    factors
+}
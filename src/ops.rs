@@ -1,45 +1,459 @@
-use crate::fixed::Fixed;
+use crate::calculus;
+use crate::fixed::{Fixed, Repr, RoundingMode};
+use crate::int::IntType;
+use crate::number_theory;
+use crate::parser::Number;
+use crate::roots;
+use std::cmp::Ordering;
 
-pub const OP_CHARS: &str = "+-*/%|&^><";
+pub const OP_CHARS: &str = "+-*/%|&^><±=!×·÷−≤≥≠";
 pub enum Op {
+    Lt, Le, Gt, Ge, Eq, Ne,
     Add, Sub,
     Mul, Div, Mod,
+    FloorDiv, EuclidMod,
     And, Or, Xor,
-    Shl, Shr
+    Shl, Shr,
+    Rotl, Rotr,
+    PlusMinus,
+    /// A product written by juxtaposition, as `2(3+4)`, binding tighter than every operator but `**`
+    ImplicitMul,
+    Pow
 }
 
 pub fn precedence(op: &Op) -> i32 {
     match op {
+        Op::Lt => 0,
+        Op::Le => 0,
+        Op::Gt => 0,
+        Op::Ge => 0,
+        Op::Eq => 0,
+        Op::Ne => 0,
         Op::And => 1,
         Op::Or => 1,
         Op::Xor => 1,
         Op::Shl => 1,
         Op::Shr => 1,
+        Op::Rotl => 1,
+        Op::Rotr => 1,
         Op::Add => 2,
         Op::Sub => 2,
         Op::Mul => 3,
         Op::Div => 3,
-        Op::Mod => 3
+        Op::Mod => 3,
+        Op::FloorDiv => 3,
+        Op::EuclidMod => 3,
+        Op::PlusMinus => 4,
+        Op::ImplicitMul => 5,
+        Op::Pow => 6
     }
 }
 
+/// Whether `a op b op c` is `a op (b op c)`
+pub fn is_right_associative(op: &Op) -> bool {
+    matches!(op, Op::Pow)
+}
+
+pub fn symbol(op: &Op) -> char {
+    match op {
+        Op::Lt => '<',
+        Op::Le => '<',
+        Op::Gt => '>',
+        Op::Ge => '>',
+        Op::Eq => '=',
+        Op::Ne => '!',
+        Op::Add => '+',
+        Op::Sub => '-',
+        Op::Mul => '*',
+        Op::Div => '/',
+        Op::Mod => '%',
+        Op::FloorDiv => '/',
+        Op::EuclidMod => '%',
+        Op::And => '&',
+        Op::Or => '|',
+        Op::Xor => '^',
+        Op::Shl => '<',
+        Op::Shr => '>',
+        Op::Rotl => '<',
+        Op::Rotr => '>',
+        Op::PlusMinus => '±',
+        Op::ImplicitMul => '*',
+        Op::Pow => '*'
+    }
+}
+
+/// Whether the comparison `op` holds between operands ordered as given
+/// (never for the other operators)
+pub fn holds(op: &Op, ordering: Ordering) -> bool {
+    match op {
+        Op::Lt => ordering.is_lt(),
+        Op::Le => ordering.is_le(),
+        Op::Gt => ordering.is_gt(),
+        Op::Ge => ordering.is_ge(),
+        Op::Eq => ordering.is_eq(),
+        Op::Ne => ordering.is_ne(),
+        _ => false
+    }
+}
+
+/// How an operand written right after another one, as in `2(3+4)` or `3pi`, multiplies it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Juxtaposition {
+    /// With the precedence of `*`, so that `6/2(1+2)` is 9
+    Product,
+    /// Before any operator, so that `6/2(1+2)` is 1
+    Tight
+}
+
+/// Choices on how operators behave, made once for a whole evaluation
+#[derive(Clone, Debug, Default)]
+pub struct Settings {
+    /// Integer division rounds toward negative infinity instead of toward zero,
+    /// and the remainder takes the sign of the divisor
+    pub floor_division: bool,
+    /// Type of the operations between two untyped integer literals
+    /// (unbounded when `None`)
+    pub int_type: Option<IntType>,
+    /// Rounding of the products and quotients of fixed-point values
+    pub rounding: RoundingMode,
+    /// Fixed-point and fixed-width integer shifts by a negative amount go the other way instead of failing
+    pub reverse_negative_shifts: bool,
+    /// Fixed-point and fixed-width integer shifts by the width of the format or more shift out every bit
+    /// instead of failing
+    pub saturate_shifts: bool,
+    /// Fractional amounts of fixed-point shifts and rotations are rounded instead of failing
+    pub round_shift_amounts: bool,
+    /// `%` is always the remainder, instead of being a percentage when no operand follows it
+    pub strict_percent: bool,
+    /// Juxtaposed operands are multiplied, instead of failing (`None`)
+    pub implicit_multiplication: Option<Juxtaposition>,
+    /// Custom units, as names and definitions in terms of built-in units and earlier custom ones,
+    /// such as `("furlong", "201.168 m")`
+    pub units: Vec<(String, String)>,
+    /// Statements and loop conditions a script may run before it fails, to stop runaway loops
+    /// (`script::DEFAULT_MAX_STEPS` when `None`)
+    pub max_steps: Option<usize>
+}
+
+/// The values an expression can be evaluated to
+pub trait Operand: Sized + Clone {
+    /// Converts a literal, failing when it can't be represented
+    fn from_number(num: Number) -> Result<Self, &'static str>;
+    /// Applies a binary operator to two values
+    fn apply(op: &Op, a: Self, b: Self, settings: &Settings) -> Result<Self, &'static str>;
+    /// Whether a value holds as a condition, which is when it isn't zero
+    fn is_true(&self) -> Result<bool, &'static str>;
+    /// The sum of values divided by `divisor`, as for a mean
+    fn sum_over(items: Vec<Self>, divisor: u32, settings: &Settings) -> Result<Self, &'static str> {
+        let mut items = items.into_iter();
+        let first = items.next().ok_or("Empty list")?;
+        let sum = items.try_fold(first, |sum, x| Self::checked(&Op::Add, sum, x, settings))?;
+        match divisor {
+            1 => Ok(sum),
+            _ => Self::apply(&Op::Div, sum, Self::from_number(Number::whole(divisor, (' ', 0)))?, settings)
+        }
+    }
+    /// Applies `+`, `-` or `*` as `apply` does, but fails instead of wrapping around
+    /// when the result is out of range, as sums and products of many values need
+    fn checked(op: &Op, a: Self, b: Self, settings: &Settings) -> Result<Self, &'static str> {
+        Self::apply(op, a, b, settings)
+    }
+    /// Converts an imaginary literal, such as `4i`
+    fn from_imaginary(_num: Number) -> Result<Self, &'static str> {
+        Err("Imaginary numbers need the complex mode")
+    }
+    /// Applies a function, such as `abs(x)`, to its arguments
+    fn call(_name: &str, _args: Vec<Self>, _settings: &Settings) -> Result<Self, &'static str> {
+        Err("Unknown function")
+    }
+    /// Applies a function of an expression, such as `map(x**2, x, list)`, to its other arguments,
+    /// with `f` giving the value of the expression for a value of its variable
+    fn call_with(
+        _name: &str, _f: &mut dyn FnMut(Self) -> Result<Self, &'static str>, _args: Vec<Self>, _settings: &Settings
+    ) -> Result<Self, &'static str> {
+        Err("Unknown function")
+    }
+    /// Makes a list of values, as `[1, 2, 3]`
+    fn list(_items: Vec<Self>) -> Result<Self, &'static str> {
+        Err("Unexpected")
+    }
+    /// Converts a value to a fixed-width integer type, as `(u8)x` or `x as u8`
+    fn cast(self, _ty: IntType) -> Result<Self, &'static str> {
+        Err("Casts need an integer mode")
+    }
+    /// The value of a named constant, such as `pi`
+    fn constant(_name: &str, _settings: &Settings) -> Result<Self, &'static str> {
+        Err("Unknown name")
+    }
+    /// Multiplies a value by the unit written after it, as `3 km` or `9.81 m/s^2`
+    fn unit(self, _unit: &str, _settings: &Settings) -> Result<Self, &'static str> {
+        Err("Unexpected")
+    }
+    /// Converts a value to the unit written after `to`, as `72 km/h to m/s`
+    fn convert(self, _unit: &str, _settings: &Settings) -> Result<Self, &'static str> {
+        Err("Conversions need the units mode")
+    }
+}
+
+/// An operator on the parts of a value made of fixed-point numbers, as `Operand::apply` or `Operand::checked`
+pub type FixedOp<const FRAC: u32, T> =
+    fn(&Op, Fixed<FRAC, T>, Fixed<FRAC, T>, &Settings) -> Result<Fixed<FRAC, T>, &'static str>;
+
+/// Functions whose first argument is an expression of the variable named by the second one,
+/// evaluated again for each value of the variable, as `map(x**2, x, [1, 2, 3])`
+pub const EXPRESSION_FUNCTIONS: [&str; 5] = ["deriv", "integrate", "map", "root", "solve"];
+
+/// Functions of whole numbers, such as `nCr(n, k)`, and `factorial` which is also written `n!`
+pub const INTEGER_FUNCTIONS: [&str; 7] = ["factorial", "nCr", "nPr", "gcd", "lcm", "isprime", "factor"];
+
+/// The argument of a function that takes a single one
+pub fn single<V>(args: Vec<V>) -> Result<V, &'static str> {
+    match <[V; 1]>::try_from(args) {
+        Ok([x]) => Ok(x),
+        Err(_) => Err("Wrong number of arguments")
+    }
+}
+
+/// The two arguments of a function that takes two
+pub fn pair<V>(args: Vec<V>) -> Result<(V, V), &'static str> {
+    match <[V; 2]>::try_from(args) {
+        Ok([x, y]) => Ok((x, y)),
+        Err(_) => Err("Wrong number of arguments")
+    }
+}
+
+/// `x` to the power `n`, by repeated squaring with `mul`, or `None` when `n` is zero
+pub fn power<V: Clone>(
+    x: V, mut n: u128, mul: impl Fn(V, V) -> Result<V, &'static str>
+) -> Result<Option<V>, &'static str> {
+    let (mut result, mut base) = (None, x);
+    while n > 0 {
+        if n & 1 == 1 {
+            result = Some(match result {
+                None => base.clone(),
+                Some(result) => mul(result, base.clone())?
+            });
+        };
+        n >>= 1;
+        if n > 0 { base = mul(base.clone(), base)? };
+    };
+    Ok(result)
+}
+
+/// The magnitude of a whole `Fixed`, failing when it has a fractional part
+fn whole<const FRAC: u32, T: Repr>(x: Fixed<FRAC, T>) -> Result<(bool, u128), &'static str> {
+    match x.is_integer() {
+        true => Ok(x.whole_magnitude()),
+        false => Err("Fractional argument")
+    }
+}
+
+/// The value of a whole `Fixed`, failing when it is negative or has a fractional part
+fn natural<const FRAC: u32, T: Repr>(x: Fixed<FRAC, T>) -> Result<u128, &'static str> {
+    match whole(x)? {
+        (true, n) if n != 0 => Err("Negative argument"),
+        (_, n) => Ok(n)
+    }
+}
+
+/// A whole number as a `Fixed`, failing when it is out of its range
+fn from_natural<const FRAC: u32, T: Repr>(n: Option<u128>) -> Result<Fixed<FRAC, T>, &'static str> {
+    n.filter(|n| n.leading_zeros() >= FRAC)
+        .and_then(|n| T::from_magnitude(false, n << FRAC))
+        .map(Fixed::from_raw)
+        .ok_or("Result out of range")
+}
+
+/// 1 when a comparison holds, and else 0
+pub fn truth<const FRAC: u32, T: Repr>(holds: bool) -> Result<Fixed<FRAC, T>, &'static str> {
+    match holds {
+        true => Fixed::one().ok_or("Result out of range"),
+        false => Ok(Fixed::ZERO)
+    }
+}
+
+/// `x` to the whole power `n`, rounding each product and the final inverse with `mode`
+pub fn fixed_power<const FRAC: u32, T: Repr>(
+    x: Fixed<FRAC, T>, n: Fixed<FRAC, T>, mode: RoundingMode
+) -> Result<Fixed<FRAC, T>, &'static str> {
+    let (neg, n) = whole(n).map_err(|_| "Fractional exponent")?;
+    let mul = |a: Fixed<FRAC, T>, b| a.checked_mul_round(b, mode).ok_or("Result out of range");
+    let one = || Fixed::one().ok_or("Result out of range");
+    let result = match power(x, n, mul)? {
+        Some(result) => result,
+        None => return one()
+    };
+    match neg {
+        false => Ok(result),
+        true if result == Fixed::ZERO => Err("Division by zero"),
+        true => one()?.checked_div_round(result, mode).ok_or("Result out of range")
+    }
+}
+
+/// Unsigned formats report an error instead of producing a negative result
+/// Comparisons give 1 when they hold and 0 otherwise
+/// Integer functions, such as `factorial` (or `n!`) and `nCr`, take the whole part
+/// of their arguments, and fail when the result is out of range
+impl<const FRAC: u32, T: Repr> Operand for Fixed<FRAC, T> {
+    fn from_number(num: Number) -> Result<Self, &'static str> {
+        num.into_fixed()
+    }
+
+    fn is_true(&self) -> Result<bool, &'static str> {
+        Ok(*self != Fixed::ZERO)
+    }
+
+    /// Sums the raw values in an `i128`, so that only the result has to be in range
+    fn sum_over(items: Vec<Self>, divisor: u32, settings: &Settings) -> Result<Self, &'static str> {
+        if items.is_empty() { return Err("Empty list") };
+        let mut sum: i128 = 0;
+        for x in items {
+            let raw = i128::try_from(x.as_raw().unsigned_abs()).map_err(|_| "Result out of range")?;
+            sum = match x.as_raw().is_negative() {
+                true => sum.checked_sub(raw),
+                false => sum.checked_add(raw)
+            }.ok_or("Result out of range")?;
+        };
+        let (neg, abs, divisor) = (sum < 0, sum.unsigned_abs(), divisor as u128);
+        let (quotient, remainder) = (abs / divisor, abs % divisor);
+        let half = remainder.cmp(&(divisor - remainder));
+        let quotient = quotient + settings.rounding.round_up(neg, quotient & 1 == 1, half, remainder != 0) as u128;
+        T::from_magnitude(neg, quotient).map(Fixed::from_raw).ok_or("Result out of range")
+    }
+
+    fn apply(op: &Op, a: Self, b: Self, settings: &Settings) -> Result<Self, &'static str> {
+        match op {
+            Op::Sub if !T::SIGNED && b > a => return Err("Negative result"),
+            Op::Div | Op::Mod | Op::FloorDiv | Op::EuclidMod
+                if b == Fixed::from_raw(T::ZERO) => return Err("Division by zero"),
+            _ => ()
+        };
+        let result = match op {
+            Op::Add => a+b,
+            Op::Sub => a-b,
+            Op::Mul | Op::ImplicitMul => a.mul_round(b, settings.rounding),
+            Op::Pow => fixed_power(a, b, settings.rounding)?,
+            Op::Lt | Op::Le | Op::Gt | Op::Ge | Op::Eq | Op::Ne => truth(holds(op, a.cmp(&b)))?,
+            Op::Div => a.div_round(b, settings.rounding),
+            Op::Mod => a%b,
+            Op::FloorDiv => a.div_floor(b),
+            Op::EuclidMod => a.rem_euclid(b),
+            Op::And => a&b,
+            Op::Or  => a|b,
+            Op::Xor => a^b,
+            Op::Shl | Op::Shr | Op::Rotl | Op::Rotr => shift(op, a, b, settings)?,
+            Op::PlusMinus => return Err("Uncertainties need the uncertain mode")
+        };
+        Ok(result)
+    }
+
+    fn checked(op: &Op, a: Self, b: Self, settings: &Settings) -> Result<Self, &'static str> {
+        let result = match op {
+            Op::Add => a.checked_add(b),
+            Op::Sub if T::SIGNED => a.checked_sub(b),
+            Op::Mul | Op::ImplicitMul => a.checked_mul_round(b, settings.rounding),
+            _ => return Fixed::apply(op, a, b, settings)
+        };
+        result.ok_or("Result out of range")
+    }
+
+    fn call(name: &str, args: Vec<Self>, settings: &Settings) -> Result<Self, &'static str> {
+        match name {
+            "abs" => Ok(single(args)?.abs()),
+            "sqrt" => single(args)?.sqrt(settings.rounding).ok_or("Square root of a negative number"),
+            "factorial" => from_natural(number_theory::factorial(natural(single(args)?)?)),
+            "nCr" | "nPr" => {
+                let (n, k) = pair(args)?;
+                let (n, k) = (natural(n)?, natural(k)?);
+                match name {
+                    "nCr" => from_natural(number_theory::binomial(n, k)),
+                    _ => from_natural(number_theory::permutations(n, k))
+                }
+            },
+            "gcd" | "lcm" => {
+                if args.len() < 2 { return Err("Wrong number of arguments") };
+                let mut result = Some(whole(args[0])?.1);
+                for x in &args[1..] {
+                    let x = whole(*x)?.1;
+                    result = match name {
+                        "gcd" => result.map(|y| number_theory::gcd(x, y)),
+                        _ => result.and_then(|y| number_theory::lcm(x, y))
+                    };
+                };
+                from_natural(result)
+            },
+            "isprime" => {
+                let n = u64::try_from(natural(single(args)?)?).map_err(|_| "Argument out of range")?;
+                from_natural(Some(number_theory::is_prime(n) as u128))
+            },
+            "factor" => match natural(single(args)?)? {
+                0 | 1 => Err("No prime factor"),
+                n => {
+                    let n = u64::try_from(n).map_err(|_| "Argument out of range")?;
+                    from_natural(Some(number_theory::prime_factors(n)[0] as u128))
+                }
+            },
+            _ => Err("Unknown function")
+        }
+    }
+
+    fn call_with(
+        name: &str, f: &mut dyn FnMut(Self) -> Result<Self, &'static str>, args: Vec<Self>, settings: &Settings
+    ) -> Result<Self, &'static str> {
+        match name {
+            "deriv" => calculus::derivative(f, single(args)?, settings),
+            "integrate" => {
+                let (a, b) = pair(args)?;
+                calculus::integral(f, a, b, settings)
+            },
+            "solve" => roots::newton(f, single(args)?, settings),
+            "root" => {
+                let (a, b) = pair(args)?;
+                roots::brent(f, a, b, settings)
+            },
+            _ => Err("Unknown function")
+        }
+    }
+
+    fn constant(name: &str, settings: &Settings) -> Result<Self, &'static str> {
+        match name {
+            "pi" => Fixed::pi(settings.rounding).ok_or("Result out of range"),
+            _ => Err("Unknown name")
+        }
+    }
+}
+
+/// Shifts or rotates the raw content of `a` by `b` bits
+/// Rotations take their amount modulo the width of the format
+pub fn shift<const FRAC: u32, T: Repr>(
+    op: &Op, a: Fixed<FRAC, T>, b: Fixed<FRAC, T>, settings: &Settings
+) -> Result<Fixed<FRAC, T>, &'static str> {
+    if !b.is_integer() && !settings.round_shift_amounts { return Err("Fractional shift amount") };
+    let (neg, bits) = b.round(settings.rounding).whole_magnitude();
+    if neg && !settings.reverse_negative_shifts { return Err("Negative shift amount") };
+    let rotation = matches!(op, Op::Rotl | Op::Rotr);
+    if !rotation && bits >= T::BITS as u128 && !settings.saturate_shifts {
+        return Err("Shift amount out of range")
+    };
+    let left = matches!(op, Op::Shl | Op::Rotl) != neg;
+    let result = match (rotation, left) {
+        (false, true) => a.shl_bits(bits.min(T::BITS as u128) as u32),
+        (false, false) => a.shr_bits(bits.min(T::BITS as u128) as u32),
+        (true, true) => a.rotate_left((bits % T::BITS as u128) as u32),
+        (true, false) => a.rotate_right((bits % T::BITS as u128) as u32)
+    };
+    Ok(result)
+}
+
 /// This function pops two elements out of a stack, then applies an operation
 /// depending on the operator that was popped out of the op_stack
 /// The result of the operation is then pushed back onto the stack
-pub fn apply_top(op_stack: &mut Vec<Op>, stack: &mut Vec<Fixed>) {
-    let func = match op_stack.pop().unwrap() {
-        Op::Add => |a, b| a+b,
-        Op::Sub => |a, b| a-b,
-        Op::Mul => |a, b| a*b,
-        Op::Div => |a, b| a/b,
-        Op::Mod => |a, b| a%b,
-        Op::And => |a, b| a&b,
-        Op::Or  => |a, b| a|b,
-        Op::Xor => |a, b| a^b,
-        Op::Shl => |a, b| a<<b,
-        Op::Shr => |a, b| a>>b
-    };
+pub fn apply_top<V: Operand>(
+    op_stack: &mut Vec<(Op, usize)>, stack: &mut Vec<V>, settings: &Settings
+) -> Result<(), (&'static str, char, usize)> {
+    let (op, index) = op_stack.pop().unwrap();
     let b = stack.pop().unwrap();
     let a = stack.pop().unwrap();
-    stack.push(func(a, b));
+    stack.push( V::apply(&op, a, b, settings).map_err(|e| (e, symbol(&op), index))? );
+    Ok(())
 }
@@ -1,5 +1,10 @@
-use crate::fixed::Fixed;
-use crate::ops::{OP_CHARS, Op, precedence, apply_top};
+use crate::bigint::BigInt;
+use crate::fixed::{Fixed, Repr};
+use crate::glyphs::superscript;
+use crate::int::IntType;
+use crate::ops::{EXPRESSION_FUNCTIONS, OP_CHARS, Juxtaposition, Op, Operand, Settings, precedence, is_right_associative, apply_top};
+use crate::script::exec_script;
+use std::ops::Range;
 
 enum LexerState {
     ExpectingNumber,
@@ -8,73 +13,549 @@ enum LexerState {
     ReadingNumberFormat,
     ReadingNumberWhole,
     ReadingNumberDecimal,
-    ReadingNumberTooLong,
+    ReadingNumberExponentSign,
+    ReadingNumberExponentStart,
+    ReadingNumberExponent,
+    ReadingNumberSuffix,
     ReadingOperator,
+    ReadingShift,
+    ReadingPlusMinus,
+    ReadingName,
+    ReadingKeyword,
+    ExpectingType,
+    ReadingType,
+    ExpectingUnit,
+    ReadingUnit,
+    ReadingSuperscript,
     IgnoreThatChar
 }
-struct Number {
-    whole: u32,
-    decimal: u64,
+/// An operator written before its operand, waiting for it
+enum Prefix {
+    /// A cast `(type)`
+    Cast(IntType),
+    /// A square root `√`
+    Root
+}
+/// A literal, as it was read from the expression
+pub struct Number {
+    whole: Vec<u32>,
+    decimal: Vec<u32>,
+    exponent: u32,
+    exponent_neg: bool,
     neg: bool,
+    imaginary: bool,
 
     base: u32,
-    decimal_count: u32
-}
-const DEFAULT: Number = Number { whole: 0, decimal: 0, neg: false, base: 10, decimal_count: 0 };
-
-fn decimal_part_is_full(num: &Number) -> bool {
-    // decimal part is full when (here they are hardcoded for the 4 usable bases):
-    //   log2(num.base) * num.decimal_count >= 32
-    match (num.base, num.decimal_count) {
-        (2, 32) => true,
-        (8, 11) => true,
-        (10, 10) => true,
-        (16, 8) => true,
-        (_, _) => false
+    start: (char, usize)
+}
+const DEFAULT: Number = Number {
+    whole: Vec::new(), decimal: Vec::new(), exponent: 0, exponent_neg: false, neg: false,
+    imaginary: false, base: 10, start: (' ', 0)
+};
+pub const OUT_OF_RANGE: &str = "Literal out of range";
+/// Exponents of literals evaluated exactly are limited, so that their value stays reasonably small
+const MAX_EXACT_EXPONENT: i64 = 10_000;
+
+/// Doubles a string of digits in place, returning the digit carried out of it
+/// (0 or 1), which is the next bit of its binary expansion
+fn double_digits(digits: &mut [u32], base: u32) -> u128 {
+    let mut carry = 0;
+    for d in digits.iter_mut().rev() {
+        let doubled = *d * 2 + carry;
+        *d = doubled % base;
+        carry = doubled / base;
+    };
+    carry as u128
+}
+
+/// Converts the digits of the decimal part into `frac` bits of binary fraction,
+/// rounded to the nearest value (ties to even)
+/// The result can be 1 << frac when rounding carries into the whole part
+fn decimal_bits(mut digits: Vec<u32>, base: u32, frac: u32) -> u128 {
+    let mut bits = 0;
+    for _ in 0..frac {
+        bits = (bits << 1) | double_digits(&mut digits, base);
+    };
+    let round_bit = double_digits(&mut digits, base) == 1;
+    let sticky = digits.iter().any(|&d| d != 0);
+    match round_bit && (sticky || bits & 1 == 1) {
+        true => bits + 1,
+        false => bits
     }
 }
 
-fn push(num: Number, stack: &mut Vec<Fixed>) {
-    let fixed_repr = match num.base {
-        2  => ((num.whole as u64) << 32) + (num.decimal << (33 -     num.decimal_count) >> 1),
-        8  => ((num.whole as u64) << 32) + (num.decimal << (33 - 3 * num.decimal_count) >> 1),
-        16 => ((num.whole as u64) << 32) + (num.decimal << (33 - 4 * num.decimal_count) >> 1),
-        10 => {
-            let whole_part = (num.whole as u64) << 32;
-            let decimal_part = num.decimal * 10u64.pow(10 - num.decimal_count);
-            let decimal_part = (decimal_part << 22) / 9765625;
-            whole_part + decimal_part
+/// Rewrites digits of base 2, 8 or 16 as binary digits
+fn to_binary_digits(digits: &[u32], base: u32) -> Vec<u32> {
+    let width = base.trailing_zeros();
+    digits.iter()
+        .flat_map(|d| (0..width).rev().map(move |bit| (d >> bit) & 1))
+        .collect()
+}
+
+/// Moves the point of the number by `shift` digits (to the right when positive)
+/// Zeros past 130 digits are not materialized: left of the point they would put
+/// the number out of range, right of it they cannot change the rounded value
+fn shift_point(num: &mut Number, shift: i64) -> Result<(), &'static str> {
+    const MAX_ZEROS: i64 = 130;
+    if shift > 0 {
+        let moved = num.decimal.len().min(shift as usize);
+        num.whole.extend(num.decimal.drain(..moved));
+        let zeros = shift - moved as i64;
+        if num.whole.iter().any(|&d| d != 0) {
+            if zeros > MAX_ZEROS { return Err(OUT_OF_RANGE) };
+            num.whole.extend(std::iter::repeat_n(0, zeros as usize));
+        };
+    } else {
+        let moved = num.whole.len().min(shift.unsigned_abs() as usize);
+        let tail = num.whole.split_off(num.whole.len() - moved);
+        let zeros = (shift.unsigned_abs() as i64 - moved as i64).min(MAX_ZEROS);
+        num.decimal.splice(0..0, std::iter::repeat_n(0, zeros as usize).chain(tail));
+    };
+    Ok(())
+}
+
+#[allow(unused)]
+impl Number {
+    /// The literal of a whole number, as if it was written at `start`
+    pub fn whole(n: u32, start: (char, usize)) -> Number {
+        Number { whole: n.to_string().chars().map(|d| d as u32 - 0x30).collect(), start, ..DEFAULT }
+    }
+
+    pub fn is_negative(&self) -> bool { self.neg }
+
+    /// The exact value of the literal, as a numerator and a positive denominator
+    /// (not reduced)
+    pub fn into_fraction(self) -> Result<(BigInt, BigInt), &'static str> {
+        let decimals = -(self.decimal.len() as i64);
+        let (base_exp, two_exp) = match self.base {
+            10 => (decimals + self.exponent(), 0),
+            _ => (decimals, self.exponent())
+        };
+        if base_exp.abs() > MAX_EXACT_EXPONENT || two_exp.abs() > MAX_EXACT_EXPONENT {
+            return Err(OUT_OF_RANGE)
+        };
+        let mut numer = BigInt::from_digits(self.whole.iter().chain(&self.decimal).copied(), self.base);
+        let mut denom = BigInt::one();
+        let power = BigInt::from(self.base).pow(base_exp.unsigned_abs() as u32);
+        match base_exp >= 0 {
+            true => numer = numer * power,
+            false => denom = denom * power
+        };
+        match two_exp >= 0 {
+            true => numer = numer.shl(two_exp as usize),
+            false => denom = denom.shl(two_exp.unsigned_abs() as usize)
+        };
+        if self.neg { numer = -numer };
+        Ok((numer, denom))
+    }
+
+    fn exponent(&self) -> i64 {
+        match self.exponent_neg {
+            false => self.exponent as i64,
+            true => -(self.exponent as i64)
         }
-        _  => panic!()
+    }
+
+    /// The value of the literal, rounded to the nearest `Fixed` (ties to even)
+    pub fn into_fixed<const FRAC: u32, T: Repr>(mut self) -> Result<Fixed<FRAC, T>, &'static str> {
+        let exponent = self.exponent();
+        if exponent != 0 {
+            if self.base != 10 {
+                self.whole = to_binary_digits(&self.whole, self.base);
+                self.decimal = to_binary_digits(&self.decimal, self.base);
+                self.base = 2;
+            };
+            shift_point(&mut self, exponent)?;
+        };
+
+        let whole = self.whole.iter().try_fold(0u128, |acc, &d| {
+            acc.checked_mul(self.base as u128)?.checked_add(d as u128)
+        });
+        let raw = whole
+            .filter(|&whole| 128 - whole.leading_zeros() <= T::BITS - FRAC)
+            .and_then(|whole| (whole << FRAC).checked_add(decimal_bits(self.decimal, self.base, FRAC)))
+            .and_then(|abs| T::from_magnitude(self.neg, abs))
+            .ok_or(OUT_OF_RANGE)?;
+        Ok(Fixed::from_raw(raw))
+    }
+}
+
+fn push<V: Operand>(num: Number, stack: &mut Vec<V>) -> Result<(), (&'static str, char, usize)> {
+    let (first_char, start) = num.start;
+    let value = match num.imaginary {
+        true => V::from_imaginary(num),
+        false => V::from_number(num)
+    };
+    stack.push( value.map_err(|e| (e, first_char, start))? );
+    Ok(())
+}
+
+/// Pushes an operator, after applying the operators of the current group
+/// that bind at least as tightly (more tightly for right-associative ones)
+fn push_op<V: Operand>(
+    op: (Op, usize), op_stack: &mut Vec<(Op, usize)>, op_counts: &mut Vec<usize>,
+    stack: &mut Vec<V>, settings: &Settings
+) -> Result<(), (&'static str, char, usize)> {
+    let mut count = op_counts.pop().unwrap();
+    let binding = precedence(&op.0) + is_right_associative(&op.0) as i32;
+    while count > 0 && binding <= precedence(&op_stack.last().unwrap().0) {
+        apply_top(op_stack, stack, settings)?;
+        count -= 1;
+    };
+    op_stack.push(op);
+    op_counts.push(count + 1);
+    Ok(())
+}
+
+/// Pushes the product implied by an operand written right after another one, starting with `c`
+fn juxtapose<V: Operand>(
+    c: char, index: usize, op_stack: &mut Vec<(Op, usize)>, op_counts: &mut Vec<usize>,
+    stack: &mut Vec<V>, settings: &Settings
+) -> Result<(), (&'static str, char, usize)> {
+    let op = match settings.implicit_multiplication {
+        None => return Err(("Unexpected", c, index)),
+        Some(Juxtaposition::Product) => Op::Mul,
+        Some(Juxtaposition::Tight) => Op::ImplicitMul
+    };
+    push_op((op, index), op_stack, op_counts, stack, settings)
+}
+
+pub fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Whether a unit such as `m/s^-2` goes on with `c`, given the characters around it
+pub fn continues_unit(c: char, prev: Option<char>, next: Option<&char>) -> bool {
+    match c {
+        '/' => next.is_some_and(|n| n.is_ascii_alphabetic()),
+        '^' => next.is_some_and(|&n| n.is_ascii_digit() || n == '-'),
+        '-' => prev == Some('^'),
+        c => is_name_char(c)
+    }
+}
+
+/// Whether the `%` at `index` is a percentage, because no operand follows it
+/// Signs followed by a space can't start an operand, so `15% - 5` is a percentage
+fn is_percent(chars: &[char], index: usize) -> bool {
+    if chars.get(index + 1) == Some(&'%') { return false };
+    let next = (index + 1..chars.len()).find(|&i| !chars[i].is_whitespace());
+    match next.map(|i| (chars[i], chars.get(i + 1))) {
+        None => true,
+        Some(('+' | '-' | '−', after)) => after.is_none_or(|c| c.is_whitespace()),
+        Some((c, _)) => !(is_name_char(c) || c == '.' || c == '(')
+    }
+}
+
+/// Turns the value on top of the stack into a percentage: of the left operand
+/// of a pending `+` or `-`, as in `200 + 15%`, and else of 1
+/// A pending `*` or `/` is applied first, so that `200 * 15%` is exactly 30
+fn percent_top<V: Operand>(
+    op_stack: &mut Vec<(Op, usize)>, op_counts: &mut [usize], stack: &mut Vec<V>, settings: &Settings, index: usize
+) -> Result<(), (&'static str, char, usize)> {
+    let hundred = V::from_number(Number { whole: vec![1, 0, 0], ..DEFAULT }).map_err(|e| (e, '%', index))?;
+    let pending = match *op_counts.last().unwrap() {
+        0 => None,
+        _ => op_stack.last().map(|(op, _)| op)
     };
-    let final_num = match num.neg {
-        false => fixed_repr as i64,
-        true => -(fixed_repr as i64)
+    let result = match pending {
+        Some(Op::Add | Op::Sub) => {
+            let value = stack.pop().unwrap();
+            V::apply(&Op::Mul, stack.last().unwrap().clone(), value, settings)
+                .and_then(|value| V::apply(&Op::Div, value, hundred, settings))
+        },
+        Some(op @ (Op::Mul | Op::Div)) => {
+            let scale = match op {
+                Op::Mul => Op::Div,
+                _ => Op::Mul
+            };
+            apply_top(op_stack, stack, settings)?;
+            *op_counts.last_mut().unwrap() -= 1;
+            V::apply(&scale, stack.pop().unwrap(), hundred, settings)
+        },
+        _ => V::apply(&Op::Div, stack.pop().unwrap(), hundred, settings)
     };
-    stack.push( Fixed::from_i64(final_num) );
+    stack.push( result.map_err(|e| (e, '%', index))? );
+    Ok(())
 }
 
+/// Applies the unit written after the value on top of the stack, or converts it
+fn unit_top<V: Operand>(
+    name: &(String, usize), convert: bool, stack: &mut Vec<V>, settings: &Settings
+) -> Result<(), (&'static str, char, usize)> {
+    let (name, start) = name;
+    let first_char = name.chars().next().unwrap();
+    let value = stack.pop().unwrap();
+    let result = match convert {
+        false => value.unit(name, settings),
+        true => value.convert(name, settings)
+    };
+    stack.push( result.map_err(|e| (e, first_char, *start))? );
+    Ok(())
+}
+
+/// Pushes the value of a name written as an operand: a variable, `i`, or a constant such as `pi`
+fn name_top<V: Operand>(
+    name: &(String, usize), stack: &mut Vec<V>, variables: &[(String, V)], settings: &Settings
+) -> Result<(), (&'static str, char, usize)> {
+    let (name, start) = name;
+    let first_char = name.chars().next().unwrap();
+    if let Some((_, value)) = variables.iter().rev().find(|(variable, _)| variable == name) {
+        stack.push(value.clone());
+        return Ok(())
+    };
+    if name == "i" {
+        return push(Number { whole: vec![1], imaginary: true, start: (first_char, *start), ..DEFAULT }, stack)
+    };
+    stack.push( V::constant(name, settings).map_err(|e| (e, first_char, *start))? );
+    Ok(())
+}
+
+/// Reads a name written right after an operand, as a unit when there is one of that name,
+/// and else, with implicit multiplication, as an operand multiplying it, as in `3pi`
+/// Numbers are never juxtaposed, so that `2 3` stays an error
+fn name_after_top<V: Operand>(
+    name: &(String, usize), op_stack: &mut Vec<(Op, usize)>, op_counts: &mut Vec<usize>,
+    stack: &mut Vec<V>, variables: &[(String, V)], settings: &Settings
+) -> Result<(), (&'static str, char, usize)> {
+    let first_char = name.0.chars().next().unwrap();
+    if settings.implicit_multiplication.is_none() || first_char.is_ascii_digit() {
+        return unit_top(name, false, stack, settings)
+    };
+    if let Ok(value) = stack.last().unwrap().clone().unit(&name.0, settings) {
+        *stack.last_mut().unwrap() = value;
+        return Ok(())
+    };
+    juxtapose(first_char, name.1, op_stack, op_counts, stack, settings)?;
+    name_top(name, stack, variables, settings)
+}
+
+/// Casts the value on top of the stack to the type named by `x as type`
+fn cast_top<V: Operand>(name: &(String, usize), stack: &mut Vec<V>) -> Result<(), (&'static str, char, usize)> {
+    let (name, start) = name;
+    let first_char = name.chars().next().unwrap();
+    let ty = name.parse().map_err(|_| ("Unknown type", first_char, *start))?;
+    let value = stack.pop().unwrap();
+    stack.push( value.cast(ty).map_err(|e| (e, first_char, *start))? );
+    Ok(())
+}
+
+/// Applies to the value on top of the stack every prefix operator, such as a cast `(type)`,
+/// that was waiting for an operand at the given depth of parentheses
+fn apply_prefixes<V: Operand>(
+    prefixes: &mut Vec<(Prefix, usize, usize)>, depth: usize, stack: &mut Vec<V>, settings: &Settings
+) -> Result<(), (&'static str, char, usize)> {
+    while prefixes.last().is_some_and(|prefix| prefix.1 == depth) {
+        let (prefix, _, index) = prefixes.pop().unwrap();
+        let value = stack.pop().unwrap();
+        let result = match prefix {
+            Prefix::Cast(ty) => value.cast(ty).map_err(|e| (e, '(', index)),
+            Prefix::Root => V::call("sqrt", vec![value], settings).map_err(|e| (e, '√', index))
+        };
+        stack.push(result?);
+    };
+    Ok(())
+}
+
+/// Raises the value on top of the stack to the power written in superscript after it,
+/// as in `x²`, where `start` is the first superscript character and its index
+fn superscript_top<V: Operand>(
+    exponent: &str, start: (char, usize), stack: &mut Vec<V>, settings: &Settings
+) -> Result<(), (&'static str, char, usize)> {
+    let (first_char, index) = start;
+    let (neg, digits) = match exponent.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, exponent.strip_prefix('+').unwrap_or(exponent))
+    };
+    if digits.is_empty() || !digits.chars().all(|d| d.is_ascii_digit()) { return Err(("Unexpected", first_char, index)) };
+    let whole = digits.chars().map(|d| d as u32 - 0x30).collect();
+    let exponent = V::from_number(Number { whole, neg, start, ..DEFAULT }).map_err(|e| (e, first_char, index))?;
+    let value = stack.pop().unwrap();
+    stack.push( V::apply(&Op::Pow, value, exponent, settings).map_err(|e| (e, first_char, index))? );
+    Ok(())
+}
+
+/// Whether `c` goes on with the unit read so far, as a superscript exponent in `m/s²`
+/// or a product in `N·m`, given the value the unit applies to, if any
+fn continues_unit_glyph<V: Operand>(
+    c: char, unit: &str, next: Option<&char>, value: Option<&V>, settings: &Settings
+) -> bool {
+    let glyph = superscript(c).is_some() || (c == '·' && next.is_some_and(|n| n.is_ascii_alphabetic()));
+    glyph && value.is_none_or(|value| value.clone().unit(unit, settings).is_ok())
+}
+
+/// Writes the unit glyph `c` read at `index` in ASCII, as `^2` for `²`
+fn push_unit_glyph(unit: &mut String, c: char, chars: &[char], index: usize) {
+    match superscript(c) {
+        Some(digit) => {
+            if superscript(chars[index - 1]).is_none() { unit.push('^') };
+            unit.push(digit);
+        },
+        None => unit.push(c)
+    };
+}
+
+/// Evaluates an expression in the default Q32.32 format
+#[allow(unused)]
 pub fn exec(expr: &str) -> Result<Fixed, (&'static str, char, usize)> {
+    exec_as(expr)
+}
+
+/// Evaluates an expression with values of type `V`, and the default settings
+#[allow(unused)]
+pub fn exec_as<V: Operand>(expr: &str) -> Result<V, (&'static str, char, usize)> {
+    exec_with(expr, &Settings::default())
+}
+
+/// The ranges of the arguments of the call whose parentheses open at `open`,
+/// and the index where they close, if they do
+fn arguments(chars: &[char], open: usize) -> (Vec<Range<usize>>, Option<usize>) {
+    let mut args = Vec::new();
+    let mut arg_start = open + 1;
+    let mut depth = 0;
+    for (i, &c) in chars.iter().enumerate().skip(open + 1) {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' if depth == 0 => {
+                args.push(arg_start..i);
+                return (args, Some(i));
+            },
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(arg_start..i);
+                arg_start = i + 1;
+            },
+            _ => ()
+        };
+    };
+    (args, None)
+}
+
+/// Whether the call to `name` whose parentheses open at `open` is to a function of an expression
+/// `solve` with two arguments solves a linear system instead
+fn is_expression_call(name: &str, open: usize, chars: &[char]) -> bool {
+    EXPRESSION_FUNCTIONS.contains(&name) && (name != "solve" || arguments(chars, open).0.len() == 3)
+}
+
+/// The index of the `=` of an equation `lhs = rhs` in `range`, which isn't part of a comparison
+fn equation_sign(chars: &[char], range: &Range<usize>) -> Option<usize> {
+    let mut depth = 0;
+    for i in range.clone() {
+        match chars[i] {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            '=' if depth == 0 && !matches!(chars[i - 1], '<' | '>' | '=' | '!') && chars.get(i + 1) != Some(&'=') => {
+                return Some(i)
+            },
+            _ => ()
+        };
+    };
+    None
+}
+
+/// The index of a `^` in `range` whose left operand is `variable`, as in `x^2`
+fn variable_xor(chars: &[char], range: &Range<usize>, variable: &str) -> Option<usize> {
+    range.clone().filter(|&i| chars[i] == '^').find(|&i| {
+        let end = (range.start..i).rev().find(|&j| !chars[j].is_whitespace()).map_or(range.start, |j| j + 1);
+        let start = (range.start..end).rev().take_while(|&j| is_name_char(chars[j])).last().unwrap_or(end);
+        // Digits before the name are a product, as in `2x^2`
+        let start = (start..end).find(|&j| !chars[j].is_ascii_digit()).unwrap_or(end);
+        chars[start..end].iter().copied().eq(variable.chars())
+    })
+}
+
+/// Evaluates a call to a function of an expression, such as `map(x**2, x, [1, 2])`, whose
+/// parentheses open at `open`, and gives the index where they close
+/// The expression is kept unevaluated, and evaluated again for each value the function gives
+/// to its variable, while the other arguments are evaluated once
+/// The expression may be an equation `lhs = rhs`, as in `solve(x**2 = 2, x, 1)`
+/// `^` fails on the variable, as it is XOR, which `x^2` hardly means
+fn expression_call<V: Operand>(
+    name: &(String, usize), open: usize, chars: &[char], stack: &mut Vec<V>, variables: &[(String, V)], settings: &Settings
+) -> Result<usize, (&'static str, char, usize)> {
+    let (name, start) = name;
+    let first_char = name.chars().next().unwrap();
+    let (args, close) = arguments(chars, open);
+    let close = close.ok_or(("Unmatched", '(', open))?;
+    if args.len() < 2 { return Err(("Wrong number of arguments", first_char, *start)) };
+    let variable: String = chars[args[1].clone()].iter().filter(|c| !c.is_whitespace()).collect();
+    if !variable.starts_with(|c: char| c.is_ascii_alphabetic()) || !variable.chars().all(is_name_char) {
+        let index = (args[1].start..close).find(|&i| !chars[i].is_whitespace()).unwrap_or(close);
+        return Err(("Expected a variable", chars[index], index))
+    };
+    if let Some(index) = variable_xor(chars, &args[0], &variable) {
+        return Err(("XOR of the variable (powers are written **)", '^', index))
+    };
+    let values = args[2..].iter()
+        .map(|arg| eval(&chars[..arg.end], arg.start, variables, settings))
+        .collect::<Result<_, _>>()?;
+    let expr = &args[0];
+    let equation = equation_sign(chars, expr);
+    let mut scope = variables.to_vec();
+    let mut expr_error = None;
+    let mut f = |x| {
+        scope.push((variable.clone(), x));
+        let value = match equation {
+            // An equation is the difference of its sides, which is zero where it holds
+            Some(sign) => eval(&chars[..sign], expr.start, &scope, settings).and_then(|lhs| {
+                let rhs = eval(&chars[..expr.end], sign + 1, &scope, settings)?;
+                V::apply(&Op::Sub, lhs, rhs, settings).map_err(|e| (e, '=', sign))
+            }),
+            None => eval(&chars[..expr.end], expr.start, &scope, settings)
+        };
+        scope.pop();
+        value.map_err(|e| expr_error.insert(e).0)
+    };
+    let result = V::call_with(name, &mut f, values, settings);
+    // Errors of the expression point into it
+    stack.push( result.map_err(|e| match expr_error {
+        Some(error) if error.0 == e => error,
+        _ => (e, first_char, *start)
+    })? );
+    Ok(close)
+}
+
+/// Evaluates an expression with values of type `V`, or a script, giving the value it ends with
+pub fn exec_with<V: Operand>(expr: &str, settings: &Settings) -> Result<V, (&'static str, char, usize)> {
+    let mut values = exec_script(expr, settings)?;
+    values.pop().ok_or(("No value", '\'', expr.chars().count()))
+}
+
+/// Evaluates the expression starting at `start` in `chars`, with the values of `variables`
+/// (the last one of a name is used)
+pub fn eval<V: Operand>(
+    chars: &[char], start: usize, variables: &[(String, V)], settings: &Settings
+) -> Result<V, (&'static str, char, usize)> {
     let mut lexer_state = LexerState::ExpectingNumber;
     let mut num = Number { ..DEFAULT };
-    let mut curr_op = ' ';
+    let mut curr_op = (' ', 0);
     let mut stack = Vec::new();
-    let mut op_stack = Vec::new();
+    let mut op_stack: Vec<(Op, usize)> = Vec::new();
     let mut op_counts = vec![0];
     let mut parens_indices = Vec::new();
+    let mut name = (String::new(), 0);
+    let mut prefixes = Vec::new();
+    let mut calls: Vec<(String, usize, usize, usize)> = Vec::new();
+    // The characters up to this index were read as part of a call to a function of an expression
+    let mut skip_until = 0;
 
-    for (index, c) in expr.chars().enumerate() {
+    for (index, &c) in chars.iter().enumerate().skip(start) {
+        if index < skip_until { continue };
         match lexer_state {
             LexerState::ReadingNumberSign => {
                 match c {
                     '+' => (),
-                    '-' => num.neg = !num.neg,
+                    '-' | '−' => num.neg = !num.neg,
                     '.' => lexer_state = LexerState::ReadingNumberDecimal,
                     '0' => lexer_state = LexerState::ReadingNumberFormat,
                     d @ '1'..='9' => {
-                        num.whole = d as u32 - 0x30;
+                        num.whole.push(d as u32 - 0x30);
                         lexer_state = LexerState::ReadingNumberWhole;
-                    }
+                    },
+                    'i' => {
+                        num.whole.push(1);
+                        num.imaginary = true;
+                        lexer_state = LexerState::ReadingNumberSuffix;
+                    },
                     other => return Err(("Unexpected", other, index))
                 };
             },
@@ -94,12 +575,17 @@ pub fn exec(expr: &str) -> Result<Fixed, (&'static str, char, usize)> {
                     }
                     '_' => lexer_state = LexerState::ReadingNumberWhole,
                     '.' => lexer_state = LexerState::ReadingNumberDecimal,
+                    'e' | 'E' => lexer_state = LexerState::ReadingNumberExponentSign,
+                    'i' => {
+                        num.imaginary = true;
+                        lexer_state = LexerState::ReadingNumberSuffix;
+                    },
                     d @ '0'..='9' => {
-                        num.whole = d as u32 - 0x30;
+                        num.whole.push(d as u32 - 0x30);
                         lexer_state = LexerState::ReadingNumberWhole;
                     },
                     _ => {
-                        push(num, &mut stack);
+                        push(num, &mut stack)?;
                         num = Number { ..DEFAULT };
                         lexer_state = LexerState::ExpectingOperator;
                     }
@@ -109,11 +595,17 @@ pub fn exec(expr: &str) -> Result<Fixed, (&'static str, char, usize)> {
                 match c {
                     '_' => (),
                     '.' => lexer_state = LexerState::ReadingNumberDecimal,
-                    d if d.to_digit(num.base).is_some() => {
-                        num.whole = num.whole * num.base + d.to_digit(num.base).unwrap();
+                    'e' | 'E' if num.base == 10 => lexer_state = LexerState::ReadingNumberExponentSign,
+                    'p' | 'P' if num.base != 10 => lexer_state = LexerState::ReadingNumberExponentSign,
+                    d if d.is_digit(num.base) => {
+                        num.whole.push(d.to_digit(num.base).unwrap());
+                    },
+                    'i' => {
+                        num.imaginary = true;
+                        lexer_state = LexerState::ReadingNumberSuffix;
                     },
                     _ => {
-                        push(num, &mut stack);
+                        push(num, &mut stack)?;
                         num = Number { ..DEFAULT };
                         lexer_state = LexerState::ExpectingOperator;
                     }
@@ -122,66 +614,217 @@ pub fn exec(expr: &str) -> Result<Fixed, (&'static str, char, usize)> {
             LexerState::ReadingNumberDecimal => {
                 match c {
                     '_' => (),
-                    d if d.to_digit(num.base).is_some() => {
-                        let digit = d.to_digit(num.base).unwrap() as u64;
-                        num.decimal = num.decimal * num.base as u64 + digit;
-                        num.decimal_count += 1;
-                        if decimal_part_is_full(&num) {
-                            lexer_state = LexerState::ReadingNumberTooLong;
-                        };
+                    'e' | 'E' if num.base == 10 => lexer_state = LexerState::ReadingNumberExponentSign,
+                    'p' | 'P' if num.base != 10 => lexer_state = LexerState::ReadingNumberExponentSign,
+                    d if d.is_digit(num.base) => {
+                        num.decimal.push(d.to_digit(num.base).unwrap());
+                    },
+                    'i' => {
+                        num.imaginary = true;
+                        lexer_state = LexerState::ReadingNumberSuffix;
                     },
                     _ => {
-                        push(num, &mut stack);
+                        push(num, &mut stack)?;
                         num = Number { ..DEFAULT };
                         lexer_state = LexerState::ExpectingOperator;
                     }
                 };
             },
-            LexerState::ReadingNumberTooLong => {
+            LexerState::ReadingNumberExponentSign => {
+                match c {
+                    '+' => lexer_state = LexerState::ReadingNumberExponentStart,
+                    '-' | '−' => {
+                        num.exponent_neg = true;
+                        lexer_state = LexerState::ReadingNumberExponentStart;
+                    },
+                    d @ '0'..='9' => {
+                        num.exponent = d as u32 - 0x30;
+                        lexer_state = LexerState::ReadingNumberExponent;
+                    },
+                    other => return Err(("Unexpected", other, index))
+                };
+            },
+            LexerState::ReadingNumberExponentStart => {
+                match c {
+                    d @ '0'..='9' => {
+                        num.exponent = d as u32 - 0x30;
+                        lexer_state = LexerState::ReadingNumberExponent;
+                    },
+                    other => return Err(("Unexpected", other, index))
+                };
+            },
+            LexerState::ReadingNumberExponent => {
                 match c {
                     '_' => (),
-                    d if d.to_digit(num.base).is_some() => (),
+                    d @ '0'..='9' => {
+                        num.exponent = num.exponent.saturating_mul(10).saturating_add(d as u32 - 0x30);
+                    },
+                    'i' => {
+                        num.imaginary = true;
+                        lexer_state = LexerState::ReadingNumberSuffix;
+                    },
                     _ => {
-                        push(num, &mut stack);
+                        push(num, &mut stack)?;
                         num = Number { ..DEFAULT };
                         lexer_state = LexerState::ExpectingOperator;
                     }
                 };
             },
+            LexerState::ReadingNumberSuffix => {
+                push(num, &mut stack)?;
+                num = Number { ..DEFAULT };
+                lexer_state = LexerState::ExpectingOperator;
+            },
             LexerState::ReadingOperator => {
-                let (op, is_two_chars) = match (curr_op, c) {
+                let (op, is_two_chars) = match (curr_op.0, c) {
+                    ('/', '/') => (Op::FloorDiv, true),
+                    ('%', '%') => (Op::EuclidMod, true),
+                    ('+', '/') => {
+                        lexer_state = LexerState::ReadingPlusMinus;
+                        continue;
+                    },
+                    ('*', '*') => (Op::Pow, true),
+                    ('<', '=') => (Op::Le, true),
+                    ('>', '=') => (Op::Ge, true),
+                    ('=', '=') => (Op::Eq, true),
+                    ('!', '=') => (Op::Ne, true),
                     ('+', _) => (Op::Add, false),
-                    ('-', _) => (Op::Sub, false),
-                    ('*', _) => (Op::Mul, false),
-                    ('/', _) => (Op::Div, false),
+                    ('-' | '−', _) => (Op::Sub, false),
+                    ('*' | '×' | '·', _) => (Op::Mul, false),
+                    ('/' | '÷', _) => (Op::Div, false),
                     ('%', _) => (Op::Mod, false),
                     ('&', _) => (Op::And, false),
                     ('|', _) => (Op::Or, false),
                     ('^', _) => (Op::Xor, false),
-                    ('<', '<') => (Op::Shl, true),
-                    ('>', '>') => (Op::Shr, true),
-                    _ => return Err(("Unexpected", c, index))
-                };
-                if *op_counts.last().unwrap() == 0 ||
-                    precedence(&op) > precedence(op_stack.last().unwrap())
-                {
-                    op_stack.push(op);
-                    let count = op_counts.pop().unwrap();
-                    op_counts.push(count + 1);
-                } else {
-                    for _ in 0..op_counts.pop().unwrap() {
-                        apply_top(&mut op_stack, &mut stack);
-                    };
-                    op_stack.push(op);
-                    op_counts.push(1);
+                    ('±', _) => (Op::PlusMinus, false),
+                    ('<', '<') | ('>', '>') => {
+                        lexer_state = LexerState::ReadingShift;
+                        continue;
+                    },
+                    ('<', _) => (Op::Lt, false),
+                    ('>', _) => (Op::Gt, false),
+                    ('≤', _) => (Op::Le, false),
+                    ('≥', _) => (Op::Ge, false),
+                    ('≠', _) => (Op::Ne, false),
+                    _ => return Err(("Unexpected", curr_op.0, curr_op.1))
                 };
+                push_op((op, curr_op.1), &mut op_stack, &mut op_counts, &mut stack, settings)?;
                 match is_two_chars {
                     false => lexer_state = LexerState::ExpectingNumber,
                     true => lexer_state = LexerState::IgnoreThatChar
                 }
             },
+            LexerState::ReadingShift => {
+                let op = match (curr_op.0, c == curr_op.0) {
+                    ('<', false) => Op::Shl,
+                    ('>', false) => Op::Shr,
+                    ('<', true) => Op::Rotl,
+                    _ => Op::Rotr
+                };
+                push_op((op, curr_op.1), &mut op_stack, &mut op_counts, &mut stack, settings)?;
+                match c == curr_op.0 {
+                    false => lexer_state = LexerState::ExpectingNumber,
+                    true => lexer_state = LexerState::IgnoreThatChar
+                }
+            },
+            LexerState::ReadingPlusMinus => {
+                match c {
+                    '-' => {
+                        push_op((Op::PlusMinus, curr_op.1), &mut op_stack, &mut op_counts, &mut stack, settings)?;
+                        lexer_state = LexerState::IgnoreThatChar;
+                    },
+                    other => return Err(("Unexpected", other, index))
+                };
+            },
+            LexerState::ReadingName => {
+                match c {
+                    c if is_name_char(c) => name.0.push(c),
+                    // A type alone in parentheses is a cast of the next operand
+                    ')' if *op_counts.last().unwrap() == 0 && op_counts.len() > 1 && name.0.parse::<IntType>().is_ok() => {
+                        op_counts.pop();
+                        prefixes.push((Prefix::Cast(name.0.parse().unwrap()), op_counts.len(), parens_indices.pop().unwrap()));
+                        lexer_state = LexerState::IgnoreThatChar;
+                    },
+                    '(' if is_expression_call(&name.0, index, chars) => {
+                        skip_until = expression_call(&name, index, chars, &mut stack, variables, settings)? + 1;
+                        lexer_state = LexerState::ExpectingOperator;
+                        continue;
+                    },
+                    '(' => {
+                        op_counts.push(0);
+                        parens_indices.push(index);
+                        calls.push((std::mem::take(&mut name.0), op_counts.len(), name.1, 1));
+                        lexer_state = LexerState::IgnoreThatChar;
+                    },
+                    _ => {
+                        name_top(&name, &mut stack, variables, settings)?;
+                        lexer_state = LexerState::ExpectingOperator;
+                    }
+                };
+            },
+            LexerState::ReadingKeyword => {
+                match c {
+                    c if continues_unit(c, name.0.chars().last(), chars.get(index + 1)) => name.0.push(c),
+                    c if continues_unit_glyph(c, &name.0, chars.get(index + 1), stack.last(), settings) => {
+                        push_unit_glyph(&mut name.0, c, chars, index)
+                    },
+                    _ if name.0 == "as" => lexer_state = LexerState::ExpectingType,
+                    // The conversion applies to everything before it in the parentheses
+                    _ if name.0 == "to" => {
+                        for _ in 0..op_counts.pop().unwrap() {
+                            apply_top(&mut op_stack, &mut stack, settings)?;
+                        };
+                        op_counts.push(0);
+                        lexer_state = LexerState::ExpectingUnit;
+                    },
+                    // A function called right after an operand, as in `2sqrt(9)`
+                    '(' if settings.implicit_multiplication.is_some() => {
+                        juxtapose(c, name.1, &mut op_stack, &mut op_counts, &mut stack, settings)?;
+                        op_counts.push(0);
+                        parens_indices.push(index);
+                        calls.push((std::mem::take(&mut name.0), op_counts.len(), name.1, 1));
+                        lexer_state = LexerState::IgnoreThatChar;
+                    },
+                    _ => {
+                        name_after_top(&name, &mut op_stack, &mut op_counts, &mut stack, variables, settings)?;
+                        lexer_state = LexerState::ExpectingOperator;
+                    }
+                };
+            },
+            LexerState::ReadingUnit => {
+                match c {
+                    c if continues_unit(c, name.0.chars().last(), chars.get(index + 1)) => name.0.push(c),
+                    c if continues_unit_glyph::<V>(c, &name.0, chars.get(index + 1), None, settings) => {
+                        push_unit_glyph(&mut name.0, c, chars, index)
+                    },
+                    _ => {
+                        unit_top(&name, true, &mut stack, settings)?;
+                        lexer_state = LexerState::ExpectingOperator;
+                    }
+                };
+            },
+            LexerState::ReadingType => {
+                match c {
+                    c if is_name_char(c) => name.0.push(c),
+                    _ => {
+                        cast_top(&name, &mut stack)?;
+                        lexer_state = LexerState::ExpectingOperator;
+                    }
+                };
+            },
+            LexerState::ReadingSuperscript => {
+                match superscript(c) {
+                    Some(c) => name.0.push(c),
+                    None => {
+                        superscript_top(&name.0, curr_op, &mut stack, settings)?;
+                        lexer_state = LexerState::ExpectingOperator;
+                    }
+                };
+            },
             LexerState::ExpectingNumber
             | LexerState::ExpectingOperator
+            | LexerState::ExpectingType
+            | LexerState::ExpectingUnit
             | LexerState::IgnoreThatChar
             => ()
         };
@@ -189,13 +832,58 @@ pub fn exec(expr: &str) -> Result<Fixed, (&'static str, char, usize)> {
         match lexer_state {
             LexerState::IgnoreThatChar => lexer_state = LexerState::ExpectingNumber,
             LexerState::ExpectingOperator => {
-                if c == ')' {
-                    if op_counts.len() == 1 { return Err(("Unmatched", c, index)) };
+                apply_prefixes(&mut prefixes, op_counts.len(), &mut stack, settings)?;
+                if c == ')' || c == ']' {
+                    // A list is closed by `]`, and a group or the arguments of a function by `)`
+                    let in_list = calls.last().is_some_and(|call| call.1 == op_counts.len() && call.0 == "[");
+                    if op_counts.len() == 1 || in_list != (c == ']') { return Err(("Unmatched", c, index)) };
                     for _ in 0..op_counts.pop().unwrap() {
-                        apply_top(&mut op_stack, &mut stack);
+                        apply_top(&mut op_stack, &mut stack, settings)?;
+                    };
+                    parens_indices.pop();
+                    if calls.last().is_some_and(|call| call.1 == op_counts.len() + 1) {
+                        let (name, _, start, count) = calls.pop().unwrap();
+                        let args = stack.split_off(stack.len() - count);
+                        let result = match in_list {
+                            true => V::list(args),
+                            false => V::call(&name, args, settings)
+                        };
+                        stack.push( result.map_err(|e| (e, name.chars().next().unwrap(), start))? );
                     };
+                } else if c == ',' && calls.last().is_some_and(|call| call.1 == op_counts.len()) {
+                    // Each argument of a function is evaluated like a group of parentheses
+                    for _ in 0..op_counts.pop().unwrap() {
+                        apply_top(&mut op_stack, &mut stack, settings)?;
+                    };
+                    op_counts.push(0);
+                    calls.last_mut().unwrap().3 += 1;
+                    lexer_state = LexerState::ExpectingNumber;
+                } else if c == '!' && chars.get(index + 1) != Some(&'=') {
+                    let value = stack.pop().unwrap();
+                    stack.push( V::call("factorial", vec![value], settings).map_err(|e| (e, c, index))? );
+                } else if c == '%' && !settings.strict_percent && is_percent(chars, index) {
+                    percent_top(&mut op_stack, &mut op_counts, &mut stack, settings, index)?;
+                } else if let Some(digit) = superscript(c) {
+                    name = (String::from(digit), index);
+                    curr_op = (c, index);
+                    lexer_state = LexerState::ReadingSuperscript;
+                } else if c == '√' {
+                    juxtapose(c, index, &mut op_stack, &mut op_counts, &mut stack, settings)?;
+                    prefixes.push((Prefix::Root, op_counts.len(), index));
+                    lexer_state = LexerState::ExpectingNumber;
+                } else if c == 'π' {
+                    juxtapose(c, index, &mut op_stack, &mut op_counts, &mut stack, settings)?;
+                    name_top(&(String::from("pi"), index), &mut stack, variables, settings)?;
+                } else if c == '(' {
+                    juxtapose(c, index, &mut op_stack, &mut op_counts, &mut stack, settings)?;
+                    op_counts.push(0);
+                    parens_indices.push(index);
+                    lexer_state = LexerState::ExpectingNumber;
+                } else if is_name_char(c) {
+                    name = (String::from(c), index);
+                    lexer_state = LexerState::ReadingKeyword;
                 } else if OP_CHARS.contains(c) {
-                    curr_op = c;
+                    curr_op = (c, index);
                     lexer_state = LexerState::ReadingOperator;
                 } else {
                     return Err(("Unexpected", c, index))
@@ -207,17 +895,60 @@ pub fn exec(expr: &str) -> Result<Fixed, (&'static str, char, usize)> {
                         op_counts.push(0);
                         parens_indices.push(index);
                     },
-                    '-' => {
+                    '[' => {
+                        op_counts.push(0);
+                        parens_indices.push(index);
+                        calls.push((String::from(c), op_counts.len(), index, 1));
+                    },
+                    '-' | '−' => {
                         num.neg = true;
                         lexer_state = LexerState::ReadingNumberSign;
                     },
+                    '√' => prefixes.push((Prefix::Root, op_counts.len(), index)),
+                    'π' => {
+                        name_top(&(String::from("pi"), index), &mut stack, variables, settings)?;
+                        lexer_state = LexerState::ExpectingOperator;
+                    },
                     '+' => lexer_state = LexerState::ReadingNumberSign,
                     '0' => lexer_state = LexerState::ReadingNumberFormat,
                     '.' => lexer_state = LexerState::ReadingNumberDecimal,
                     d @ '1'..='9' => {
-                        num.whole = d as u32 - 0x30;
+                        num.whole.push(d as u32 - 0x30);
                         lexer_state = LexerState::ReadingNumberWhole;
                     },
+                    c if is_name_char(c) => {
+                        name = (String::from(c), index);
+                        lexer_state = LexerState::ReadingName;
+                    },
+                    // `[]` is an empty list
+                    ']' if parens_indices.last().is_some_and(|&open| {
+                        chars[open] == '[' && chars[open + 1..index].iter().all(|c| c.is_whitespace())
+                    }) => {
+                        op_counts.pop();
+                        parens_indices.pop();
+                        let (_, _, start, _) = calls.pop().unwrap();
+                        stack.push( V::list(Vec::new()).map_err(|e| (e, '[', start))? );
+                        lexer_state = LexerState::ExpectingOperator;
+                    },
+                    other => return Err(("Unexpected", other, index))
+                };
+                num.start = (c, index);
+            },
+            LexerState::ExpectingType => {
+                match c {
+                    c if is_name_char(c) => {
+                        name = (String::from(c), index);
+                        lexer_state = LexerState::ReadingType;
+                    },
+                    other => return Err(("Unexpected", other, index))
+                };
+            },
+            LexerState::ExpectingUnit => {
+                match c {
+                    c if c.is_ascii_alphabetic() => {
+                        name = (String::from(c), index);
+                        lexer_state = LexerState::ReadingUnit;
+                    },
                     other => return Err(("Unexpected", other, index))
                 };
             },
@@ -225,8 +956,18 @@ pub fn exec(expr: &str) -> Result<Fixed, (&'static str, char, usize)> {
             | LexerState::ReadingNumberFormat
             | LexerState::ReadingNumberWhole
             | LexerState::ReadingNumberDecimal
-            | LexerState::ReadingNumberTooLong
+            | LexerState::ReadingNumberExponentSign
+            | LexerState::ReadingNumberExponentStart
+            | LexerState::ReadingNumberExponent
+            | LexerState::ReadingNumberSuffix
             | LexerState::ReadingOperator
+            | LexerState::ReadingShift
+            | LexerState::ReadingPlusMinus
+            | LexerState::ReadingName
+            | LexerState::ReadingKeyword
+            | LexerState::ReadingType
+            | LexerState::ReadingUnit
+            | LexerState::ReadingSuperscript
             => ()
         };
     };
@@ -236,21 +977,38 @@ pub fn exec(expr: &str) -> Result<Fixed, (&'static str, char, usize)> {
         LexerState::ReadingNumberFormat
         | LexerState::ReadingNumberWhole
         | LexerState::ReadingNumberDecimal
-        | LexerState::ReadingNumberTooLong
-        => push(num, &mut stack),
+        | LexerState::ReadingNumberExponent
+        | LexerState::ReadingNumberSuffix
+        => push(num, &mut stack)?,
+
+        LexerState::ReadingType => cast_top(&name, &mut stack)?,
+        LexerState::ReadingKeyword if name.0 != "as" && name.0 != "to" => {
+            name_after_top(&name, &mut op_stack, &mut op_counts, &mut stack, variables, settings)?
+        },
+        LexerState::ReadingUnit => unit_top(&name, true, &mut stack, settings)?,
+        LexerState::ReadingSuperscript => superscript_top(&name.0, curr_op, &mut stack, settings)?,
+        LexerState::ReadingName => name_top(&name, &mut stack, variables, settings)?,
 
         LexerState::IgnoreThatChar
         | LexerState::ExpectingNumber
         | LexerState::ReadingNumberSign
+        | LexerState::ReadingNumberExponentSign
+        | LexerState::ReadingNumberExponentStart
         | LexerState::ReadingOperator
-        => return Err(("Unexpected end of expression", '\'', expr.len()))
+        | LexerState::ReadingShift
+        | LexerState::ReadingPlusMinus
+        | LexerState::ReadingKeyword
+        | LexerState::ExpectingType
+        | LexerState::ExpectingUnit
+        => return Err(("Unexpected end of expression", '\'', chars.len()))
     };
+    apply_prefixes(&mut prefixes, op_counts.len(), &mut stack, settings)?;
     if op_counts.len() > 1 {
         let idx = op_counts.len() - 2;
-        return Err(("Unmatched", '(', parens_indices[idx]))
+        return Err(("Unmatched", chars[parens_indices[idx]], parens_indices[idx]))
     };
     for _ in 0..op_counts.pop().unwrap() {
-        apply_top(&mut op_stack, &mut stack);
+        apply_top(&mut op_stack, &mut stack, settings)?;
     };
     Ok(stack.pop().unwrap())
 }
@@ -0,0 +1,183 @@
+use crate::bigint::BigInt;
+use crate::fixed::{Fixed, Repr, RoundingMode};
+use crate::ops::{Op, Operand, Settings, INTEGER_FUNCTIONS, holds, single};
+use crate::parser::Number;
+use std::fmt::{
+    self, Display
+};
+
+/// An exact fraction of arbitrary-precision integers, kept reduced
+/// and with a positive denominator
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rational {
+    num: BigInt,
+    den: BigInt
+}
+
+#[allow(unused)]
+impl Rational {
+    /// Panics when `den` is zero
+    pub fn new(num: BigInt, den: BigInt) -> Self {
+        if den.is_zero() { panic!("zero denominator") };
+        let gcd = num.gcd(&den);
+        let (num, den) = (&num / &gcd, &den / &gcd);
+        match den.is_negative() {
+            false => Rational { num, den },
+            true => Rational { num: -num, den: -den }
+        }
+    }
+    pub fn from_int(num: BigInt) -> Self {
+        Rational { num, den: BigInt::one() }
+    }
+    pub fn numer(&self) -> &BigInt { &self.num }
+    pub fn denom(&self) -> &BigInt { &self.den }
+    pub fn is_integer(&self) -> bool { self.den == BigInt::one() }
+
+    fn into_integer(self) -> Result<BigInt, &'static str> {
+        match self.is_integer() {
+            true => Ok(self.num),
+            false => Err("Not an integer")
+        }
+    }
+
+    /// The nearest `Fixed` in the direction given by `mode`, if it is in range
+    pub fn to_fixed<const FRAC: u32, T: Repr>(&self, mode: RoundingMode) -> Option<Fixed<FRAC, T>> {
+        let scaled = self.num.abs().shl(FRAC as usize);
+        let (quotient, rem) = scaled.divrem(&self.den);
+        let half = rem.shl(1).cmp(&self.den);
+        let neg = self.num.is_negative();
+        let abs = match mode.round_up(neg, quotient.is_odd(), half, !rem.is_zero()) {
+            true => quotient + BigInt::one(),
+            false => quotient
+        };
+        T::from_magnitude(neg, abs.to_u128()?).map(Fixed::from_raw)
+    }
+
+    /// The exact value of a `Fixed`
+    pub fn from_fixed<const FRAC: u32, T: Repr>(x: Fixed<FRAC, T>) -> Self {
+        let abs = BigInt::from(x.as_raw().unsigned_abs());
+        let num = match x.as_raw().is_negative() {
+            true => -abs,
+            false => abs
+        };
+        Rational::new(num, BigInt::one().shl(FRAC as usize))
+    }
+
+    /// The nearest multiple of 10^-`decimals` in the direction given by `mode`,
+    /// scaled by 10^`decimals`
+    fn scaled_decimal(&self, decimals: usize, mode: RoundingMode) -> BigInt {
+        let scaled = &self.num.abs() * &BigInt::from(10u32).pow(decimals as u32);
+        let (quotient, rem) = scaled.divrem(&self.den);
+        let half = rem.shl(1).cmp(&self.den);
+        let neg = self.num.is_negative();
+        let abs = match mode.round_up(neg, quotient.is_odd(), half, !rem.is_zero()) {
+            true => quotient + BigInt::one(),
+            false => quotient
+        };
+        match neg {
+            true => -abs,
+            false => abs
+        }
+    }
+
+    /// The nearest multiple of 10^-`decimals` in the direction given by `mode`
+    pub fn round_decimal(&self, decimals: usize, mode: RoundingMode) -> Self {
+        Rational::new(self.scaled_decimal(decimals, mode), BigInt::from(10u32).pow(decimals as u32))
+    }
+
+    /// The value written with exactly `decimals` digits after the point,
+    /// rounded in the direction given by `mode`
+    pub fn to_decimal_places(&self, decimals: usize, mode: RoundingMode) -> String {
+        let scaled = self.scaled_decimal(decimals, mode);
+        let sign = if scaled.is_negative() { "-" } else { "" };
+        let digits = format!("{:0>width$}", scaled.abs().to_string(), width = decimals + 1);
+        let (whole, decimal) = digits.split_at(digits.len() - decimals);
+        match decimal {
+            "" => format!("{sign}{whole}"),
+            decimal => format!("{sign}{whole}.{decimal}")
+        }
+    }
+
+    /// The value written with at most `decimals` digits after the point,
+    /// rounded in the direction given by `mode`
+    pub fn to_decimal_string(&self, decimals: usize, mode: RoundingMode) -> String {
+        let digits = self.to_decimal_places(decimals, mode);
+        match digits.contains('.') {
+            true => String::from(digits.trim_end_matches('0').trim_end_matches('.')),
+            false => digits
+        }
+    }
+}
+
+impl Operand for Rational {
+    fn from_number(num: Number) -> Result<Self, &'static str> {
+        let (numer, denom) = num.into_fraction()?;
+        Ok(Rational::new(numer, denom))
+    }
+
+    fn is_true(&self) -> Result<bool, &'static str> {
+        Ok(!self.num.is_zero())
+    }
+
+    /// `//` and `%%` are defined on all fractions, `**` needs a whole exponent,
+    /// `%`, `&`, `|`, `^` and the shifts are only defined on integers,
+    /// where they behave as in the integer mode
+    fn apply(op: &Op, a: Self, b: Self, settings: &Settings) -> Result<Self, &'static str> {
+        let result = match op {
+            Op::Add => Rational::new(&a.num * &b.den + &b.num * &a.den, &a.den * &b.den),
+            Op::Sub => Rational::new(&a.num * &b.den - &b.num * &a.den, &a.den * &b.den),
+            Op::Mul | Op::ImplicitMul => Rational::new(&a.num * &b.num, &a.den * &b.den),
+            Op::Div | Op::FloorDiv | Op::EuclidMod if b.num.is_zero() => return Err("Division by zero"),
+            Op::Div => Rational::new(&a.num * &b.den, &a.den * &b.num),
+            Op::FloorDiv => Rational::from_int((&a.num * &b.den).divrem_floor(&(&a.den * &b.num)).0),
+            Op::EuclidMod => {
+                // a - |b| * floor(a / |b|), over the common denominator
+                let (x, y) = (&a.num * &b.den, (&b.num * &a.den).abs());
+                Rational::new(x.divrem_euclid(&y).1, &a.den * &b.den)
+            },
+            Op::Pow => {
+                let n = b.into_integer().map_err(|_| "Fractional exponent")?;
+                let exp = n.abs();
+                let (num, den) = (BigInt::apply(op, a.num, exp.clone(), settings)?, BigInt::apply(op, a.den, exp, settings)?);
+                match n.is_negative() {
+                    false => Rational::new(num, den),
+                    true if num.is_zero() => return Err("Division by zero"),
+                    true => Rational::new(den, num)
+                }
+            },
+            Op::Lt | Op::Le | Op::Gt | Op::Ge | Op::Eq | Op::Ne => {
+                let ordering = (&a.num * &b.den).cmp(&(&b.num * &a.den));
+                Rational::from_int(BigInt::from(holds(op, ordering) as u32))
+            },
+            Op::PlusMinus => return Err("Uncertainties need the uncertain mode"),
+            Op::Mod | Op::And | Op::Or | Op::Xor | Op::Shl | Op::Shr | Op::Rotl | Op::Rotr => {
+                let (x, y) = (a.into_integer()?, b.into_integer()?);
+                Rational::from_int(BigInt::apply(op, x, y, settings)?)
+            }
+        };
+        Ok(result)
+    }
+
+    fn call(name: &str, args: Vec<Self>, settings: &Settings) -> Result<Self, &'static str> {
+        match name {
+            "abs" => {
+                let x = single(args)?;
+                Ok(Rational { num: x.num.abs(), den: x.den })
+            },
+            _ if INTEGER_FUNCTIONS.contains(&name) => {
+                let args = args.into_iter().map(Rational::into_integer).collect::<Result<_, _>>()?;
+                Ok(Rational::from_int(BigInt::call(name, args, settings)?))
+            },
+            _ => Err("Unknown function")
+        }
+    }
+}
+
+impl Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.is_integer() {
+            true => write!(f, "{}", self.num),
+            false => write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
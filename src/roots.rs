@@ -0,0 +1,121 @@
+use crate::calculus::{Function, derivative};
+use crate::fixed::{Fixed, Repr};
+use crate::ops::Settings;
+
+/// Root finding fails after this many steps, as the method doesn't converge
+const MAX_ITERATIONS: usize = 200;
+
+/// Roots are found to within this many of the smallest step of the format
+const TOLERANCE: u128 = 4;
+
+fn tolerance<const FRAC: u32, T: Repr>() -> Fixed<FRAC, T> {
+    Fixed::from_raw(T::from_magnitude(false, TOLERANCE).unwrap())
+}
+
+fn is_negative<const FRAC: u32, T: Repr>(x: Fixed<FRAC, T>) -> bool {
+    x.as_raw().is_negative()
+}
+
+/// A root of `f` by Newton's method from `guess`, with the derivative by central differences
+pub fn newton<const FRAC: u32, T: Repr>(
+    f: Function<FRAC, T>, guess: Fixed<FRAC, T>, settings: &Settings
+) -> Result<Fixed<FRAC, T>, &'static str> {
+    let mut x = guess;
+    for _ in 0..MAX_ITERATIONS {
+        let y = f(x)?;
+        if y == Fixed::ZERO { return Ok(x) };
+        let slope = derivative(f, x, settings)?;
+        if slope == Fixed::ZERO { return Err("Zero derivative") };
+        let step = y.checked_div_round(slope, settings.rounding).ok_or("Method diverged")?;
+        x = x.checked_sub(step).ok_or("Method diverged")?;
+        if step.abs() <= tolerance() { return Ok(x) };
+    };
+    Err("No convergence")
+}
+
+/// The step of inverse quadratic interpolation (or of the secant method, when `a` is `c`)
+/// from `b` to the root, as a fraction `p / q` with `p` positive, or none when it is out of range
+fn interpolation<const FRAC: u32, T: Repr>(
+    points: [(Fixed<FRAC, T>, Fixed<FRAC, T>); 3], m: Fixed<FRAC, T>, settings: &Settings
+) -> Option<(Fixed<FRAC, T>, Fixed<FRAC, T>)> {
+    let [(a, fa), (b, fb), (c, fc)] = points;
+    let mul = |x: Fixed<FRAC, T>, y| x.checked_mul_round(y, settings.rounding);
+    let div = |x: Fixed<FRAC, T>, y: Fixed<FRAC, T>| match y == Fixed::ZERO {
+        true => None,
+        false => x.checked_div_round(y, settings.rounding)
+    };
+    let one = Fixed::one()?;
+    let s = div(fb, fa)?;
+    let (p, q) = match a == c {
+        true => (mul(m.checked_add(m)?, s)?, one.checked_sub(s)?),
+        false => {
+            let (q, r) = (div(fa, fc)?, div(fb, fc)?);
+            let left = mul(mul(m.checked_add(m)?, q)?, q.checked_sub(r)?)?;
+            let right = mul(b.checked_sub(a)?, r.checked_sub(one)?)?;
+            let p = mul(s, left.checked_sub(right)?)?;
+            (p, mul(mul(q.checked_sub(one)?, r.checked_sub(one)?)?, s.checked_sub(one)?)?)
+        }
+    };
+    match is_negative(p) {
+        true => Some((Fixed::ZERO.checked_sub(p)?, q)),
+        false => Some((p, Fixed::ZERO.checked_sub(q)?))
+    }
+}
+
+/// A root of `f` between `a` and `b`, where it changes sign, by Brent's method: inverse quadratic
+/// interpolation when it makes progress, and bisection otherwise, so that the bracket always shrinks
+pub fn brent<const FRAC: u32, T: Repr>(
+    f: Function<FRAC, T>, a: Fixed<FRAC, T>, b: Fixed<FRAC, T>, settings: &Settings
+) -> Result<Fixed<FRAC, T>, &'static str> {
+    let out_of_range = "Result out of range";
+    let tolerance = tolerance();
+    let (mut a, mut b) = (a, b);
+    let (mut fa, mut fb) = (f(a)?, f(b)?);
+    if fa == Fixed::ZERO { return Ok(a) };
+    if fb == Fixed::ZERO { return Ok(b) };
+    if is_negative(fa) == is_negative(fb) { return Err("No sign change") };
+    let (mut c, mut fc) = (a, fa);
+    let mut d = b.checked_sub(a).ok_or(out_of_range)?;
+    let mut e = d;
+    for _ in 0..MAX_ITERATIONS {
+        // The root is between `b` and `c`, and `b` is the best estimate
+        if is_negative(fb) == is_negative(fc) {
+            (c, fc) = (a, fa);
+            d = b.checked_sub(a).ok_or(out_of_range)?;
+            e = d;
+        };
+        if fc.abs() < fb.abs() {
+            (a, fa) = (b, fb);
+            (b, fb) = (c, fc);
+            (c, fc) = (a, fa);
+        };
+        let m = c.checked_sub(b).ok_or(out_of_range)?.shr_bits(1);
+        if m.abs() <= tolerance || fb == Fixed::ZERO { return Ok(b) };
+        let interpolated = match e.abs() >= tolerance && fa.abs() > fb.abs() {
+            true => interpolation([(a, fa), (b, fb), (c, fc)], m, settings).filter(|&(p, q)| {
+                // The step has to stay well inside the bracket, and shrink fast enough
+                let mul = |x: Fixed<FRAC, T>, y| x.checked_mul_round(y, settings.rounding);
+                let bound = mul(m, q).and_then(|mq| mq.checked_add(mq)?.checked_add(mq));
+                let bound = bound.zip(mul(tolerance, q)).map(|(x, y)| x.checked_sub(y.abs()).unwrap_or(x));
+                let previous = mul(e, q).map(|x| x.abs());
+                match (p.checked_add(p), bound, previous) {
+                    (Some(p2), Some(bound), Some(previous)) => p2 < bound && p2 < previous && q != Fixed::ZERO,
+                    _ => false
+                }
+            }),
+            false => None
+        };
+        match interpolated.and_then(|(p, q)| p.checked_div_round(q, settings.rounding)) {
+            Some(step) => (e, d) = (d, step),
+            None => (e, d) = (m, m)
+        };
+        (a, fa) = (b, fb);
+        b = match d.abs() > tolerance {
+            true => b.checked_add(d),
+            false if is_negative(m) => b.checked_sub(tolerance),
+            false => b.checked_add(tolerance)
+        }.ok_or(out_of_range)?;
+        fb = f(b)?;
+    };
+    Err("No convergence")
+}
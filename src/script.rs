@@ -0,0 +1,349 @@
+use crate::glyphs::spells;
+use crate::ops::{Op, Operand, Settings};
+use crate::parser::{Number, eval, is_name_char};
+use std::ops::Range;
+
+/// Statements and loop conditions a script may run when `Settings::max_steps` isn't given
+pub const DEFAULT_MAX_STEPS: usize = 1_000_000;
+const KEYWORDS: [&str; 6] = ["if", "else", "while", "for", "in", "return"];
+
+/// Statements, with the index where each one starts
+type Block = Vec<(usize, Statement)>;
+
+/// A statement of a script, with its expressions as bounds among the characters of the script
+enum Statement {
+    /// An expression, whose value is given
+    Expression(Range<usize>),
+    /// `name = expr`, or `name += expr` with the operator written before `=` (`+`, `-`, `*` or `/`)
+    Assign((String, usize), Option<(Op, char, usize)>, Range<usize>),
+    /// `if cond { } else if cond { } else { }`, as each condition with its block, and the block of `else`
+    If(Vec<(Range<usize>, Block)>, Block),
+    /// `while cond { }`
+    While(Range<usize>, Block),
+    /// `for name in start..end { }`, which counts from `start` by steps of 1 while below `end`
+    For((String, usize), Range<usize>, Range<usize>, Block),
+    /// `return expr`, which ends the script with the value of `expr`
+    Return(Range<usize>),
+    /// `{ }`, a block of its own
+    Block(Block)
+}
+
+/// Runs a script with values of type `V`, giving the values of its expressions as they are run,
+/// and last the value of `return` when the script ends with it
+/// Statements are separated by `;` or line breaks outside of parentheses and brackets, and variables
+/// assigned in a block go out of scope at its end
+/// Errors point at a character by its index among the characters of the script
+pub fn exec_script<V: Operand>(script: &str, settings: &Settings) -> Result<Vec<V>, (&'static str, char, usize)> {
+    let original: Vec<char> = script.chars().collect();
+    let mut chars = original.clone();
+    // Errors found on the ASCII spelling of a glyph show the glyph
+    let show_glyph = |(e, c, index)| match original.get(index) {
+        Some(&glyph) if spells(glyph, c) => (e, glyph, index),
+        _ => (e, c, index)
+    };
+    blank_comments(&mut chars)?;
+    let block = parse_block(&chars, &mut 0, None)?;
+    let mut run = Run { chars: &chars, settings, variables: Vec::new(), values: Vec::new(), steps: 0 };
+    run.run_block(&block).map_err(show_glyph)?;
+    Ok(run.values)
+}
+
+/// Blanks out the comments of a script: from `#` to the end of the line, and from `/*` to `*/`
+/// (`//` is always a floor division)
+fn blank_comments(chars: &mut [char]) -> Result<(), (&'static str, char, usize)> {
+    let mut i = 0;
+    while i < chars.len() {
+        match (chars[i], chars.get(i + 1)) {
+            ('#', _) => {
+                while i < chars.len() && chars[i] != '\n' {
+                    chars[i] = ' ';
+                    i += 1;
+                };
+            },
+            ('/', Some('*')) => {
+                let end = match (i + 2..chars.len()).find(|&j| chars[j] == '*' && chars.get(j + 1) == Some(&'/')) {
+                    Some(end) => end + 2,
+                    None => return Err(("Unterminated comment", '/', i))
+                };
+                chars[i..end].fill(' ');
+                i = end;
+            },
+            _ => i += 1
+        };
+    };
+    Ok(())
+}
+
+fn unexpected(chars: &[char], index: usize) -> (&'static str, char, usize) {
+    match chars.get(index) {
+        Some(&c) => ("Unexpected", c, index),
+        None => ("Unexpected end of expression", '\'', index)
+    }
+}
+
+/// The index of the first character from `pos` which isn't blank
+fn skip_blanks(chars: &[char], pos: usize) -> usize {
+    (pos..chars.len()).find(|&i| !chars[i].is_whitespace()).unwrap_or(chars.len())
+}
+
+/// The name starting at `pos`, if there is one
+fn word_at(chars: &[char], pos: usize) -> Option<String> {
+    match chars.get(pos) {
+        Some(&c) if c.is_ascii_alphabetic() || c == '_' => Some(chars[pos..].iter().take_while(|&&c| is_name_char(c)).collect()),
+        _ => None
+    }
+}
+
+/// The bounds of `start..end` without the blanks around it
+fn trimmed(chars: &[char], start: usize, end: usize) -> Range<usize> {
+    let start = (start..end).find(|&i| !chars[i].is_whitespace()).unwrap_or(end);
+    let end = (start..end).rev().find(|&i| !chars[i].is_whitespace()).map_or(start, |i| i + 1);
+    start..end
+}
+
+/// The end of the expression starting at `start`: the first of `stops` outside of parentheses
+/// and brackets
+fn expression_end(chars: &[char], start: usize, stops: &[char]) -> usize {
+    let mut depth = 0;
+    for (i, &c) in chars.iter().enumerate().skip(start) {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            c if depth <= 0 && stops.contains(&c) => return i,
+            _ => ()
+        };
+    };
+    chars.len()
+}
+
+/// Reads the statements from `pos` up to the `}` closing the block opened at `open`,
+/// or up to the end of the script
+fn parse_block(chars: &[char], pos: &mut usize, open: Option<usize>) -> Result<Block, (&'static str, char, usize)> {
+    let mut block = Vec::new();
+    loop {
+        while chars.get(*pos).is_some_and(|&c| c.is_whitespace() || c == ';') { *pos += 1 };
+        let start = *pos;
+        let word = word_at(chars, start);
+        let statement = match (chars.get(start), word.as_deref()) {
+            (None, _) => return match open {
+                None => Ok(block),
+                Some(open) => Err(("Unmatched", '{', open))
+            },
+            (Some('}'), _) => {
+                *pos += 1;
+                return match open {
+                    Some(_) => Ok(block),
+                    None => Err(("Unmatched", '}', start))
+                }
+            },
+            (Some('{'), _) => {
+                *pos = start + 1;
+                Statement::Block(parse_block(chars, pos, Some(start))?)
+            },
+            (_, Some("if")) => parse_if(chars, pos)?,
+            (_, Some("while")) => {
+                *pos = start + 5;
+                let (condition, block) = condition_block(chars, pos)?;
+                Statement::While(condition, block)
+            },
+            (_, Some("for")) => parse_for(chars, pos)?,
+            (_, Some("return")) => {
+                *pos = expression_end(chars, start + 6, &[';', '\n', '}']);
+                Statement::Return(trimmed(chars, start + 6, *pos))
+            },
+            (_, Some("else" | "in")) => return Err(("Unexpected", chars[start], start)),
+            (_, word) => {
+                *pos = expression_end(chars, start, &[';', '\n', '}']);
+                match word {
+                    Some(name) => assignment(chars, (name, start), *pos),
+                    None => Statement::Expression(trimmed(chars, start, *pos))
+                }
+            }
+        };
+        block.push((start, statement));
+    }
+}
+
+/// Reads the statement `name = expr` or `name += expr` ending at `end`, or else an expression
+fn assignment(chars: &[char], name: (&str, usize), end: usize) -> Statement {
+    let (name, start) = name;
+    let op_index = skip_blanks(chars, start + name.len());
+    let (op, expr_start) = match (chars.get(op_index).filter(|_| op_index < end), chars.get(op_index + 1)) {
+        (Some('='), next) if next != Some(&'=') => (None, op_index + 1),
+        (Some(&c @ ('+' | '-' | '*' | '/')), Some('=')) => {
+            let op = match c {
+                '+' => Op::Add,
+                '-' => Op::Sub,
+                '*' => Op::Mul,
+                _ => Op::Div
+            };
+            (Some((op, c, op_index)), op_index + 2)
+        },
+        _ => return Statement::Expression(trimmed(chars, start, end))
+    };
+    Statement::Assign((String::from(name), start), op, trimmed(chars, expr_start, end))
+}
+
+/// Reads a condition from `pos` up to the `{` of its block, and the block
+fn condition_block(chars: &[char], pos: &mut usize) -> Result<(Range<usize>, Block), (&'static str, char, usize)> {
+    let end = expression_end(chars, *pos, &['{', ';', '}']);
+    if chars.get(end) != Some(&'{') { return Err(unexpected(chars, end)) };
+    let condition = trimmed(chars, *pos, end);
+    *pos = end + 1;
+    Ok((condition, parse_block(chars, pos, Some(end))?))
+}
+
+/// Reads `if cond { }`, with the `else if cond { }` and `else { }` following it
+fn parse_if(chars: &[char], pos: &mut usize) -> Result<Statement, (&'static str, char, usize)> {
+    let mut branches = Vec::new();
+    let mut keyword = *pos;
+    loop {
+        *pos = keyword + 2;
+        branches.push(condition_block(chars, pos)?);
+        let next = skip_blanks(chars, *pos);
+        if word_at(chars, next).as_deref() != Some("else") {
+            return Ok(Statement::If(branches, Vec::new()))
+        };
+        let after = skip_blanks(chars, next + 4);
+        match (word_at(chars, after).as_deref(), chars.get(after)) {
+            (Some("if"), _) => keyword = after,
+            (_, Some('{')) => {
+                *pos = after + 1;
+                return Ok(Statement::If(branches, parse_block(chars, pos, Some(after))?))
+            },
+            _ => return Err(unexpected(chars, after))
+        };
+    }
+}
+
+/// Reads `for name in start..end { }`
+fn parse_for(chars: &[char], pos: &mut usize) -> Result<Statement, (&'static str, char, usize)> {
+    let name_start = skip_blanks(chars, *pos + 3);
+    let name = match word_at(chars, name_start) {
+        Some(name) if !KEYWORDS.contains(&name.as_str()) => name,
+        _ => return Err(unexpected(chars, name_start))
+    };
+    let in_start = skip_blanks(chars, name_start + name.len());
+    if word_at(chars, in_start).as_deref() != Some("in") { return Err(unexpected(chars, in_start)) };
+    *pos = in_start + 2;
+    let (range, block) = condition_block(chars, pos)?;
+    let dots = match (range.start..range.end.saturating_sub(1)).find(|&i| chars[i] == '.' && chars[i + 1] == '.') {
+        Some(dots) => dots,
+        None => return Err(("Expected a range", 'i', in_start))
+    };
+    Ok(Statement::For((name, name_start), trimmed(chars, range.start, dots), trimmed(chars, dots + 2, range.end), block))
+}
+
+/// A script being run
+struct Run<'a, V> {
+    chars: &'a [char],
+    settings: &'a Settings,
+    /// Variables in scope, the innermost last
+    variables: Vec<(String, V)>,
+    /// Values of the expressions run so far
+    values: Vec<V>,
+    steps: usize
+}
+
+impl<V: Operand> Run<'_, V> {
+    fn eval(&self, expr: &Range<usize>) -> Result<V, (&'static str, char, usize)> {
+        eval(&self.chars[..expr.end], expr.start, &self.variables, self.settings)
+    }
+
+    /// Whether the condition `expr` holds
+    fn holds(&self, expr: &Range<usize>) -> Result<bool, (&'static str, char, usize)> {
+        self.eval(expr)?.is_true().map_err(|e| (e, self.chars[expr.start], expr.start))
+    }
+
+    /// Counts a step of the statement starting at `start`, failing past the step limit
+    fn step(&mut self, start: usize) -> Result<(), (&'static str, char, usize)> {
+        self.steps += 1;
+        match self.steps > self.settings.max_steps.unwrap_or(DEFAULT_MAX_STEPS) {
+            true => Err(("Step limit reached", self.chars[start], start)),
+            false => Ok(())
+        }
+    }
+
+    /// Fails when `name` is that of a constant such as `pi`, which a variable would hide
+    /// (`i` may be a variable, as a counter)
+    fn check_name(&self, name: &(String, usize)) -> Result<(), (&'static str, char, usize)> {
+        let (name, start) = name;
+        match V::constant(name, self.settings) {
+            Err("Unknown name") => Ok(()),
+            _ => Err(("Assignment to a built-in name", name.chars().next().unwrap(), *start))
+        }
+    }
+
+    /// Runs the statements of a block, giving whether one of them returned
+    /// The variables assigned in the block are dropped at its end
+    fn run_block(&mut self, block: &Block) -> Result<bool, (&'static str, char, usize)> {
+        let depth = self.variables.len();
+        let mut returned = Ok(false);
+        for (start, statement) in block {
+            returned = self.run(*start, statement);
+            if !matches!(returned, Ok(false)) { break };
+        };
+        self.variables.truncate(depth);
+        returned
+    }
+
+    /// Runs a statement, giving whether it returned
+    fn run(&mut self, start: usize, statement: &Statement) -> Result<bool, (&'static str, char, usize)> {
+        self.step(start)?;
+        match statement {
+            Statement::Expression(expr) => {
+                let value = self.eval(expr)?;
+                self.values.push(value);
+            },
+            Statement::Assign(variable, op, expr) => {
+                self.check_name(variable)?;
+                let (name, name_start) = variable;
+                let mut value = self.eval(expr)?;
+                let variable = self.variables.iter().rposition(|(variable, _)| variable == name);
+                if let Some((op, c, index)) = op {
+                    let current = match variable {
+                        Some(i) => self.variables[i].1.clone(),
+                        None => return Err(("Unknown name", name.chars().next().unwrap(), *name_start))
+                    };
+                    value = V::apply(op, current, value, self.settings).map_err(|e| (e, *c, *index))?;
+                };
+                match variable {
+                    Some(i) => self.variables[i].1 = value,
+                    None => self.variables.push((name.clone(), value))
+                };
+            },
+            Statement::If(branches, otherwise) => {
+                for (condition, block) in branches {
+                    if self.holds(condition)? { return self.run_block(block) };
+                };
+                return self.run_block(otherwise)
+            },
+            Statement::While(condition, block) => while self.holds(condition)? {
+                if self.run_block(block)? { return Ok(true) };
+                self.step(start)?;
+            },
+            Statement::For(name, from, to, block) => {
+                self.check_name(name)?;
+                let (mut counter, end) = (self.eval(from)?, self.eval(to)?);
+                let one = V::from_number(Number::whole(1, ('f', start))).map_err(|e| (e, 'f', start))?;
+                while V::apply(&Op::Lt, counter.clone(), end.clone(), self.settings)
+                    .and_then(|below| below.is_true()).map_err(|e| (e, 'f', start))?
+                {
+                    self.variables.push((name.0.clone(), counter.clone()));
+                    let returned = self.run_block(block);
+                    self.variables.pop();
+                    if returned? { return Ok(true) };
+                    counter = V::apply(&Op::Add, counter, one.clone(), self.settings).map_err(|e| (e, 'f', start))?;
+                    self.step(start)?;
+                };
+            },
+            Statement::Return(expr) => {
+                let value = self.eval(expr)?;
+                self.values.push(value);
+                return Ok(true)
+            },
+            Statement::Block(block) => return self.run_block(block)
+        };
+        Ok(false)
+    }
+}
@@ -0,0 +1,158 @@
+use crate::ops::{Op, Operand, Settings};
+use crate::parser::Number;
+use std::cmp::Ordering;
+
+fn whole<V: Operand>(n: usize) -> Result<V, &'static str> {
+    let n = u32::try_from(n).map_err(|_| "Result out of range")?;
+    V::from_number(Number::whole(n, (' ', 0)))
+}
+
+fn divisor(n: usize) -> Result<u32, &'static str> {
+    u32::try_from(n).map_err(|_| "Result out of range")
+}
+
+fn compare<V: Operand>(a: &V, b: &V, settings: &Settings) -> Result<Ordering, &'static str> {
+    match (V::apply(&Op::Lt, a.clone(), b.clone(), settings)?.is_true()?, V::apply(&Op::Gt, a.clone(), b.clone(), settings)?.is_true()?) {
+        (true, _) => Ok(Ordering::Less),
+        (_, true) => Ok(Ordering::Greater),
+        _ => Ok(Ordering::Equal)
+    }
+}
+
+/// The values in increasing order
+fn sorted<V: Operand>(mut xs: Vec<V>, settings: &Settings) -> Result<Vec<V>, &'static str> {
+    if xs.is_empty() { return Err("Empty list") };
+    let mut error = None;
+    xs.sort_by(|a, b| compare(a, b, settings).unwrap_or_else(|e| {
+        error = Some(e);
+        Ordering::Equal
+    }));
+    match error {
+        Some(e) => Err(e),
+        None => Ok(xs)
+    }
+}
+
+pub fn mean<V: Operand>(xs: Vec<V>, settings: &Settings) -> Result<V, &'static str> {
+    let n = divisor(xs.len())?;
+    V::sum_over(xs, n, settings)
+}
+
+/// The middle value, or the mean of the two middle values
+pub fn median<V: Operand>(xs: Vec<V>, settings: &Settings) -> Result<V, &'static str> {
+    let mut xs = sorted(xs, settings)?;
+    let n = xs.len();
+    match n % 2 {
+        1 => Ok(xs.swap_remove(n / 2)),
+        _ => V::sum_over(xs.drain(n / 2 - 1..=n / 2).collect(), 2, settings)
+    }
+}
+
+/// The most frequent value, and the smallest of them on a tie
+pub fn mode<V: Operand>(xs: Vec<V>, settings: &Settings) -> Result<V, &'static str> {
+    let xs = sorted(xs, settings)?;
+    let (mut best, mut best_count) = (0, 0);
+    let mut start = 0;
+    for i in 1..=xs.len() {
+        if i == xs.len() || compare(&xs[start], &xs[i], settings)? != Ordering::Equal {
+            if i - start > best_count {
+                (best, best_count) = (start, i - start);
+            };
+            start = i;
+        };
+    };
+    Ok(xs[best].clone())
+}
+
+/// The differences of the values from their mean, with whether they are negative
+/// In unsigned formats, which can't hold them, negative differences are given by their size
+fn deviations<V: Operand>(xs: &[V], settings: &Settings) -> Result<Vec<(V, bool)>, &'static str> {
+    let mean = mean(xs.to_vec(), settings)?;
+    xs.iter().map(|x| match V::checked(&Op::Sub, x.clone(), mean.clone(), settings) {
+        Err("Negative result") => Ok((V::checked(&Op::Sub, mean.clone(), x.clone(), settings)?, true)),
+        deviation => Ok((deviation?, false))
+    }).collect()
+}
+
+/// The mean of the products of the deviations of `xs` and `ys`, which are divided by `n - 1`
+/// for a sample, and by `n` for a population
+fn comoment<V: Operand>(xs: &[V], ys: &[V], sample: bool, settings: &Settings) -> Result<V, &'static str> {
+    if xs.len() != ys.len() { return Err("Mismatched lengths") };
+    let n = match (xs.len(), sample) {
+        (0, _) => return Err("Empty list"),
+        (1, true) => return Err("Not enough values"),
+        (n, true) => n - 1,
+        (n, false) => n
+    };
+    let (mut positive, mut negative) = (Vec::new(), Vec::new());
+    for ((x, x_negative), (y, y_negative)) in deviations(xs, settings)?.into_iter().zip(deviations(ys, settings)?) {
+        let product = V::checked(&Op::Mul, x, y, settings)?;
+        match x_negative != y_negative {
+            true => negative.push(product),
+            false => positive.push(product)
+        };
+    };
+    let positive = match positive.is_empty() {
+        true => whole(0)?,
+        false => V::sum_over(positive, divisor(n)?, settings)?
+    };
+    match negative.is_empty() {
+        true => Ok(positive),
+        false => V::checked(&Op::Sub, positive, V::sum_over(negative, divisor(n)?, settings)?, settings)
+    }
+}
+
+pub fn variance<V: Operand>(xs: Vec<V>, sample: bool, settings: &Settings) -> Result<V, &'static str> {
+    comoment(&xs, &xs, sample, settings)
+}
+
+pub fn stddev<V: Operand>(xs: Vec<V>, sample: bool, settings: &Settings) -> Result<V, &'static str> {
+    V::call("sqrt", vec![variance(xs, sample, settings)?], settings)
+}
+
+pub fn covariance<V: Operand>(xs: Vec<V>, ys: Vec<V>, settings: &Settings) -> Result<V, &'static str> {
+    comoment(&xs, &ys, true, settings)
+}
+
+/// Pearson's correlation coefficient
+pub fn correlation<V: Operand>(xs: Vec<V>, ys: Vec<V>, settings: &Settings) -> Result<V, &'static str> {
+    let covariance = comoment(&xs, &ys, true, settings)?;
+    let spread = V::checked(&Op::Mul, stddev(xs, true, settings)?, stddev(ys, true, settings)?, settings)?;
+    if !spread.is_true()? { return Err("Constant values") };
+    V::apply(&Op::Div, covariance, spread, settings)
+}
+
+/// The slope and intercept of the least squares line through the points of `xs` and `ys`
+pub fn linreg<V: Operand>(xs: Vec<V>, ys: Vec<V>, settings: &Settings) -> Result<(V, V), &'static str> {
+    let covariance = comoment(&xs, &ys, true, settings)?;
+    let variance = comoment(&xs, &xs, true, settings)?;
+    if !variance.is_true()? { return Err("Constant values") };
+    let slope = V::apply(&Op::Div, covariance, variance, settings)?;
+    let offset = V::checked(&Op::Mul, slope.clone(), mean(xs, settings)?, settings)?;
+    let intercept = V::checked(&Op::Sub, mean(ys, settings)?, offset, settings)?;
+    Ok((slope, intercept))
+}
+
+/// The value below which `p` percent of the values fall, interpolating linearly between
+/// the two values around the rank `p / 100 * (n - 1)`
+/// The interpolation weighs the two values, which keeps it between them when their difference
+/// is out of range
+pub fn percentile<V: Operand>(xs: Vec<V>, p: V, settings: &Settings) -> Result<V, &'static str> {
+    let xs = sorted(xs, settings)?;
+    let hundred = whole::<V>(100)?;
+    if compare(&p, &whole(0)?, settings)? == Ordering::Less || compare(&p, &hundred, settings)? == Ordering::Greater {
+        return Err("Percentile out of range")
+    };
+    let rank = V::apply(&Op::Div, V::checked(&Op::Mul, p, whole(xs.len() - 1)?, settings)?, hundred, settings)?;
+    let mut k = 0;
+    while k + 1 < xs.len() && compare(&whole(k + 1)?, &rank, settings)? != Ordering::Greater {
+        k += 1;
+    };
+    if k + 1 == xs.len() { return Ok(xs[k].clone()) };
+    let fraction = V::apply(&Op::Sub, rank, whole(k)?, settings)?;
+    if !fraction.is_true()? { return Ok(xs[k].clone()) };
+    let rest = V::apply(&Op::Sub, whole(1)?, fraction.clone(), settings)?;
+    let low = V::checked(&Op::Mul, xs[k].clone(), rest, settings)?;
+    let high = V::checked(&Op::Mul, xs[k + 1].clone(), fraction, settings)?;
+    V::checked(&Op::Add, low, high, settings)
+}
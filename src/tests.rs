@@ -23,28 +23,41 @@ macro_rules! generate_func {
             assert_eq!(lhs $op rhs, expected)
         }
     };
-    (fn(exact, int) $fnname:tt for $op:tt) => {
+    (fn(shift, $reverse:tt) $fnname:tt for $op:tt) => {
         pub fn $fnname(a: f64, b: f64) {
+            use crate::ops::{Op, Operand, Settings};
             let a = Fixed::from_f64(a).as_i64();
             let b = Fixed::from_f64(b).as_i64();
             let lhs = Fixed::from_i64(a);
             let rhs = Fixed::from_i64(b);
-            let expected = Fixed::from_i64(a $op (b >> 32));
-            assert_eq!(lhs $op rhs, expected)
+            let amount = super::round_div(b as i128, 1 << 32);
+            let expected = match (amount.unsigned_abs().min(63) as u32, amount < 0) {
+                (_, false) if amount >= 64 => a $op 63 $op 1,
+                (bits, false) => a $op bits,
+                (_, true) if amount <= -64 => a $reverse 63 $reverse 1,
+                (bits, true) => a $reverse bits
+            };
+            let settings = Settings {
+                reverse_negative_shifts: true, saturate_shifts: true, round_shift_amounts: true, ..Settings::default()
+            };
+            let op = match stringify!($op) { "<<" => Op::Shl, _ => Op::Shr };
+            assert_eq!(Fixed::apply(&op, lhs, rhs, &settings), Ok(Fixed::from_i64(expected)));
+            let default = match (b & 0xFFFF_FFFF != 0, b < 0, amount >= 64) {
+                (true, _, _) => Err("Fractional shift amount"),
+                (_, true, _) => Err("Negative shift amount"),
+                (_, _, true) => Err("Shift amount out of range"),
+                _ => Ok(Fixed::from_i64(expected))
+            };
+            assert_eq!(lhs $op rhs, default)
         }
     };
-    (fn(approx, $epsilon:tt) $fnname:tt for $op:tt) => {
+    (fn(rounded, $ratio:expr) $fnname:tt for $op:tt) => {
         pub fn $fnname(a: f64, b: f64) {
             let lhs = Fixed::from_f64(a);
             let rhs = Fixed::from_f64(b);
-            let result = (lhs $op rhs).as_i64();
-            let expected = Fixed::from_f64(a $op b).as_i64();
-            assert!(
-                expected.abs_diff(result) < $epsilon,
-                "{} {} {} => expected={}, result={}",
-                a, stringify!($op), b,
-                Fixed::from_i64(expected), Fixed::from_i64(result)
-            )
+            let (num, den) = $ratio(lhs.as_i64() as i128, rhs.as_i64() as i128);
+            let expected = super::round_div(num, den) as i64;
+            assert_eq!((lhs $op rhs).as_i64(), expected, "{} {} {}", a, stringify!($op), b)
         }
     }
 }
@@ -63,14 +76,14 @@ macro_rules! test_func {
 make_test_functions!(
     fn add(exact) for +,
     fn sub(exact) for -,
-    fn mul(approx, (1 << 32)) for *,
-    fn div(approx, (1 << 16)) for /,
+    fn mul(rounded, |a, b| (a * b, 1 << 32)) for *,
+    fn div(rounded, |a, b| (a << 32, b)) for /,
     fn rem(exact) for %,
     fn and(exact) for &,
     fn ior(exact) for |,
     fn xor(exact) for ^,
-    fn shl(exact, int) for <<,
-    fn shr(exact, int) for >>,
+    fn shl(shift, >>) for <<,
+    fn shr(shift, <<) for >>,
     with [
         ( 0.0, 1.0),
         (-1.0, 0.5),
@@ -96,3 +109,1136 @@ make_test_functions!(
         ( 89759.877,  66300.215)
     ]
 );
+
+/// `num / den` rounded to the nearest integer (ties to even)
+fn round_div(num: i128, den: i128) -> i128 {
+    let (quotient, remainder) = (num / den, num % den);
+    let away = match (2 * remainder.abs()).cmp(&den.abs()) {
+        std::cmp::Ordering::Less => false,
+        std::cmp::Ordering::Equal => quotient % 2 != 0,
+        std::cmp::Ordering::Greater => true
+    };
+    match (away, (num < 0) != (den < 0)) {
+        (false, _) => quotient,
+        (true, false) => quotient + 1,
+        (true, true) => quotient - 1
+    }
+}
+
+/// Exact value of a literal as the raw content of a `Fixed`, computed from
+/// the rational number decimal_digits / base^decimal_count, and rounded to
+/// the nearest value (ties to even)
+fn literal_reference(literal: &str) -> i64 {
+    let (neg, literal) = match literal.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, literal)
+    };
+    let (base, literal) = match literal.get(..2) {
+        Some("0b") => (2, &literal[2..]),
+        Some("0o") => (8, &literal[2..]),
+        Some("0x") => (16, &literal[2..]),
+        _ => (10, literal)
+    };
+    let (whole, decimal) = literal.split_once('.').unwrap_or((literal, ""));
+    let digits = |s: &str| -> Vec<u128> {
+        s.chars().filter(|&c| c != '_').map(|c| c.to_digit(base).unwrap() as u128).collect()
+    };
+    let base = base as u128;
+
+    let whole = digits(whole).iter().fold(0, |acc, d| acc * base + d);
+    let (num, den) = digits(decimal).iter().fold((0, 1), |(num, den), d| {
+        (num * base + d, den * base)
+    });
+    let quotient = (num << 32) / den;
+    let remainder = (num << 32) % den;
+    let rounded = match (2 * remainder).cmp(&den) {
+        std::cmp::Ordering::Less => quotient,
+        std::cmp::Ordering::Equal => quotient + (quotient & 1),
+        std::cmp::Ordering::Greater => quotient + 1
+    };
+    // Built in i128, as the magnitude of the minimum is out of range of i64
+    let raw = ((whole << 32) + rounded) as i128;
+    match neg {
+        false => raw as i64,
+        true => -raw as i64
+    }
+}
+
+macro_rules! test_literals {
+    ( $fnname:tt, [ $( $lit:expr ),+ ] ) => {
+        #[test]
+        fn $fnname() {
+            use crate::fixed::Fixed;
+            $(
+                let expected = Fixed::from_i64(literal_reference($lit));
+                assert_eq!(crate::parser::exec($lit), Ok(expected), "literal {}", $lit);
+            )+
+        }
+    };
+}
+
+#[test]
+fn literal_reference_sanity() {
+    assert_eq!(literal_reference("0.1"), 429_496_730);
+    assert_eq!(literal_reference("0.99999999999"), 1 << 32);
+    assert_eq!(literal_reference("-2.5"), -(5 << 31));
+    assert_eq!(literal_reference("0x0.000000008"), 0);
+    assert_eq!(literal_reference("0x0.000000018"), 2);
+    assert_eq!(literal_reference("-2147483648"), i64::MIN);
+}
+
+test_literals!(literals_short, [
+    "0", "1", "-1", "0.5", "0.1", "-0.1", "0.2", "0.3", "0.7", "1.25",
+    "3.14159", "42.000", "1_000.000_1", "2147483647.5", "-2147483648",
+    "0x1f.8", "0b101.011", "0o7.4", ".5", "-.75"
+]);
+
+test_literals!(literals_long, [
+    "0.99999999999", "0.999999999999999999", "0.0000000001", "0.00000000012",
+    "0.00000000011641532182", "3.1415926535897932384626433",
+    "2.7182818284590452353602874", "-1.4142135623730950488016887",
+    "65535.9999999999999999999999", "0.1234567890123456789012345678"
+]);
+
+test_literals!(literals_ties, [
+    "0x0.000000008", "0x0.000000018", "0x0.000000028", "-0x0.000000038",
+    "0x0.0000000080000001", "0b0.000000000000000000000000000000011",
+    "0o0.00000000000004", "0x0.ffffffff8", "0x0.fffffffe8"
+]);
+
+macro_rules! test_same_value {
+    ( $fnname:tt, [ $( ($lhs:expr, $rhs:expr) ),+ ] ) => {
+        #[test]
+        fn $fnname() {
+            $(
+                assert_eq!(crate::parser::exec($lhs), crate::parser::exec($rhs), "{} == {}", $lhs, $rhs);
+            )+
+        }
+    };
+}
+
+test_same_value!(literals_exponent, [
+    ("1.5e-3", "0.0015"),
+    ("6.02E3", "6020"),
+    ("-2.5e+2", "-250"),
+    ("1_0e1_0", "100_000_000_000e-1"),
+    ("314159265358979e-14", "3.14159265358979"),
+    ("0.000001e6", "1"),
+    ("1e-11", "0"),
+    ("123e-999999999999", "0"),
+    ("0e999999999999", "0"),
+    ("0x1.8p4", "24"),
+    ("0x1P-2", "0.25"),
+    ("0xA.Bp+1", "21.375"),
+    ("-0b1p31", "-0x8000_0000"),
+    ("0o1.4p1", "3"),
+    ("0x1p-33", "0"),
+    ("0x3p-34", "0x1p-32")
+]);
+
+#[test]
+fn literals_out_of_range() {
+    use crate::parser::exec;
+    assert_eq!(exec("1e10"), Err(("Literal out of range", '1', 0)));
+    assert_eq!(exec("2 + 2147483648"), Err(("Literal out of range", '2', 4)));
+    assert_eq!(exec("-0x1p31"), exec("-2147483648"));
+    assert_eq!(exec("0x1p31"), Err(("Literal out of range", '0', 0)));
+    assert_eq!(exec("1 * -0.5e999"), Err(("Literal out of range", '-', 4)));
+    assert_eq!(exec("2147483647.9999999999"), Err(("Literal out of range", '2', 0)));
+    assert_eq!(exec("1e"), Err(("Unexpected end of expression", '\'', 2)));
+    assert_eq!(exec("1e+)"), Err(("Unexpected", ')', 3)));
+}
+
+#[test]
+fn display_formats() {
+    use crate::parser::exec;
+    let fmt = |expr: &str| format!("{}", exec(expr).unwrap());
+    let fmt_prec = |expr: &str| format!("{:.3}", exec(expr).unwrap());
+    let fmt_sci = |expr: &str| format!("{:e}", exec(expr).unwrap());
+    let fmt_sci_prec = |expr: &str| format!("{:.2E}", exec(expr).unwrap());
+
+    assert_eq!(fmt("0.5"), "0.5");
+    assert_eq!(fmt("-12.25"), "-12.25");
+    assert_eq!(fmt("1/3"), "0.333333333");
+    assert_eq!(fmt("0.9999999999"), "1");
+    assert_eq!(fmt_prec("2/3"), "0.667");
+    assert_eq!(fmt_prec("0.9999"), "1.000");
+    assert_eq!(fmt_prec("-7"), "-7.000");
+
+    assert_eq!(fmt_sci("0.0015"), "1.5e-3");
+    assert_eq!(fmt_sci("6020"), "6.02e3");
+    assert_eq!(fmt_sci("-1"), "-1e0");
+    assert_eq!(fmt_sci("0"), "0e0");
+    assert_eq!(fmt_sci("1/3"), "3.33333333e-1");
+    assert_eq!(fmt_sci("9.9999999999"), "1e1");
+    assert_eq!(fmt_sci_prec("123456"), "1.23E5");
+    assert_eq!(fmt_sci_prec("-0.0009996"), "-1.00E-3");
+    assert_eq!(fmt_sci_prec("0"), "0.00E0");
+}
+
+#[test]
+fn wide_repr_matches_i64() {
+    use crate::fixed::Fixed;
+    type Wide = Fixed<32, i128>;
+    let values = [0.0, 1.0, -1.0, 0.5, -0.25, 96.436, -16.128, 70223.582, -20363.576, -0.000_001];
+    for a in values {
+        for b in values {
+            let (x, y) = (Fixed::from_f64(a).as_i64(), Fixed::from_f64(b).as_i64());
+            let (lhs, rhs) = (Fixed::from_i64(x), Fixed::from_i64(y));
+            let (wide_lhs, wide_rhs) = (Wide::from_raw(x.into()), Wide::from_raw(y.into()));
+            assert_eq!((lhs * rhs).as_i64(), (wide_lhs * wide_rhs).as_raw() as i64, "{a} * {b}");
+            if b != 0.0 {
+                assert_eq!((lhs / rhs).as_i64(), (wide_lhs / wide_rhs).as_raw() as i64, "{a} / {b}");
+            };
+        };
+    };
+}
+
+#[test]
+fn formats() {
+    use crate::fixed::Fixed;
+    use crate::parser::exec_as;
+    assert_eq!(exec_as::<Fixed<16, i32>>("1.5 * -2.25"), Ok(Fixed::from_raw(-221_184)));
+    assert_eq!(exec_as::<Fixed<16, i32>>("32767 + 0.5"), Ok(Fixed::from_raw(0x7FFF_8000)));
+    assert_eq!(exec_as::<Fixed<16, i32>>("32768"), Err(("Literal out of range", '3', 0)));
+    assert_eq!(exec_as::<Fixed<31, i32>>("-1"), Ok(Fixed::<31, i32>::MIN));
+    assert_eq!(exec_as::<Fixed<31, i32>>("0.5 * 0.5"), Ok(Fixed::from_raw(1 << 29)));
+    assert_eq!(exec_as::<Fixed<31, i32>>("1"), Err(("Literal out of range", '1', 0)));
+    assert_eq!(exec_as::<Fixed<48, i64>>("0.1"), Ok(Fixed::from_raw(28_147_497_671_066)));
+    assert_eq!(exec_as::<Fixed<64, i128>>("-3 / 4"), Ok(Fixed::from_raw(-3 << 62)));
+    assert_eq!(exec_as::<Fixed<64, i128>>("0x1p-64 * -0.5"), Ok(Fixed::from_raw(0)));
+    assert_eq!(exec_as::<Fixed<64, i128>>("0x3p-64 * -0.5"), Ok(Fixed::from_raw(-2)));
+
+    assert_eq!(format!("{}", exec_as::<Fixed<16, i32>>("1/3").unwrap()), "0.3333");
+    assert_eq!(format!("{}", exec_as::<Fixed<31, i32>>("-0.75").unwrap()), "-0.75");
+    assert_eq!(format!("{}", exec_as::<Fixed<48, i64>>("2/3").unwrap()), "0.66666666666667");
+    assert_eq!(format!("{}", exec_as::<Fixed<64, i128>>("1/3").unwrap()), "0.3333333333333333333");
+    assert_eq!(format!("{:e}", exec_as::<Fixed<64, i128>>("-12345678901234567 * 100").unwrap()), "-1.2345678901234567e18");
+}
+
+#[test]
+fn rounding_modes() {
+    use crate::fixed::{Fixed, RoundingMode};
+    use crate::ops::Settings;
+    use crate::parser::exec_with;
+    let modes = [
+        RoundingMode::NearestEven, RoundingMode::NearestAway, RoundingMode::TowardZero,
+        RoundingMode::Floor, RoundingMode::Ceil
+    ];
+    let all_modes = |a: i64, b: i64, f: fn(Fixed, Fixed, RoundingMode) -> Fixed| {
+        modes.map(|mode| f(Fixed::from_i64(a), Fixed::from_i64(b), mode).as_i64())
+    };
+    // 2.5 and -2.5 ulps
+    assert_eq!(all_modes(5, 1 << 31, Fixed::mul_round), [2, 3, 2, 2, 3]);
+    assert_eq!(all_modes(-5, 1 << 31, Fixed::mul_round), [-2, -3, -2, -3, -2]);
+    // 0.75 and -0.75 ulps
+    assert_eq!(all_modes(3, 1 << 30, Fixed::mul_round), [1, 1, 0, 0, 1]);
+    assert_eq!(all_modes(-3, 1 << 30, Fixed::mul_round), [-1, -1, 0, -1, 0]);
+    // 1/3 and -2/3 ulps
+    assert_eq!(all_modes(1, 3 << 32, Fixed::div_round), [0, 0, 0, 0, 1]);
+    assert_eq!(all_modes(-2, 3 << 32, Fixed::div_round), [-1, -1, 0, -1, 0]);
+    // 3.5 ulps, in 128 bits
+    let (a, b) = (Fixed::<64, i128>::from_raw(7), Fixed::<64, i128>::from_raw(2 << 64));
+    assert_eq!(a.div_round(b, RoundingMode::NearestEven).as_raw(), 4);
+    assert_eq!(a.div_round(b, RoundingMode::TowardZero).as_raw(), 3);
+    assert_eq!(Fixed::<64, i128>::from_raw(-7).mul_round(Fixed::from_raw(1 << 63), RoundingMode::Floor).as_raw(), -4);
+
+    let fmt = |mode, expr| {
+        let settings = Settings { rounding: mode, ..Settings::default() };
+        exec_with::<Fixed<16, i32>>(expr, &settings).map(|n| n.as_raw())
+    };
+    assert_eq!(fmt(RoundingMode::NearestEven, "2 / 3"), Ok(43_691));
+    assert_eq!(fmt(RoundingMode::TowardZero, "2 / 3"), Ok(43_690));
+    assert_eq!(fmt(RoundingMode::Floor, "-2 / 3"), Ok(-43_691));
+    assert_eq!(fmt(RoundingMode::Ceil, "0x1p-16 * 0.5"), Ok(1));
+}
+
+#[test]
+fn euclidean_division() {
+    use crate::bigint::BigInt;
+    use crate::fixed::Fixed;
+    use crate::int::Int;
+    use crate::parser::{exec, exec_as};
+    use crate::rational::Rational;
+    let fixed = |x: f64| Fixed::from_f64(x);
+    assert_eq!(fixed(-7.0) % fixed(3.0), fixed(-1.0));
+    assert_eq!(fixed(-7.0).rem_euclid(fixed(3.0)), fixed(2.0));
+    assert_eq!(fixed(-7.0).rem_euclid(fixed(-3.0)), fixed(2.0));
+    assert_eq!(fixed(-7.0).div_euclid(fixed(3.0)), fixed(-3.0));
+    assert_eq!(fixed(-7.0).div_euclid(fixed(-3.0)), fixed(3.0));
+    assert_eq!(fixed(7.0).div_floor(fixed(-2.0)), fixed(-4.0));
+    assert_eq!(fixed(-7.5).rem_euclid(fixed(2.0)), fixed(0.5));
+    assert_eq!(fixed(5.5).div_floor(fixed(1.5)), fixed(3.0));
+
+    assert_eq!(exec("-7 %% 3"), Ok(fixed(2.0)));
+    assert_eq!(exec("7 %% -3"), Ok(fixed(1.0)));
+    assert_eq!(exec("-7 // 2"), Ok(fixed(-4.0)));
+    assert_eq!(exec("1 + 7 // 2 * 2"), Ok(fixed(7.0)));
+    assert_eq!(exec("1 + 7 / 2 * 2"), Ok(fixed(8.0)));
+    assert_eq!(exec("1 %% 0"), Err(("Division by zero", '%', 2)));
+    assert_eq!(exec("1 // (1 - 1)"), Err(("Division by zero", '/', 2)));
+
+    let fmt = |expr: &str| exec_as::<Rational>(expr).map(|n| n.to_string());
+    assert_eq!(fmt("-15/2 %% 2"), Ok(String::from("1/2")));
+    assert_eq!(fmt("7/3 %% (-1/2)"), Ok(String::from("1/3")));
+    assert_eq!(fmt("-7/3 // (1/2)"), Ok(String::from("-5")));
+    let fmt = |expr: &str| exec_as::<BigInt>(expr).map(|n| n.to_string());
+    assert_eq!(fmt("-7 %% -3"), Ok(String::from("2")));
+    assert_eq!(fmt("7 // -2"), Ok(String::from("-4")));
+    let fmt = |expr: &str| exec_as::<Int>(expr).map(|n| n.to_string());
+    assert_eq!(fmt("(i8)-128 // -1"), Ok(String::from("-128")));
+    assert_eq!(fmt("(u8)250 %% 7"), Ok(String::from("5")));
+}
+
+#[test]
+fn shift_rules() {
+    use crate::bigint::BigInt;
+    use crate::fixed::{Fixed, UFixed};
+    use crate::int::Int;
+    use crate::ops::Settings;
+    use crate::parser::{exec, exec_as, exec_with};
+    let fixed = |x: f64| Fixed::from_f64(x);
+    assert_eq!(exec("1 << 2.9"), Err(("Fractional shift amount", '<', 2)));
+    assert_eq!(exec("1 << -1"), Err(("Negative shift amount", '<', 2)));
+    assert_eq!(exec("1 >> 64"), Err(("Shift amount out of range", '>', 2)));
+    assert_eq!(exec("1 << 63"), Ok(fixed(0.0)));
+
+    let lenient = Settings {
+        reverse_negative_shifts: true, saturate_shifts: true, round_shift_amounts: true,
+        ..Settings::default()
+    };
+    let run = |expr| exec_with::<Fixed>(expr, &lenient);
+    assert_eq!(run("1 << 2.9"), Ok(fixed(8.0)));
+    assert_eq!(run("1 << 2.5"), Ok(fixed(4.0)));
+    assert_eq!(run("16 << -2"), Ok(fixed(4.0)));
+    assert_eq!(run("16 >> -2"), Ok(fixed(64.0)));
+    assert_eq!(run("-16 >> 1000"), Ok(Fixed::from_i64(-1)));
+    assert_eq!(run("16 >> 1000"), Ok(fixed(0.0)));
+    assert_eq!(run("16 << 1e9"), Ok(fixed(0.0)));
+    assert_eq!(exec_with::<UFixed>("0x8000_0000 >> 1000", &lenient), Ok(UFixed::from_raw(0)));
+    let round_only = Settings { round_shift_amounts: true, ..Settings::default() };
+    assert_eq!(exec_with::<Fixed>("1 << -0.4", &round_only), Ok(fixed(1.0)));
+    assert_eq!(exec_with::<Fixed>("1 << -0.6", &round_only), Err(("Negative shift amount", '<', 2)));
+
+    assert_eq!(exec("0x4000_0000 <<< 2"), Ok(Fixed::from_i64(1)));
+    assert_eq!(exec("1 >>> 1"), Ok(Fixed::from_i64(1 << 31)));
+    assert_eq!(exec("3 <<< 64 + 1"), Ok(fixed(6.0)));
+    assert_eq!(exec("1 <<< 0.5"), Err(("Fractional shift amount", '<', 2)));
+    assert_eq!(exec("1 <<< -1"), Err(("Negative shift amount", '<', 2)));
+    assert_eq!(exec_with::<Fixed>("1 <<< -1", &lenient), Ok(Fixed::from_i64(1 << 31)));
+    assert_eq!(exec("1 <<<< 1"), Err(("Unexpected", '<', 5)));
+
+    let fmt = |expr: &str| exec_as::<Int>(expr).map(|n| n.to_string());
+    assert_eq!(fmt("(u8)0x81 <<< 1"), Ok(String::from("3")));
+    assert_eq!(fmt("(u8)0x81 >>> 1"), Ok(String::from("192")));
+    assert_eq!(fmt("(i8)-128 >>> 7"), Ok(String::from("1")));
+    assert_eq!(fmt("(u16)1 >>> -1"), Ok(String::from("2")));
+    assert_eq!(fmt("(u32)1 <<< 33"), Ok(String::from("2")));
+    assert_eq!(fmt("1 <<< 1"), Err(("Rotation needs a fixed width", '<', 2)));
+    assert_eq!(exec_as::<BigInt>("1 >>> 1"), Err(("Rotation needs a fixed width", '>', 2)));
+}
+
+#[test]
+fn roots_and_angles() {
+    use crate::fixed::{Fixed, RoundingMode};
+    let nearest = RoundingMode::NearestEven;
+    let fixed = |x: f64| Fixed::from_f64(x);
+    assert_eq!(fixed(2.0).sqrt(nearest), Some(Fixed::from_i64(6_074_001_000)));
+    assert_eq!(fixed(2.0).sqrt(RoundingMode::Floor), Some(Fixed::from_i64(6_074_000_999)));
+    assert_eq!(fixed(0.0).sqrt(nearest), Some(fixed(0.0)));
+    assert_eq!(fixed(-1.0).sqrt(nearest), None);
+    assert_eq!(Fixed::<64, i128>::from_raw(2 << 64).sqrt(nearest), Some(Fixed::from_raw(26_087_635_650_665_564_425)));
+    assert_eq!(fixed(3.0).hypot(fixed(-4.0), nearest), Some(fixed(5.0)));
+    assert_eq!(Fixed::<32, i64>::MAX.hypot(Fixed::MAX, nearest), None);
+    assert_eq!(fixed(0.0).atan2(fixed(-1.0), nearest), Some(Fixed::from_i64(13_493_037_705)));
+    assert_eq!(fixed(-0.5).atan2(fixed(-0.5), nearest), Some(Fixed::from_i64(-10_119_778_278)));
+    assert_eq!(fixed(4.0).atan2(fixed(3.0), nearest), Some(Fixed::from_i64(3_982_702_635)));
+    assert_eq!(fixed(0.0).atan2(fixed(0.0), nearest), Some(fixed(0.0)));
+    let pi = Fixed::<64, i128>::from_raw(0).atan2(Fixed::from_raw(-1), nearest);
+    assert_eq!(pi, Some(Fixed::from_raw(57_952_155_664_616_982_739)));
+}
+
+#[test]
+fn complex_mode() {
+    use crate::complex::Complex;
+    use crate::fixed::{Fixed, RoundingMode};
+    use crate::parser::{exec, exec_as};
+    let fmt = |expr: &str| exec_as::<Complex>(expr).map(|z| z.to_string());
+    assert_eq!(fmt("(3 + 4i) * (1 - 2i)"), Ok(String::from("11-2i")));
+    assert_eq!(fmt("(1 + 2i) / (3 - 4i)"), Ok(String::from("-0.2+0.4i")));
+    assert_eq!(fmt("(1 + 2i) / (0.5i)"), Ok(String::from("4-2i")));
+    assert_eq!(fmt("abs(3+4i)"), Ok(String::from("5")));
+    assert_eq!(fmt("sqrt(-1)"), Ok(String::from("1i")));
+    assert_eq!(fmt("sqrt(-3+4i)"), Ok(String::from("1+2i")));
+    assert_eq!(fmt("sqrt(3-4i)"), Ok(String::from("2-1i")));
+    assert_eq!(fmt("i * i"), Ok(String::from("-1")));
+    assert_eq!(fmt("-i + 0x1p1i"), Ok(String::from("1i")));
+    assert_eq!(fmt("conj(1.5 + i) - re(2i) * im(2i)"), Ok(String::from("1.5-1i")));
+    assert_eq!(fmt("6 & 3 << 1"), Ok(String::from("4")));
+    assert_eq!(fmt("(1 + i) & 1"), Err(("Bitwise operation on a complex number", '&', 8)));
+    assert_eq!(fmt("1 << i"), Err(("Bitwise operation on a complex number", '<', 2)));
+    assert_eq!(fmt("3 %% i"), Err(("Integer division of a complex number", '%', 2)));
+    assert_eq!(fmt("1 / (i - i)"), Err(("Division by zero", '/', 2)));
+    assert_eq!(fmt("abs(1, 2)"), Err(("Wrong number of arguments", 'a', 0)));
+    assert_eq!(fmt("sin(1)"), Err(("Unknown function", 's', 0)));
+    assert_eq!(fmt("(2 + j)"), Err(("Unknown name", 'j', 5)));
+    assert_eq!(fmt("abs(3"), Err(("Unmatched", '(', 3)));
+    assert_eq!(fmt("1e9i * 1e9i"), Err(("Result out of range", '*', 5)));
+    assert_eq!(fmt("(2e4 + 3e4i) * (2e4 - 3e4i)"), Ok(String::from("1300000000")));
+    assert_eq!(fmt("(3e4 + 4e4i) * (4e4 - 3e4i)"), Err(("Result out of range", '*', 13)));
+    let unsigned = |expr: &str| exec_as::<Complex<Fixed<32, u64>>>(expr).map(|z| z.to_string());
+    assert_eq!(unsigned("conj(2 + 3i)"), Err(("Conjugate in an unsigned format", 'c', 0)));
+    assert_eq!(unsigned("conj(2)"), Ok(String::from("2")));
+
+    let z = exec_as::<Complex>("-3 - 4i").unwrap();
+    let (modulus, arg) = z.polar(RoundingMode::NearestEven).unwrap();
+    assert_eq!((modulus, arg), (Fixed::from_f64(5.0), Fixed::from_i64(-9_510_335_069)));
+    assert_eq!(format!("{:e}", exec_as::<Complex>("1500 - 0.25i").unwrap()), "1.5e3-2.5e-1i");
+
+    assert_eq!(exec("3i"), Err(("Imaginary numbers need the complex mode", '3', 0)));
+    assert_eq!(exec("sqrt(4) + abs(-2)"), Ok(Fixed::from_f64(4.0)));
+    assert_eq!(exec("sqrt(-4)"), Err(("Square root of a negative number", 's', 0)));
+}
+
+#[test]
+fn interval_mode() {
+    use crate::fixed::Fixed;
+    use crate::interval::Interval;
+    use crate::parser::exec_as;
+    let fmt = |expr: &str| exec_as::<Interval>(expr).map(|x| x.to_string());
+    assert_eq!(exec_as::<Interval>("0.5"), Ok(Interval::point(Fixed::from_f64(0.5))));
+    assert_eq!(exec_as::<Interval>("0.1"), Ok(Interval::new(Fixed::from_raw(429496729), Fixed::from_raw(429496730)).unwrap()));
+    assert_eq!(exec_as::<Interval>("-0.1"), Ok(Interval::new(Fixed::from_raw(-429496730), Fixed::from_raw(-429496729)).unwrap()));
+    assert_eq!(fmt("0.1 + 0.2"), Ok(String::from("[0.299999999, 0.300000001]")));
+    assert_eq!(fmt("1 / 3 * 3"), Ok(String::from("[0.999999999, 1.000000001]")));
+    assert_eq!(fmt("(0.1 - 0.2) * 10"), Ok(String::from("[-1.000000004, -0.999999998]")));
+    assert_eq!(fmt("sqrt(2)"), Ok(String::from("[1.414213562, 1.414213563]")));
+    assert_eq!(fmt("abs(0.5 - 1 / 3)"), Ok(String::from("[0.166666666, 0.166666667]")));
+    assert_eq!(fmt("2 * 3 - 7 % 4"), Ok(String::from("3")));
+    assert_eq!(fmt("hi(0.1) - lo(0.1)"), Ok(String::from("0")));
+    assert_eq!(fmt("(hi(0.1) - lo(0.1)) * 1e9"), Ok(String::from("0.232830644")));
+    assert_eq!(fmt("lo(1 / 3)"), Ok(String::from("0.333333333")));
+    assert_eq!(fmt("hi(2 / 3)"), Ok(String::from("0.666666667")));
+    assert_eq!(fmt("1 / (0.1 - 0.1)"), Err(("Division by an interval containing zero", '/', 2)));
+    assert_eq!(fmt("0.1 % 2"), Err(("Inexact operand", '%', 4)));
+    assert_eq!(fmt("0x7fffffff + 1"), Err(("Result out of range", '+', 11)));
+    assert_eq!(fmt("65536 * 65536"), Err(("Result out of range", '*', 6)));
+    assert_eq!(fmt("sqrt(-0.1)"), Err(("Square root of a negative number", 's', 0)));
+
+    assert_eq!(Interval::new(Fixed::from_f64(2.0), Fixed::from_f64(1.0)), Err("Empty interval"));
+
+    let x = exec_as::<Interval>("1 / 3").unwrap();
+    assert_eq!(x.midpoint_radius(9), (String::from("0.333333333"), String::from("0.000000001")));
+    let x = exec_as::<Interval>("2.5").unwrap();
+    assert_eq!(x.midpoint_radius(9), (String::from("2.5"), String::from("0")));
+}
+
+#[test]
+fn uncertainty_mode() {
+    use crate::fixed::Fixed;
+    use crate::interval::Interval;
+    use crate::parser::{exec, exec_as};
+    use crate::uncertain::Uncertain;
+    let fmt = |expr: &str| exec_as::<Uncertain>(expr).map(|x| x.to_string());
+    assert_eq!(exec_as::<Uncertain>("3 ± 0.5"), Ok(Uncertain::new(Fixed::from_f64(3.0), Fixed::from_f64(0.5))));
+    assert_eq!(exec_as::<Uncertain>("3+/-0.5"), exec_as::<Uncertain>("3 ± 0.5"));
+    assert_eq!(fmt("(10.0 ± 0.2) * (3.1 ± 0.05)"), Ok(String::from("31.00 ± 0.80")));
+    assert_eq!(fmt("(10 +/- 0.2) / (3.1 +/- 0.05)"), Ok(String::from("3.226 ± 0.083")));
+    assert_eq!(fmt("10 ± 0.3 - 3 ± 0.4"), Ok(String::from("7.00 ± 0.50")));
+    assert_eq!(fmt("2 * 5 ± 0.5"), Ok(String::from("10.0 ± 1.0")));
+    assert_eq!(fmt("(5 ± 0.3) ± 0.4"), Ok(String::from("5.00 ± 0.50")));
+    assert_eq!(fmt("sqrt(4 ± 0.4)"), Ok(String::from("2.00 ± 0.10")));
+    assert_eq!(fmt("abs(-5 +/- 1) + 1"), Ok(String::from("6.0 ± 1.0")));
+    assert_eq!(fmt("(7 ± 0.1) % 2"), Ok(String::from("1.00 ± 0.10")));
+    assert_eq!(fmt("(7 ± 0.1) // 2"), Ok(String::from("3")));
+    assert_eq!(fmt("1000 ± 15"), Ok(String::from("1000 ± 15")));
+    assert_eq!(fmt("sigma(100 ± 12.5) + value(100 ± 12.5)"), Ok(String::from("112.5")));
+    assert_eq!(fmt("6 & 3 << 1"), Ok(String::from("4")));
+    assert_eq!(fmt("(7 ± 0.1) & 3"), Err(("Bitwise operation on an uncertain value", '&', 10)));
+    assert_eq!(fmt("1 << 2 ± 1"), Err(("Bitwise operation on an uncertain value", '<', 2)));
+    assert_eq!(fmt("2 ± -1"), Err(("Negative uncertainty", '±', 2)));
+    assert_eq!(fmt("1 ± (2 ± 1)"), Err(("Uncertainty of an uncertainty", '±', 2)));
+    assert_eq!(fmt("sqrt(0 ± 1)"), Err(("Infinite uncertainty", 's', 0)));
+    assert_eq!(fmt("1 +/ 2"), Err(("Unexpected", ' ', 4)));
+    assert_eq!(fmt("1 +/-"), Err(("Unexpected end of expression", '\'', 5)));
+
+    assert_eq!(exec("1 ± 2"), Err(("Uncertainties need the uncertain mode", '±', 2)));
+    assert_eq!(exec_as::<Interval>("10 +/- 0.25"), Ok(Interval::new(Fixed::from_f64(9.75), Fixed::from_f64(10.25)).unwrap()));
+}
+
+#[test]
+fn units_mode() {
+    use crate::fixed::Fixed;
+    use crate::ops::Settings;
+    use crate::parser::{exec, exec_as, exec_with};
+    use crate::units::Quantity;
+    let fmt = |expr: &str| exec_as::<Quantity>(expr).map(|x| x.to_string());
+    assert_eq!(exec_as::<Quantity>("3 km + 200 m"), Ok(Quantity::new(Fixed::from_f64(3200.0), [0, 1, 0, 0, 0, 0, 0])));
+    assert_eq!(fmt("3 km + 200 m"), Ok(String::from("3200 m")));
+    assert_eq!(fmt("3 km-200 m"), Ok(String::from("2800 m")));
+    assert_eq!(fmt("5 kg * 9.81 m/s^2"), Ok(String::from("49.05 kg m/s^2")));
+    assert_eq!(fmt("72 km/h to m/s"), Ok(String::from("20 m/s")));
+    assert_eq!(fmt("60 mph to km/h"), Ok(String::from("96.56064 km/h")));
+    assert_eq!(fmt("1 in + 1 ft to cm"), Ok(String::from("33.02 cm")));
+    assert_eq!(fmt("1 kWh to MJ"), Ok(String::from("3.6 MJ")));
+    assert_eq!(fmt("(1 mi to ft) * 2"), Ok(String::from("3218.688 m")));
+    assert_eq!(fmt("(3 + 2) m^2 / 2 s"), Ok(String::from("2.5 m^2/s")));
+    assert_eq!(fmt("1 / 4 s"), Ok(String::from("0.25 s^-1")));
+    assert_eq!(fmt("sqrt(16 m^2) * 6 m/2"), Ok(String::from("12 m^2")));
+    assert_eq!(fmt("2 min * 3 Hz"), Ok(String::from("360")));
+    assert_eq!(fmt("1 m + 1 s"), Err(("Mismatched units", '+', 4)));
+    assert_eq!(fmt("1 s to m"), Err(("Mismatched units", 'm', 7)));
+    assert_eq!(fmt("1 foo"), Err(("Unknown unit", 'f', 2)));
+    assert_eq!(fmt("1 m^1000"), Err(("Invalid exponent", 'm', 2)));
+    assert_eq!(fmt("sqrt(2 m)"), Err(("Square root of an odd dimension", 's', 0)));
+    assert_eq!(fmt("1 m & 3"), Err(("Bitwise operation on a quantity with units", '&', 4)));
+    assert_eq!(fmt("1 m << 3"), Ok(String::from("8 m")));
+    assert_eq!(fmt("2 m to"), Err(("Unexpected end of expression", '\'', 6)));
+    // Values stay in the units they are written in, and are only converted where units meet
+    assert_eq!(fmt("1 km/h * 1 h"), Ok(String::from("1000 m")));
+    assert_eq!(fmt("100 km/h * 2.5 h to km"), Ok(String::from("250 km")));
+    assert_eq!(fmt("1 km + 1 mi"), Ok(String::from("2609.344 m")));
+    assert_eq!(fmt("1 day / 1 h"), Ok(String::from("24")));
+    assert_eq!(fmt("1 nm"), Ok(String::from("0.000000001 m")));
+
+    let settings = Settings {
+        units: [("furlong", "201.168 m"), ("fortnight", "14 day"), ("speed", "furlong/fortnight")]
+            .map(|(name, def)| (String::from(name), String::from(def))).to_vec(),
+        ..Settings::default()
+    };
+    let fmt_with = |expr: &str| exec_with::<Quantity>(expr, &settings).map(|x| x.to_string());
+    assert_eq!(fmt_with("1 speed to mm/h"), Ok(String::from("598.714286 mm/h")));
+    assert_eq!(fmt_with("2 furlong to m"), Ok(String::from("402.336 m")));
+    assert_eq!(fmt_with("1 kfurlong"), Err(("Unknown unit", 'k', 2)));
+    let signed = Settings {
+        units: vec![(String::from("neg"), String::from("-1 m")), (String::from("none"), String::from("0 m"))],
+        ..Settings::default()
+    };
+    assert_eq!(exec_with::<Quantity>("2 neg", &signed), Err(("Unit of negative size", 'n', 2)));
+    assert_eq!(exec_with::<Quantity>("2 none", &signed), Err(("Unit of zero size", 'n', 2)));
+
+    assert_eq!(exec("1 km"), Err(("Unexpected", 'k', 2)));
+    assert_eq!(exec("1 to m"), Err(("Conversions need the units mode", 'm', 5)));
+}
+
+#[test]
+fn percentages() {
+    use crate::fixed::Fixed;
+    use crate::ops::Settings;
+    use crate::parser::{exec, exec_as, exec_with};
+    use crate::rational::Rational;
+    assert_eq!(exec("200 + 15%"), Ok(Fixed::from_f64(230.0)));
+    assert_eq!(exec("200 - 15%"), Ok(Fixed::from_f64(170.0)));
+    assert_eq!(exec("200 * 15%"), Ok(Fixed::from_f64(30.0)));
+    assert_eq!(exec("50 / 25%"), Ok(Fixed::from_f64(200.0)));
+    assert_eq!(exec("15%"), exec("0.15"));
+    assert_eq!(exec("(200 + 15%) * 2"), Ok(Fixed::from_f64(460.0)));
+    assert_eq!(exec("2 * 100 + 10%"), Ok(Fixed::from_f64(220.0)));
+    assert_eq!(exec("200 + 15% - 5"), Ok(Fixed::from_f64(225.0)));
+    assert_eq!(exec("abs(-50%)"), Ok(Fixed::from_f64(0.5)));
+    assert_eq!(exec("7 % 3"), Ok(Fixed::from_f64(1.0)));
+    assert_eq!(exec("7 % -2"), Ok(Fixed::from_f64(1.0)));
+    assert_eq!(exec("7 %(2)"), Ok(Fixed::from_f64(1.0)));
+    assert_eq!(exec("7 %% 3"), Ok(Fixed::from_f64(1.0)));
+    assert_eq!(exec("5 %%"), Err(("Unexpected end of expression", '\'', 4)));
+    assert_eq!(exec_as::<Rational>("1/3 + 50%").map(|x| x.to_string()), Ok(String::from("1/2")));
+
+    let strict = Settings { strict_percent: true, ..Settings::default() };
+    assert_eq!(exec_with::<Fixed>("200 + 15%", &strict), Err(("Unexpected end of expression", '\'', 9)));
+    assert_eq!(exec_with::<Fixed>("7 % 3", &strict), Ok(Fixed::from_f64(1.0)));
+}
+
+#[test]
+fn combinatorics() {
+    use crate::bigint::BigInt;
+    use crate::fixed::Fixed;
+    use crate::number_theory::{binomial, is_prime, prime_factors};
+    use crate::parser::{exec, exec_as};
+    assert_eq!(exec("5!"), Ok(Fixed::from_f64(120.0)));
+    assert_eq!(exec("0!"), Ok(Fixed::from_f64(1.0)));
+    assert_eq!(exec("2 * 3! + 1"), Ok(Fixed::from_f64(13.0)));
+    assert_eq!(exec("(1 + 2)!!"), Ok(Fixed::from_f64(720.0)));
+    assert_eq!(exec("12!"), Ok(Fixed::from_f64(479001600.0)));
+    assert_eq!(exec("13!"), Err(("Result out of range", '!', 2)));
+    assert_eq!(exec("2.5!"), Err(("Fractional argument", '!', 3)));
+    assert_eq!(exec("-3!"), Err(("Negative argument", '!', 2)));
+    assert_eq!(exec("nCr(10, 3) + nPr(10, 3)"), Ok(Fixed::from_f64(840.0)));
+    assert_eq!(exec("nCr(3, 5)"), Ok(Fixed::from_f64(0.0)));
+    assert_eq!(exec("nCr(33, 16)"), Ok(Fixed::from_f64(1166803110.0)));
+    assert_eq!(exec("nCr(34, 17)"), Err(("Result out of range", 'n', 0)));
+    assert_eq!(exec("nCr(60, 30)"), Err(("Result out of range", 'n', 0)));
+    assert_eq!(exec("nCr(10)"), Err(("Wrong number of arguments", 'n', 0)));
+    assert_eq!(exec("gcd(12, 18, -30) + lcm(4, 6, 10)"), Ok(Fixed::from_f64(66.0)));
+    assert_eq!(exec("lcm(65536, 65537)"), Err(("Result out of range", 'l', 0)));
+    assert_eq!(exec("isprime(2147483647) + isprime(1) + isprime(91)"), Ok(Fixed::from_f64(1.0)));
+    assert_eq!(exec("factor(2147483646)"), Ok(Fixed::from_f64(2.0)));
+    assert_eq!(exec("factor(1)"), Err(("No prime factor", 'f', 0)));
+    assert_eq!(exec_as::<Fixed<64, i128>>("factor(2147483647 * 2147483629)"), exec_as("2147483629"));
+
+    assert_eq!(exec_as::<BigInt>("30!").map(|n| n.to_string()), Ok(String::from("265252859812191058636308480000000")));
+    assert_eq!(exec_as::<BigInt>("nCr(100, 50)").map(|n| n.to_string()), Ok(String::from("100891344545564193334812497256")));
+    assert_eq!(exec_as::<BigInt>("factor(600851475143)").map(|n| n.to_string()), Ok(String::from("71")));
+    assert_eq!(exec_as::<BigInt>("100000!"), Err(("Argument out of range", '!', 6)));
+
+    assert_eq!(binomial(200, 100), None);
+    assert_eq!(binomial(128, 64), Some(23951146041928082866135587776380551750));
+    assert_eq!(binomial(130, 65), Some(95067625827960698145584333020095113100));
+    assert!(is_prime(18446744073709551557) && !is_prime(3215031751));
+    assert_eq!(prime_factors(600851475143), vec![71, 839, 1471, 6857]);
+    assert_eq!(prime_factors(1 << 40), vec![2; 40]);
+    assert_eq!(prime_factors(4611686014132420609), vec![2147483647, 2147483647]);
+}
+
+#[test]
+fn implicit_multiplication() {
+    use crate::complex::Complex;
+    use crate::fixed::{Fixed, RoundingMode};
+    use crate::ops::{Juxtaposition, Settings};
+    use crate::parser::{exec, exec_with};
+    use crate::units::Quantity;
+    let product = Settings { implicit_multiplication: Some(Juxtaposition::Product), ..Settings::default() };
+    let tight = Settings { implicit_multiplication: Some(Juxtaposition::Tight), ..Settings::default() };
+    assert_eq!(exec("2(3+4)"), Err(("Unexpected", '(', 1)));
+    assert_eq!(exec("3pi"), Err(("Unexpected", 'p', 1)));
+    assert_eq!(exec("pi"), Ok(Fixed::pi(RoundingMode::NearestEven).unwrap()));
+    assert_eq!(exec_with::<Fixed>("2(3+4)", &product), Ok(Fixed::from_f64(14.0)));
+    assert_eq!(exec_with::<Fixed>("(1+2)(3+4)", &product), Ok(Fixed::from_f64(21.0)));
+    assert_eq!(exec_with::<Fixed>("3pi", &product), exec("3*pi"));
+    assert_eq!(exec_with::<Fixed>("2 sqrt(9)", &product), Ok(Fixed::from_f64(6.0)));
+    assert_eq!(exec_with::<Fixed>("2sqrt(9)", &tight), Ok(Fixed::from_f64(6.0)));
+    assert_eq!(exec_with::<Fixed>("6/2(1+2)", &product), Ok(Fixed::from_f64(9.0)));
+    assert_eq!(exec_with::<Fixed>("6/2(1+2)", &tight), Ok(Fixed::from_f64(1.0)));
+    assert_eq!(exec_with::<Fixed>("1/2pi", &tight), exec("1/(2*pi)"));
+    assert_eq!(exec_with::<Fixed>("2(3)!", &product), Ok(Fixed::from_f64(12.0)));
+    // Letters that can continue a literal are part of it
+    assert_eq!(exec_with::<Fixed>("0x1f(2)", &product), Ok(Fixed::from_f64(62.0)));
+    assert_eq!(exec_with::<Fixed>("0x1 pi", &product), exec("pi"));
+    assert_eq!(exec_with::<Fixed>("2e(3)", &product), Err(("Unexpected", '(', 2)));
+    assert_eq!(exec_with::<Fixed>("2 3", &product), Err(("Unexpected", '3', 2)));
+    assert_eq!(exec_with::<Fixed>("2 x", &product), Err(("Unknown name", 'x', 2)));
+    assert_eq!(exec_with::<Fixed>("(2)(3) as", &product), Err(("Unexpected end of expression", '\'', 9)));
+    assert_eq!(exec_with::<Complex>("(1+2)i", &product), exec_with("3i", &product));
+    // Units take precedence over constants
+    let quantity = exec_with::<Quantity>("2pi m", &tight).unwrap();
+    assert_eq!(quantity.to_string(), "6.283185307 m");
+}
+
+#[test]
+fn powers_and_comparisons() {
+    use crate::bigint::BigInt;
+    use crate::complex::Complex;
+    use crate::fixed::Fixed;
+    use crate::parser::{exec, exec_as};
+    use crate::rational::Rational;
+    use crate::units::Quantity;
+    assert_eq!(exec("2**3**2"), Ok(Fixed::from_f64(512.0)));
+    assert_eq!(exec("-2**2"), Ok(Fixed::from_f64(4.0)));
+    assert_eq!(exec("2**-2 * 3"), Ok(Fixed::from_f64(0.75)));
+    assert_eq!(exec("2**0.5"), Err(("Fractional exponent", '*', 1)));
+    assert_eq!(exec("0**-1"), Err(("Division by zero", '*', 1)));
+    assert_eq!(exec("2**40"), Err(("Result out of range", '*', 1)));
+    assert_eq!(exec("1 + 1 == 2"), Ok(Fixed::from_f64(1.0)));
+    assert_eq!(exec("1 << 2 < 3"), Ok(Fixed::from_f64(0.0)));
+    assert_eq!(exec("3! != 6"), Ok(Fixed::from_f64(0.0)));
+    assert_eq!(exec("2 >= 2 <= 0"), Ok(Fixed::from_f64(0.0)));
+    assert_eq!(exec_as::<BigInt>("2**100").map(|n| n.to_string()), Ok(String::from("1267650600228229401496703205376")));
+    assert_eq!(exec_as::<BigInt>("(-1)**(1 << 100)").map(|n| n.to_string()), Ok(String::from("1")));
+    assert_eq!(exec_as::<BigInt>("2**-1"), Err(("Negative exponent", '*', 1)));
+    assert_eq!(exec_as::<Rational>("(2/3)**-2").map(|r| r.to_string()), Ok(String::from("9/4")));
+    assert_eq!(exec_as::<Rational>("1/3 < 0.34").map(|r| r.to_string()), Ok(String::from("1")));
+    assert_eq!(exec_as::<Complex>("(1+i)**-1"), exec_as("0.5-0.5i"));
+    assert_eq!(exec_as::<Complex>("i < 1"), Err(("Ordering of complex numbers", '<', 2)));
+    assert_eq!(exec_as::<Quantity>("(3 m)**2").map(|q| q.to_string()), Ok(String::from("9 m^2")));
+    assert_eq!(exec_as::<Quantity>("2 ** (1 m)"), Err(("Exponent with units", '*', 2)));
+    assert_eq!(exec_as::<Quantity>("1 km < 1 mi").map(|q| q.to_string()), Ok(String::from("1")));
+    assert_eq!(exec_as::<Quantity>("1 km < 1 s"), Err(("Mismatched units", '<', 5)));
+}
+
+#[test]
+fn unicode_glyphs() {
+    use crate::fixed::Fixed;
+    use crate::glyphs::to_ascii;
+    use crate::parser::{exec, exec_as};
+    use crate::units::Quantity;
+    assert_eq!(exec("2×3²−√16"), Ok(Fixed::from_f64(14.0)));
+    assert_eq!(exec("6 ÷ 4 · 2"), Ok(Fixed::from_f64(3.0)));
+    assert_eq!(exec("√√16"), Ok(Fixed::from_f64(2.0)));
+    assert_eq!(exec("2⁻¹"), Ok(Fixed::from_f64(0.5)));
+    assert_eq!(exec("1 ≤ 2 ≠ 0"), Ok(Fixed::from_f64(1.0)));
+    assert_eq!(exec("π"), exec("pi"));
+    assert_eq!(exec("1 ÷ 0"), Err(("Division by zero", '÷', 2)));
+    assert_eq!(exec("√−4"), Err(("Square root of a negative number", '√', 0)));
+    let fmt = |expr: &str| exec_as::<Quantity>(expr).map(|q| q.to_string());
+    assert_eq!(fmt("9.81 m/s² * 2 s"), fmt("19.62 m/s"));
+    assert_eq!(fmt("2 N·m"), fmt("2 kg m^2/s^2"));
+    assert_eq!(fmt("1 km² to m²"), Ok(String::from("1000000 m^2")));
+
+    assert_eq!(to_ascii("2×3²−√π"), "2*3**2-sqrt(pi)");
+    assert_eq!(to_ascii("√(1+3) ≥ 2π"), "sqrt(1+3) >= 2 pi");
+    assert_eq!(to_ascii("√1e−3 ÷ 2"), "sqrt(1e-3) / 2");
+    assert_eq!(to_ascii("9.81 m/s² to km·h⁻²"), "9.81 m/s^2 to km h^-2");
+    for expr in ["2×3²−√π", "√abs(−4)⁻¹", "√2²", "1 ≤ 2 ≠ 0"] {
+        assert_eq!(exec(&to_ascii(expr)), exec(expr));
+    };
+}
+
+#[test]
+fn comments_and_statements() {
+    use crate::fixed::Fixed;
+    use crate::ops::Settings;
+    use crate::parser::exec;
+    use crate::script::exec_script;
+    let script = "# header\n/* a whole line */\n7 // 2; (1 +\n 2) /* inline */ * 3\n";
+    let values = exec_script::<Fixed>(script, &Settings::default());
+    assert_eq!(values, Ok(vec![Fixed::from_f64(3.0), Fixed::from_f64(9.0)]));
+    assert_eq!(exec("1 + 1; 2 * 3;"), Ok(Fixed::from_f64(6.0)));
+    assert_eq!(exec("1 # 2\n"), Ok(Fixed::from_f64(1.0)));
+    assert_eq!(exec("1 /* 2\n3 */ + 4"), Ok(Fixed::from_f64(5.0)));
+    assert_eq!(exec("1; 1 ÷ 0"), Err(("Division by zero", '÷', 5)));
+    assert_eq!(exec("1 /* 2"), Err(("Unterminated comment", '/', 2)));
+    assert_eq!(exec("(1; 2)"), Err(("Unexpected", ';', 2)));
+    assert_eq!(exec("1\n2 +"), Err(("Unexpected end of expression", '\'', 5)));
+    assert_eq!(exec_script::<Fixed>("# nothing\n/* at all */", &Settings::default()), Ok(vec![]));
+    assert_eq!(exec_script::<Fixed>("", &Settings::default()), Ok(vec![]));
+    assert_eq!(exec("7 // 2 # note"), Ok(Fixed::from_f64(3.0)));
+    assert_eq!(exec("// 2"), Err(("Unexpected", '/', 0)));
+}
+
+#[test]
+fn scripts() {
+    use crate::fixed::Fixed;
+    use crate::ops::Settings;
+    use crate::parser::{exec, exec_as};
+    use crate::rational::Rational;
+    use crate::script::exec_script;
+    let amortization = "
+        principal = 1000; rate = 0.01; months = 12
+        growth = 1
+        for m in 0..months { growth *= 1 + rate }
+        payment = principal * rate * growth / (growth - 1)
+        balance = principal
+        paid = 0
+        while balance > 0.01 {
+            balance = balance * (1 + rate) - payment
+            paid += 1
+        }
+        paid";
+    assert_eq!(exec(amortization), Ok(Fixed::from_f64(12.0)));
+    let settings = Settings::default();
+    let values = exec_script::<Fixed>("for k in 0..3 { k * 2 }; return 7; 8", &settings);
+    assert_eq!(values, Ok(vec![Fixed::from_f64(0.0), Fixed::from_f64(2.0), Fixed::from_f64(4.0), Fixed::from_f64(7.0)]));
+    let values = exec_script::<Fixed>("x = 1; x += 2", &settings);
+    assert_eq!(values, Ok(vec![]));
+    assert_eq!(exec("x = 1\nif x > 1 { 10 } else if x == 1 { 20 } else { 30 }"), Ok(Fixed::from_f64(20.0)));
+    assert_eq!(exec("sign = 0; x = -3; if x < 0 { sign = -1 }; sign"), Ok(Fixed::from_f64(-1.0)));
+    assert_eq!(exec("pi = 3; pi"), Err(("Assignment to a built-in name", 'p', 0)));
+    assert_eq!(exec("s = 0; for i in 0..5 { s += i }; s"), Ok(Fixed::from_f64(10.0)));
+    assert_eq!(exec("for pi in 0..3 { }"), Err(("Assignment to a built-in name", 'p', 4)));
+    // A variable named after a function doesn't hide the function
+    assert_eq!(exec("sqrt = 16; sqrt(sqrt)"), Ok(Fixed::from_f64(4.0)));
+    assert_eq!(exec_as::<Rational>("s = 0; for n in 1..4 { s += 1/n }; s").map(|r| r.to_string()), Ok(String::from("11/6")));
+
+    // Variables assigned in a block are local to it
+    assert_eq!(exec("if 1 { y = 2 }; y"), Err(("Unknown name", 'y', 16)));
+    assert_eq!(exec("for k in 0..3 { }; k"), Err(("Unknown name", 'k', 19)));
+    assert_eq!(exec("x = 1; { x = 2; y = 3 }; x"), Ok(Fixed::from_f64(2.0)));
+    assert_eq!(exec("{ y = 3 }; y"), Err(("Unknown name", 'y', 11)));
+    assert_eq!(exec("x = 1; x"), Ok(Fixed::from_f64(1.0)));
+    assert_eq!(exec("x += 1"), Err(("Unknown name", 'x', 0)));
+    assert_eq!(exec("x = 1"), Err(("No value", '\'', 5)));
+    let limited = Settings { max_steps: Some(100), ..Settings::default() };
+    assert_eq!(exec_script::<Fixed>("n = 0; while 1 { n += 1 }", &limited), Err(("Step limit reached", 'n', 17)));
+    assert_eq!(exec("if 1 { 2"), Err(("Unmatched", '{', 5)));
+    assert_eq!(exec("1 }"), Err(("Unmatched", '}', 2)));
+    assert_eq!(exec("if 1 2"), Err(("Unexpected end of expression", '\'', 6)));
+    assert_eq!(exec("for k in 3 { }"), Err(("Expected a range", 'i', 6)));
+    assert_eq!(exec("else { 1 }"), Err(("Unexpected", 'e', 0)));
+}
+
+#[test]
+fn lists() {
+    use crate::fixed::Fixed;
+    use crate::int::Int;
+    use crate::list::Value;
+    use crate::ops::Settings;
+    use crate::parser::exec_as;
+    use crate::script::exec_script;
+    let fmt = |expr: &str| exec_as::<Value<Fixed>>(expr).map(|x| x.to_string());
+    assert_eq!(fmt("[1, 2, 3] * 2"), Ok(String::from("[2, 4, 6]")));
+    assert_eq!(fmt("10 - [1, 2] + [0.5, 0.25]"), Ok(String::from("[9.5, 8.25]")));
+    assert_eq!(fmt("[[1, 2], [3, 4]] / 2"), Ok(String::from("[[0.5, 1], [1.5, 2]]")));
+    assert_eq!(fmt("[1, 2] < [2, 1]"), Ok(String::from("[1, 0]")));
+    assert_eq!(fmt("sqrt([4, 9])"), Ok(String::from("[2, 3]")));
+    assert_eq!(fmt("[[], [ ]]"), Ok(String::from("[[], []]")));
+    assert_eq!(fmt("len([])"), Ok(String::from("0")));
+    assert_eq!(fmt("mean([])"), Err(("Empty list", 'm', 0)));
+    assert_eq!(fmt("product([100000, 100000])"), Err(("Result out of range", 'p', 0)));
+    assert_eq!(fmt("sum([1, 2, 3]) + product(2, 3)"), Ok(String::from("12")));
+    assert_eq!(fmt("mean([1, 2, 3, 4])"), Ok(String::from("2.5")));
+    assert_eq!(fmt("[min([3, 1, 2]), max(3, 1, 2), len([5, 6])]"), Ok(String::from("[1, 3, 2]")));
+    assert_eq!(fmt("sum([[1, 2], [3, 4]])"), Ok(String::from("[4, 6]")));
+    assert_eq!(fmt("range(3)"), Ok(String::from("[0, 1, 2]")));
+    assert_eq!(fmt("range(1, 2, 0.25)"), Ok(String::from("[1, 1.25, 1.5, 1.75]")));
+    assert_eq!(fmt("range(3, 0, -1)"), Ok(String::from("[3, 2, 1]")));
+    assert_eq!(fmt("map(x**2 + 1, x, range(1, 4))"), Ok(String::from("[2, 5, 10]")));
+    assert_eq!(fmt("map(sum(row), row, [[1, 2], [3, 4]])"), Ok(String::from("[3, 7]")));
+    assert_eq!(fmt("x = 10; map(x, x, [1]); x"), Ok(String::from("10")));
+    let values = exec_script::<Value<Fixed>>("xs = [1,\n 2]\nsum(xs)", &Settings::default());
+    assert_eq!(values, Ok(vec![Value::Single(Fixed::from_f64(3.0))]));
+    let wrapped = exec_as::<Value<Int>>("(u8)[250, 5] + 10").map(|x| x.to_string());
+    assert_eq!(wrapped, Ok(String::from("[4, 15]")));
+
+    assert_eq!(fmt("[1, 2] + [1]"), Err(("Mismatched lengths", '+', 7)));
+    assert_eq!(fmt("sum([])"), Err(("Empty list", 's', 0)));
+    assert_eq!(fmt("range(1, 2, 0)"), Err(("Zero step", 'r', 0)));
+    assert_eq!(fmt("range(1e6 + 1)"), Err(("List too long", 'r', 0)));
+    assert_eq!(fmt("map(1/x, x, [1, 0])"), Err(("Division by zero", '/', 5)));
+    assert_eq!(fmt("map(x, 2, [1])"), Err(("Expected a variable", '2', 7)));
+    assert_eq!(fmt("[1, 2"), Err(("Unmatched", '[', 0)));
+    assert_eq!(fmt("[1, 2)"), Err(("Unmatched", ')', 5)));
+    assert_eq!(fmt("if [1] { 1 }"), Err(("List as a condition", '[', 3)));
+    assert_eq!(exec_as::<Fixed>("[1]"), Err(("Unexpected", '[', 0)));
+}
+
+#[test]
+fn matrices() {
+    use crate::fixed::Fixed;
+    use crate::list::Value;
+    use crate::parser::exec_as;
+    use crate::rational::Rational;
+    let fmt = |expr: &str| exec_as::<Value<Fixed>>(expr).map(|x| x.to_string());
+    assert_eq!(fmt("[[1, 2], [3, 4]] * [[5, 6], [7, 8]]"), Ok(String::from("[[19, 22], [43, 50]]")));
+    assert_eq!(fmt("[[1, 2], [3, 4]] * [1, 1]"), Ok(String::from("[3, 7]")));
+    assert_eq!(fmt("[1, 1] * [[1, 2], [3, 4]]"), Ok(String::from("[4, 6]")));
+    assert_eq!(fmt("[1, 2] * [3, 4]"), Ok(String::from("[3, 8]")));
+    assert_eq!(fmt("[[1, 2], [3, 4]] * 2 + identity(2)"), Ok(String::from("[[3, 4], [6, 9]]")));
+    assert_eq!(fmt("transpose([[1, 2, 3], [4, 5, 6]])"), Ok(String::from("[[1, 4], [2, 5], [3, 6]]")));
+    assert_eq!(fmt("transpose([1, 2])"), Ok(String::from("[[1], [2]]")));
+    assert_eq!(fmt("det([[1, 2], [3, 4]])"), Ok(String::from("-2")));
+    assert_eq!(fmt("det([[0, 1, 2], [1, 0, 3], [4, -3, 8]])"), Ok(String::from("-2")));
+    assert_eq!(fmt("det([[1, 2], [2, 4]])"), Ok(String::from("0")));
+    assert_eq!(fmt("inverse([[4, 7], [2, 6]])"), Ok(String::from("[[0.6, -0.7], [-0.2, 0.4]]")));
+    assert_eq!(fmt("a = [[1, 2], [3, 4]]; a * inverse(a)"), Ok(String::from("[[1, 0], [0, 1]]")));
+    assert_eq!(fmt("solve([[2, 1], [1, 3]], [3, 5])"), Ok(String::from("[0.8, 1.4]")));
+    let exact = exec_as::<Value<Rational>>("inverse([[1, 2], [3, 4]])").map(|x| x.to_string());
+    assert_eq!(exact, Ok(String::from("[[-2, 1], [3/2, -1/2]]")));
+
+    assert_eq!(fmt("[[1, 2, 3]] * [[1, 2], [3, 4]]"), Err(("Right operand rows don't match left operand columns", '*', 12)));
+    assert_eq!(fmt("[[1, 2], [3]] * [[1]]"), Err(("Left operand not a matrix", '*', 14)));
+    assert_eq!(fmt("inverse([[1, 2], [2, 4]])"), Err(("Singular matrix", 'i', 0)));
+    assert_eq!(fmt("det([[1, 2, 3], [4, 5, 6]])"), Err(("Argument not a square matrix", 'd', 0)));
+    assert_eq!(fmt("solve([1, 2], [1])"), Err(("First argument not a square matrix", 's', 0)));
+    assert_eq!(fmt("solve([[1, 0], [0, 1]], [1, 2, 3])"), Err(("Second argument not a list of the matrix size", 's', 0)));
+    assert_eq!(fmt("identity(1.5)"), Err(("Argument not a size", 'i', 0)));
+}
+
+#[test]
+fn statistics() {
+    use crate::complex::Complex;
+    use crate::fixed::Fixed;
+    use crate::list::Value;
+    use crate::parser::exec_as;
+    use crate::rational::Rational;
+    use crate::uncertain::Uncertain;
+    use crate::units::Quantity;
+    let fmt = |expr: &str| exec_as::<Value<Fixed>>(expr).map(|x| x.to_string());
+    assert_eq!(fmt("median([3, 1, 2, 4])"), Ok(String::from("2.5")));
+    assert_eq!(fmt("median([5, 1, 3])"), Ok(String::from("3")));
+    assert_eq!(fmt("mode([3, 1, 3, 2, 1])"), Ok(String::from("1")));
+    assert_eq!(fmt("pvariance([2, 4, 4, 4, 5, 5, 7, 9])"), Ok(String::from("4")));
+    assert_eq!(fmt("pstddev([2, 4, 4, 4, 5, 5, 7, 9])"), Ok(String::from("2")));
+    assert_eq!(fmt("variance([1, 2, 3, 4, 5])"), Ok(String::from("2.5")));
+    assert_eq!(fmt("stddev([2, 4, 6])"), Ok(String::from("2")));
+    assert_eq!(fmt("percentile([5, 1, 4, 2, 3], 25)"), Ok(String::from("2")));
+    assert_eq!(fmt("percentile([1, 2, 3, 4], 50)"), Ok(String::from("2.5")));
+    assert_eq!(fmt("percentile([1, 2, 3, 4], 100)"), Ok(String::from("4")));
+    assert_eq!(fmt("covariance([1, 2, 3], [2, 4, 7])"), Ok(String::from("2.5")));
+    assert_eq!(fmt("correlation([1, 2, 3], [6, 4, 2])"), Ok(String::from("-1")));
+    assert_eq!(fmt("linreg([1, 2, 3], [3, 5, 7])"), Ok(String::from("[2, 1]")));
+    // The sum overflows as a fixed point number, but not the mean
+    assert_eq!(fmt("mean(range(100000) * 10000)"), Ok(String::from("499995000")));
+    assert_eq!(fmt("sum(range(100000) * 10000)"), Err(("Result out of range", 's', 0)));
+    let exact = exec_as::<Value<Rational>>("[mean([1, 2, 4]), variance([1, 2, 4])]").map(|x| x.to_string());
+    assert_eq!(exact, Ok(String::from("[7/3, 7/3]")));
+    // Sums and interpolations don't wrap around
+    assert_eq!(fmt("percentile([2147483647, -2147483648], 50)"), Ok(String::from("-0.5")));
+    assert_eq!(fmt("pvariance([70000, -70000])"), Err(("Result out of range", 'p', 0)));
+    let complex = |expr: &str| exec_as::<Value<Complex>>(expr).map(|x| x.to_string());
+    assert_eq!(complex("mean([2000000000, 2000000000])"), Ok(String::from("2000000000")));
+    assert_eq!(complex("median([2147483647, 2147483646])"), Ok(String::from("2147483646.5")));
+    assert_eq!(complex("variance([2000000000, -2000000000])"), Err(("Result out of range", 'v', 0)));
+    let uncertain = exec_as::<Value<Uncertain>>("mean([2000000000, 2000000000])").map(|x| x.to_string());
+    assert_eq!(uncertain, Ok(String::from("2000000000")));
+    let quantity = exec_as::<Value<Quantity>>("mean([2000000000, 2000000000])").map(|x| x.to_string());
+    assert_eq!(quantity, Ok(String::from("2000000000")));
+    // Unsigned formats take deviations below the mean by their size
+    let unsigned = |expr: &str| exec_as::<Value<Fixed<32, u64>>>(expr).map(|x| x.to_string());
+    assert_eq!(unsigned("variance([1, 2, 3])"), Ok(String::from("1")));
+    assert_eq!(unsigned("linreg([1, 2, 3], [3, 5, 7])"), Ok(String::from("[2, 1]")));
+    assert_eq!(unsigned("covariance([1, 2, 3], [3, 2, 1])"), Err(("Negative result", 'c', 0)));
+
+    assert_eq!(fmt("median(range(0))"), Err(("Empty list", 'm', 0)));
+    assert_eq!(fmt("variance([1])"), Err(("Not enough values", 'v', 0)));
+    assert_eq!(fmt("percentile([1, 2], 101)"), Err(("Percentile out of range", 'p', 0)));
+    assert_eq!(fmt("covariance([1, 2], [1, 2, 3])"), Err(("Mismatched lengths", 'c', 0)));
+    assert_eq!(fmt("linreg([1, 1], [1, 2])"), Err(("Constant values", 'l', 0)));
+}
+
+#[test]
+fn equations() {
+    use crate::fixed::Fixed;
+    use crate::list::Value;
+    use crate::parser::exec_as;
+    let fmt = |expr: &str| exec_as::<Value<Fixed>>(expr).map(|x| x.to_string());
+    assert_eq!(fmt("solve(x**2 - 2 = 0, x, 1)"), Ok(String::from("1.414213562")));
+    // `^` is XOR, which fails on the variable, but not elsewhere
+    assert_eq!(fmt("solve(x^2 - 2 = 0, x, 1)"), Err(("XOR of the variable (powers are written **)", '^', 7)));
+    assert_eq!(fmt("integrate(2x ^ 3, x, 0, 1)"), Err(("XOR of the variable (powers are written **)", '^', 13)));
+    assert_eq!(fmt("map(x^2, x, [1, 2, 3])"), Err(("XOR of the variable (powers are written **)", '^', 5)));
+    assert_eq!(fmt("root(x - (6 ^ 3), x, 0, 10)"), Ok(String::from("5")));
+    assert_eq!(fmt("map(xs^2, xs, [1, 2])"), Err(("XOR of the variable (powers are written **)", '^', 6)));
+    assert_eq!(fmt("solve(x**2 = 2, x, -1)"), Ok(String::from("-1.414213562")));
+    assert_eq!(fmt("solve(x**2 - 2, x, 100)"), fmt("sqrt(2)"));
+    assert_eq!(fmt("root(x**2 - 2, x, 0, 2)"), fmt("sqrt(2)"));
+    assert_eq!(fmt("root(x**3 - x - 2, x, 1, 2)"), Ok(String::from("1.521379707")));
+    assert_eq!(fmt("root(x = 3, x, 10, 0)"), Ok(String::from("3")));
+    assert_eq!(fmt("a = 5; solve(x * x = a, x, 1)"), fmt("sqrt(5)"));
+    // With two arguments, solve solves a linear system
+    assert_eq!(fmt("solve([[2, 1], [1, 3]], [3, 5])"), Ok(String::from("[0.8, 1.4]")));
+    assert_eq!(fmt("map(solve(x**2 = a, x, 1), a, [4, 9])"), Ok(String::from("[2, 3]")));
+
+    assert_eq!(fmt("root(x**2 + 1, x, 0, 2)"), Err(("No sign change", 'r', 0)));
+    assert_eq!(fmt("solve(x**2 + 1, x, 1)"), Err(("Zero derivative", 's', 0)));
+    assert_eq!(fmt("solve(x**2 + 1, x, 0.9)"), Err(("No convergence", 's', 0)));
+    assert_eq!(fmt("solve(1 / x = 1, x, 3)"), Err(("Method diverged", 's', 0)));
+    assert_eq!(fmt("root(1 / x, x, -1, 2)"), Err(("Division by zero", '/', 7)));
+    assert_eq!(fmt("solve(x**2 = y, x, 1)"), Err(("Unknown name", 'y', 13)));
+}
+
+#[test]
+fn calculus() {
+    use crate::fixed::Fixed;
+    use crate::list::Value;
+    use crate::parser::exec_as;
+    let fmt = |expr: &str| exec_as::<Value<Fixed>>(expr).map(|x| x.to_string());
+    assert_eq!(fmt("integrate(x**2, x, 0, 3)"), Ok(String::from("9")));
+    assert_eq!(fmt("integrate(x**2, x, 3, 0)"), Ok(String::from("-9")));
+    assert_eq!(fmt("integrate(x, x, 2, 2)"), Ok(String::from("0")));
+    assert_eq!(fmt("integrate(1 / x, x, 1, 2)"), Ok(String::from("0.693147181")));
+    assert_eq!(fmt("integrate(4 / (1 + x * x), x, 0, 1)"), Ok(String::from("3.141592654")));
+    // The derivative of sqrt is infinite at 0, where the intervals get small
+    assert_eq!(fmt("integrate(sqrt(x), x, 0, 1)"), Ok(String::from("0.666666667")));
+    assert_eq!(fmt("integrate(integrate(x * y, x, 0, 1), y, 0, 2)"), Ok(String::from("1")));
+    assert_eq!(fmt("deriv(x**2, x, 3)"), Ok(String::from("6")));
+    assert_eq!(fmt("deriv(x**2 - 4 * x, x, -1.5)"), Ok(String::from("-7")));
+    assert_eq!(fmt("map(deriv(x * x, x, a), a, [1, 2])"), Ok(String::from("[2, 4]")));
+    assert_eq!(fmt("solve(integrate(t, t, 0, x) = 2, x, 1)"), Ok(String::from("2")));
+    let close = |expr: &str, expected: f64| match exec_as::<Fixed>(expr) {
+        Ok(x) => (x.as_i64() as f64 / (1u64 << 32) as f64 - expected).abs() < 1e-6,
+        Err(_) => false
+    };
+    assert!(close("deriv(sqrt(x), x, 4)", 0.25));
+    assert!(close("deriv(1 / x, x, 2)", -0.25));
+    // Within 4 of the smallest step of Q32.32
+    let third = exec_as::<Fixed>("integrate(sqrt(x), x, 0, 1)").map(|x| (x.as_i64() - (2i64 << 32) / 3).abs() <= 4);
+    assert_eq!(third, Ok(true));
+
+    assert_eq!(fmt("integrate(1 / x, x, -1, 1)"), Err(("Division by zero", '/', 12)));
+    assert_eq!(fmt("deriv(sqrt(x), x, 0)"), Err(("Square root of a negative number", 's', 6)));
+    assert_eq!(fmt("integrate(x, x, 0)"), Err(("Wrong number of arguments", 'i', 0)));
+    assert_eq!(fmt("deriv(x, 2, 1)"), Err(("Expected a variable", '2', 9)));
+}
+
+#[test]
+fn unsigned_formats() {
+    use crate::fixed::{Fixed, UFixed};
+    use crate::parser::exec_as;
+    assert_eq!(exec_as::<UFixed>("4294967295.5"), Ok(UFixed::from_raw(0xFFFF_FFFF_8000_0000)));
+    assert_eq!(exec_as::<UFixed>("4294967296"), Err(("Literal out of range", '4', 0)));
+    assert_eq!(exec_as::<UFixed>("-1"), Err(("Literal out of range", '-', 0)));
+    assert_eq!(exec_as::<UFixed>("-0"), Ok(UFixed::from_raw(0)));
+    assert_eq!(exec_as::<UFixed>("3 - 5"), Err(("Negative result", '-', 2)));
+    assert_eq!(exec_as::<UFixed>("5 * (1 - 1.5)"), Err(("Negative result", '-', 7)));
+    assert_eq!(exec_as::<UFixed>("3000000000 + 1000000000"), Ok(UFixed::from_raw(4_000_000_000 << 32)));
+    assert_eq!(exec_as::<UFixed>("0x8000_0000 >> 31"), Ok(UFixed::from_raw(1 << 32)));
+    assert_eq!(exec_as::<UFixed>("65536.5 * 65535"), Ok(UFixed::from_raw(0xFFFF_7FFF_8000_0000)));
+    assert_eq!(exec_as::<UFixed>("4000000000 / 0.5 - 1"), exec_as("3705032703"));
+    assert_eq!(exec_as::<UFixed<16, u32>>("65535.25 & 0xFF.F"), Ok(Fixed::from_raw(0x00FF_4000)));
+    assert_eq!(exec_as::<UFixed<31, u32>>("1.5 * 1.25"), Ok(Fixed::from_raw(0xF000_0000)));
+    assert_eq!(exec_as::<UFixed<64, u128>>("0xFFFF_FFFF_FFFF_FFFF.8 / 2"), Ok(Fixed::from_raw((1 << 127) - (1 << 62))));
+    assert_eq!(exec_as::<UFixed<64, u128>>("3 - 0x1p-64 * 5"), Ok(Fixed::from_raw((3 << 64) - 5)));
+
+    assert_eq!(format!("{}", exec_as::<UFixed>("4294967295.75").unwrap()), "4294967295.75");
+    assert_eq!(format!("{:+.1}", exec_as::<UFixed<16, u32>>("2.25").unwrap()), "+2.3");
+}
+
+#[test]
+fn bigint_matches_i128() {
+    use crate::bigint::BigInt;
+    let values: [i128; 12] = [
+        0, 1, -1, 7, -7, 1 << 40, -(1 << 40) + 3, 0xFFFF_FFFF, -0x1_0000_0000,
+        123_456_789_012_345_678, -98_765_432_109_876_543, i64::MAX as i128
+    ];
+    for a in values {
+        for b in values {
+            let (x, y) = (BigInt::from(a), BigInt::from(b));
+            assert_eq!((&x + &y).to_i128(), Some(a + b), "{a} + {b}");
+            assert_eq!((&x - &y).to_i128(), Some(a - b), "{a} - {b}");
+            assert_eq!((&x & &y).to_i128(), Some(a & b), "{a} & {b}");
+            assert_eq!((&x | &y).to_i128(), Some(a | b), "{a} | {b}");
+            assert_eq!((&x ^ &y).to_i128(), Some(a ^ b), "{a} ^ {b}");
+            assert_eq!(x.cmp(&y), a.cmp(&b), "{a} cmp {b}");
+            if a.unsigned_abs() < 1 << 63 && b.unsigned_abs() < 1 << 63 {
+                assert_eq!((&x * &y).to_i128(), Some(a * b), "{a} * {b}");
+            };
+            if b != 0 {
+                assert_eq!((&x / &y).to_i128(), Some(a / b), "{a} / {b}");
+                assert_eq!((&x % &y).to_i128(), Some(a % b), "{a} % {b}");
+                let floor = a / b - (a % b != 0 && (a < 0) != (b < 0)) as i128;
+                assert_eq!(x.divrem_floor(&y), (BigInt::from(floor), BigInt::from(a - b * floor)));
+            };
+        };
+        for shift in [0, 1, 5, 31, 32, 33, 64] {
+            let x = BigInt::from(a);
+            assert_eq!(x.shr(shift).to_i128(), Some(a >> shift), "{a} >> {shift}");
+            if a.unsigned_abs() < 1 << 63 {
+                assert_eq!(x.shl(shift).to_i128(), Some(a << shift), "{a} << {shift}");
+            };
+        };
+        assert_eq!(BigInt::from(a).to_string(), a.to_string());
+        assert_eq!(BigInt::from(a).to_str_radix(16), format!("{:x}", a.unsigned_abs()));
+    };
+}
+
+#[test]
+fn bigint_long_division() {
+    use crate::bigint::BigInt;
+    let digits = |s: &str| BigInt::from_digits(s.chars().map(|c| c.to_digit(10).unwrap()), 10);
+    let a = digits("31415926535897932384626433832795028841971693993751058209749445923078164062862");
+    let b = digits("27182818284590452353602874713526624977572470936999595749669");
+    let r = digits("1414213562373095048801688724209698078569671875376948073176");
+    let n = &(&a * &b) + &r;
+    assert_eq!(n.divrem(&b), (a.clone(), r.clone()));
+    assert_eq!((-n.clone()).divrem(&b), (-a.clone(), -r.clone()));
+    assert_eq!(n.divrem(&a).1, r);
+    assert_eq!(BigInt::from(6u32).pow(40).gcd(&BigInt::from(10u32).pow(30)), BigInt::from(2u32).pow(30));
+    assert_eq!(digits("340282366920938463463374607431768211456").to_str_radix(2), format!("1{}", "0".repeat(128)));
+    assert_eq!(BigInt::from(-255i128).to_str_radix(36), "73");
+}
+
+#[test]
+fn rational_mode() {
+    use crate::fixed::{Fixed, RoundingMode};
+    use crate::parser::exec_as;
+    use crate::rational::Rational;
+    let fmt = |expr: &str| exec_as::<Rational>(expr).map(|r| r.to_string());
+    assert_eq!(fmt("1/3*3"), Ok(String::from("1")));
+    assert_eq!(fmt("1/3 - 1/2"), Ok(String::from("-1/6")));
+    assert_eq!(fmt("0.1 + 0.2"), Ok(String::from("3/10")));
+    assert_eq!(fmt("2.5e-3 * 0x1.8p4"), Ok(String::from("3/50")));
+    assert_eq!(fmt("4294967296 * 4294967296 - 1"), Ok(String::from("18446744073709551615")));
+    assert_eq!(fmt("-7 % 3"), Ok(String::from("-1")));
+    assert_eq!(fmt("-6 & 3 | 8 ^ 1"), Ok(String::from("11")));
+    assert_eq!(fmt("1 << 70 >> 68"), Ok(String::from("4")));
+    assert_eq!(fmt("-5 >> 1"), Ok(String::from("-3")));
+    assert_eq!(fmt("1.5 % 1"), Err(("Not an integer", '%', 4)));
+    assert_eq!(fmt("4 & 1/2"), Err(("Not an integer", '&', 2)));
+    assert_eq!(fmt("1 << -1"), Err(("Shift amount out of range", '<', 2)));
+    assert_eq!(fmt("1 / (2 - 2)"), Err(("Division by zero", '/', 2)));
+    assert_eq!(fmt("1 % 0"), Err(("Division by zero", '%', 2)));
+    assert_eq!(fmt("1e10001"), Err(("Literal out of range", '1', 0)));
+
+    let third = exec_as::<Rational>("-1/3").unwrap();
+    let round = |mode| third.to_fixed::<32, i64>(mode).unwrap().as_i64();
+    assert_eq!(round(RoundingMode::NearestEven), -1_431_655_765);
+    assert_eq!(round(RoundingMode::TowardZero), -1_431_655_765);
+    assert_eq!(round(RoundingMode::Floor), -1_431_655_766);
+    assert_eq!(round(RoundingMode::Ceil), -1_431_655_765);
+    let tie = exec_as::<Rational>("5 / 2 / 4294967296").unwrap();
+    assert_eq!(tie.to_fixed::<32, i64>(RoundingMode::NearestEven), Some(Fixed::from_i64(2)));
+    assert_eq!(tie.to_fixed::<32, i64>(RoundingMode::NearestAway), Some(Fixed::from_i64(3)));
+    assert_eq!(exec_as::<Rational>("(1 << 31) - 1").unwrap().to_fixed::<32, i64>(RoundingMode::Floor), Some(Fixed::from_i64(i64::MAX >> 32 << 32)));
+    assert_eq!(exec_as::<Rational>("1 << 31").unwrap().to_fixed::<32, i64>(RoundingMode::Floor), None);
+}
+
+#[test]
+fn int_mode() {
+    use crate::bigint::BigInt;
+    use crate::ops::Settings;
+    use crate::parser::{exec_as, exec_with};
+    let fmt = |expr: &str| exec_as::<BigInt>(expr).map(|n| n.to_string());
+    let floor = Settings { floor_division: true, ..Settings::default() };
+    let fmt_floor = |expr: &str| exec_with::<BigInt>(expr, &floor).map(|n| n.to_string());
+    assert_eq!(fmt("0xFFFF_FFFF_FFFF + 1"), Ok(String::from("281474976710656")));
+    assert_eq!(fmt("18446744073709551615 * 18446744073709551615"),
+        Ok(String::from("340282366920938463426481119284349108225")));
+    assert_eq!(fmt("1 << 100 >> 98"), Ok(String::from("4")));
+    assert_eq!(fmt("-1 >> 100"), Ok(String::from("-1")));
+    assert_eq!(fmt("0xF0F0 & -16 | 3 ^ 0b101"), Ok(String::from("61686")));
+    assert_eq!(fmt("1.5e2 + 0x1p4"), Ok(String::from("166")));
+    assert_eq!(fmt("-7 / 2"), Ok(String::from("-3")));
+    assert_eq!(fmt("-7 % 2"), Ok(String::from("-1")));
+    assert_eq!(fmt_floor("-7 / 2"), Ok(String::from("-4")));
+    assert_eq!(fmt_floor("-7 % 2"), Ok(String::from("1")));
+    assert_eq!(fmt_floor("7 % -2"), Ok(String::from("-1")));
+    assert_eq!(fmt("2.5"), Err(("Not an integer", '2', 0)));
+    assert_eq!(fmt("3 + 1e-1"), Err(("Not an integer", '1', 4)));
+    assert_eq!(fmt("5 / 0"), Err(("Division by zero", '/', 2)));
+    assert_eq!(fmt("1 << (1 << 21)"), Err(("Shift amount out of range", '<', 2)));
+
+    let big = exec_as::<BigInt>("0 - (1 << 64) - 255").unwrap();
+    assert_eq!(big.fmt_radix(16), "-0x100000000000000ff");
+    assert_eq!(big.fmt_radix(2), format!("-0b1{}11111111", "0".repeat(56)));
+    assert_eq!(big.fmt_radix(10), "-18446744073709551871");
+    assert_eq!(exec_as::<BigInt>("35 * 36 + 35").unwrap().fmt_radix(36), "zz");
+}
+
+#[test]
+fn fixed_width_int() {
+    use crate::int::Int;
+    use crate::ops::Settings;
+    use crate::parser::{exec, exec_as, exec_with};
+    let fmt = |expr: &str| exec_as::<Int>(expr).map(|n| n.to_string());
+    let settings = |ty: &str| Settings { int_type: Some(ty.parse().unwrap()), ..Settings::default() };
+    let fmt_as = |ty: &str, expr: &str| exec_with::<Int>(expr, &settings(ty)).map(|n| n.to_string());
+    assert_eq!(fmt_as("u8", "0xFF + 1"), Ok(String::from("0")));
+    assert_eq!(fmt_as("i16", "0xFF + 1"), Ok(String::from("256")));
+    assert_eq!(fmt_as("i8", "127 + 1"), Ok(String::from("-128")));
+    assert_eq!(fmt_as("i8", "-128 / -1"), Ok(String::from("-128")));
+    assert_eq!(fmt_as("u64", "0 - 1"), Ok(String::from("18446744073709551615")));
+    assert_eq!(fmt_as("i128", "(1 << 126) * 2"), Ok(String::from("-170141183460469231731687303715884105728")));
+    assert_eq!(fmt_as("u8", "1 << 9"), Err(("Shift amount out of range", '<', 2)));
+    assert_eq!(fmt_as("i8", "1 << -1"), Err(("Negative shift amount", '<', 2)));
+    let lenient = |ty: &str| Settings { saturate_shifts: true, reverse_negative_shifts: true, ..settings(ty) };
+    let fmt_lenient = |ty: &str, expr: &str| exec_with::<Int>(expr, &lenient(ty)).map(|n| n.to_string());
+    assert_eq!(fmt_lenient("u8", "1 << 9"), Ok(String::from("0")));
+    assert_eq!(fmt_lenient("i8", "-16 >> 8"), Ok(String::from("-1")));
+    assert_eq!(fmt_lenient("i8", "16 >> -2"), Ok(String::from("64")));
+    assert_eq!(fmt_as("i32", "-8 >> 1"), Ok(String::from("-4")));
+    assert_eq!(fmt_as("u32", "(0 - 8) >> 1"), Ok(String::from("2147483644")));
+    // Literals out of range wrap around as the results of operations do
+    assert_eq!(fmt_as("u8", "300 + 1"), Ok(String::from("45")));
+
+    assert_eq!(fmt("(u8)300"), Ok(String::from("44")));
+    assert_eq!(fmt("300 as u8 + 1"), Ok(String::from("45")));
+    assert_eq!(fmt("(u8)(200 + 100) as i16"), Ok(String::from("44")));
+    assert_eq!(fmt("((i8)255)"), Ok(String::from("-1")));
+    assert_eq!(fmt("(u8)(i8)-1"), Ok(String::from("255")));
+    assert_eq!(fmt("(u8)1 - 2"), Ok(String::from("255")));
+    assert_eq!(fmt("(u16)1 << (u8)15"), Ok(String::from("32768")));
+    assert_eq!(fmt("(u16)1 << (u8)17"), Err(("Shift amount out of range", '<', 7)));
+    assert_eq!(fmt("(i8)1 + 200"), Ok(String::from("-55")));
+    assert_eq!(fmt("(u8)1 + (i8)1"), Err(("Mismatched types", '+', 6)));
+    assert_eq!(fmt("(u7)1"), Err(("Unknown name", 'u', 1)));
+    assert_eq!(fmt("1 as f32"), Err(("Unknown type", 'f', 5)));
+    assert_eq!(fmt("1 is u8"), Err(("Unexpected", 'i', 2)));
+    assert_eq!(fmt("(1 + u8)"), Err(("Unknown name", 'u', 5)));
+    assert_eq!(exec("(u8)1"), Err(("Casts need an integer mode", '(', 0)));
+
+    let minus_one = exec_with::<Int>("-1", &settings("i16")).unwrap().with_default_type(Some("i16".parse().unwrap()));
+    assert_eq!(minus_one.fmt_radix(16), "0xffff");
+    assert_eq!(minus_one.fmt_radix(10), "-1");
+    let u8 = Some("u8".parse().unwrap());
+    assert_eq!(exec_as::<Int>("300").unwrap().with_default_type(u8).to_string(), "44");
+    assert_eq!(exec_as::<Int>("-1").unwrap().with_default_type(u8).to_string(), "255");
+}
@@ -0,0 +1,147 @@
+use crate::fixed::{Fixed, Repr, RoundingMode};
+use crate::ops::{FixedOp, Op, Operand, Settings, INTEGER_FUNCTIONS, single};
+use crate::parser::Number;
+use crate::rational::Rational;
+use std::fmt::{
+    self, Display
+};
+
+const OUT_OF_RANGE: &str = "Result out of range";
+
+/// A measured value of type `F`, with the standard deviation of its error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Uncertain<F = Fixed> {
+    value: F,
+    sigma: F
+}
+
+#[allow(unused)]
+impl<F: Copy> Uncertain<F> {
+    pub fn new(value: F, sigma: F) -> Self {
+        Uncertain { value, sigma }
+    }
+    pub fn value(&self) -> F { self.value }
+    pub fn sigma(&self) -> F { self.sigma }
+}
+
+impl<const FRAC: u32, T: Repr> Uncertain<Fixed<FRAC, T>> {
+    fn exact(value: Fixed<FRAC, T>) -> Self {
+        Uncertain { value, sigma: Fixed::ZERO }
+    }
+
+    pub fn is_exact(&self) -> bool { self.sigma == Fixed::ZERO }
+
+    /// Applies a binary operator, with `fixed` applying the operators on the values and uncertainties
+    fn operate(op: &Op, a: Self, b: Self, settings: &Settings, fixed: FixedOp<FRAC, T>) -> Result<Self, &'static str> {
+        let apply = |op: &Op, x, y| fixed(op, x, y, settings);
+        let hypot = |x: Fixed<FRAC, T>, y| x.hypot(y, settings.rounding).ok_or(OUT_OF_RANGE);
+        let (sub, mul, div) = (&Op::Sub, &Op::Mul, &Op::Div);
+        let value = match op {
+            Op::PlusMinus => a.value,
+            _ => apply(op, a.value, b.value)?
+        };
+        let sigma = match op {
+            Op::Add | Op::Sub => hypot(a.sigma, b.sigma)?,
+            Op::Mul | Op::ImplicitMul => hypot(apply(mul, b.value, a.sigma)?, apply(mul, a.value, b.sigma)?)?,
+            Op::Div => apply(div, hypot(a.sigma, apply(mul, value, b.sigma)?)?, b.value.abs())?,
+            // The remainder is a - n * b, for the whole quotient n
+            Op::Mod | Op::EuclidMod => {
+                let quotient = apply(div, apply(sub, a.value, value)?, b.value)?;
+                hypot(a.sigma, apply(mul, quotient, b.sigma)?)?
+            },
+            Op::FloorDiv => Fixed::ZERO,
+            Op::Pow if !b.is_exact() => return Err("Uncertain exponent"),
+            Op::Pow if a.is_exact() || b.value == Fixed::ZERO => Fixed::ZERO,
+            // The derivative of x^n is n x^(n-1)
+            Op::Pow => {
+                let one = Fixed::one().ok_or(OUT_OF_RANGE)?;
+                let slope = apply(mul, b.value, apply(op, a.value, apply(sub, b.value, one)?)?)?;
+                apply(mul, slope.abs(), a.sigma)?
+            },
+            Op::Lt | Op::Le | Op::Gt | Op::Ge | Op::Eq | Op::Ne => Fixed::ZERO,
+            Op::PlusMinus if !b.is_exact() => return Err("Uncertainty of an uncertainty"),
+            Op::PlusMinus if b.value < Fixed::ZERO => return Err("Negative uncertainty"),
+            Op::PlusMinus => hypot(a.sigma, b.value)?,
+            Op::And | Op::Or | Op::Xor | Op::Shl | Op::Shr | Op::Rotl | Op::Rotr => {
+                match a.is_exact() && b.is_exact() {
+                    true => Fixed::ZERO,
+                    false => return Err("Bitwise operation on an uncertain value")
+                }
+            }
+        };
+        Ok(Uncertain { value, sigma })
+    }
+}
+
+/// Uncertainties are propagated to first order, the errors of the two operands
+/// being independent: `x - x` has the uncertainty of the difference of two measures of x
+/// Bitwise operations, integer functions and exponents need exact operands,
+/// and comparisons are made on the values
+impl<const FRAC: u32, T: Repr> Operand for Uncertain<Fixed<FRAC, T>> {
+    fn from_number(num: Number) -> Result<Self, &'static str> {
+        Ok(Uncertain::exact(num.into_fixed()?))
+    }
+
+    fn is_true(&self) -> Result<bool, &'static str> {
+        Ok(self.value != Fixed::ZERO)
+    }
+
+    fn apply(op: &Op, a: Self, b: Self, settings: &Settings) -> Result<Self, &'static str> {
+        Uncertain::operate(op, a, b, settings, Fixed::apply)
+    }
+
+    fn checked(op: &Op, a: Self, b: Self, settings: &Settings) -> Result<Self, &'static str> {
+        Uncertain::operate(op, a, b, settings, Fixed::checked)
+    }
+
+    /// Sums the values apart, so that only the result has to be in range,
+    /// and the uncertainties in quadrature
+    fn sum_over(items: Vec<Self>, divisor: u32, settings: &Settings) -> Result<Self, &'static str> {
+        let (values, sigmas): (Vec<_>, Vec<_>) = items.into_iter().map(|x| (x.value, x.sigma)).unzip();
+        let sigma = sigmas.into_iter().try_fold(Fixed::ZERO, |sum, x| sum.hypot(x, settings.rounding));
+        let sigma = Fixed::sum_over(vec![sigma.ok_or(OUT_OF_RANGE)?], divisor, settings)?;
+        Ok(Uncertain { value: Fixed::sum_over(values, divisor, settings)?, sigma })
+    }
+
+    fn call(name: &str, args: Vec<Self>, settings: &Settings) -> Result<Self, &'static str> {
+        if INTEGER_FUNCTIONS.contains(&name) {
+            if !args.iter().all(Uncertain::is_exact) { return Err("Integer function of an uncertain value") };
+            return Ok(Uncertain::exact(Fixed::call(name, args.iter().map(|x| x.value).collect(), settings)?))
+        };
+        let x = single(args)?;
+        match name {
+            "abs" => Ok(Uncertain { value: x.value.abs(), sigma: x.sigma }),
+            "sqrt" => {
+                let value = Fixed::call(name, vec![x.value], settings)?;
+                if x.is_exact() { return Ok(Uncertain::exact(value)) };
+                if value == Fixed::ZERO { return Err("Infinite uncertainty") };
+                let sigma = Fixed::apply(&Op::Div, x.sigma, value, settings)?.shr_bits(1);
+                Ok(Uncertain { value, sigma })
+            },
+            "value" => Ok(Uncertain::exact(x.value)),
+            "sigma" => Ok(Uncertain::exact(x.sigma)),
+            _ => Err("Unknown function")
+        }
+    }
+
+    fn constant(name: &str, settings: &Settings) -> Result<Self, &'static str> {
+        Ok(Uncertain::exact(Fixed::constant(name, settings)?))
+    }
+}
+
+/// Writes `value ± sigma`, with the uncertainty rounded to two significant digits
+/// and the value rounded to the same decimal place
+impl<const FRAC: u32, T: Repr> Display for Uncertain<Fixed<FRAC, T>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_exact() { return write!(f, "{}", self.value) };
+        let decimals = Fixed::<FRAC, T>::DEFAULT_DECIMALS;
+        let sigma = Rational::from_fixed(self.sigma);
+        let shown = match sigma.to_decimal_places(decimals, RoundingMode::NearestEven).split_once('.') {
+            Some(("0", decimal)) => decimal.find(|c| c != '0').map_or(decimals, |i| i + 2).min(decimals),
+            Some((whole, _)) if whole.len() == 1 => 1,
+            _ => 0
+        };
+        let value = Rational::from_fixed(self.value).to_decimal_places(shown, RoundingMode::NearestEven);
+        write!(f, "{value} ± {}", sigma.to_decimal_places(shown, RoundingMode::NearestEven))
+    }
+}
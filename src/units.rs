@@ -0,0 +1,377 @@
+use crate::bigint::BigInt;
+use crate::fixed::{Fixed, Repr, RoundingMode};
+use crate::ops::{FixedOp, Op, Operand, Settings, INTEGER_FUNCTIONS, single};
+use crate::parser::{Number, exec_as};
+use crate::rational::Rational;
+use std::fmt::{
+    self, Display
+};
+
+const OUT_OF_RANGE: &str = "Result out of range";
+
+/// Exponents of the SI base units, in the order of `BASE_UNITS`
+pub type Dimension = [i8; 7];
+
+const DIMENSIONLESS: Dimension = [0; 7];
+const BASE_UNITS: [&str; 7] = ["kg", "m", "s", "A", "K", "mol", "cd"];
+
+/// Names, values in SI base units, dimensions, and whether they take prefixes
+const UNITS: &[(&str, &str, Dimension, bool)] = &[
+    ("g", "0.001", [1, 0, 0, 0, 0, 0, 0], true),
+    ("m", "1", [0, 1, 0, 0, 0, 0, 0], true),
+    ("s", "1", [0, 0, 1, 0, 0, 0, 0], true),
+    ("A", "1", [0, 0, 0, 1, 0, 0, 0], true),
+    ("K", "1", [0, 0, 0, 0, 1, 0, 0], true),
+    ("mol", "1", [0, 0, 0, 0, 0, 1, 0], true),
+    ("cd", "1", [0, 0, 0, 0, 0, 0, 1], true),
+    ("Hz", "1", [0, 0, -1, 0, 0, 0, 0], true),
+    ("N", "1", [1, 1, -2, 0, 0, 0, 0], true),
+    ("Pa", "1", [1, -1, -2, 0, 0, 0, 0], true),
+    ("J", "1", [1, 2, -2, 0, 0, 0, 0], true),
+    ("W", "1", [1, 2, -3, 0, 0, 0, 0], true),
+    ("C", "1", [0, 0, 1, 1, 0, 0, 0], true),
+    ("V", "1", [1, 2, -3, -1, 0, 0, 0], true),
+    ("ohm", "1", [1, 2, -3, -2, 0, 0, 0], true),
+    ("F", "1", [-1, -2, 4, 2, 0, 0, 0], true),
+    ("L", "0.001", [0, 3, 0, 0, 0, 0, 0], true),
+    ("l", "0.001", [0, 3, 0, 0, 0, 0, 0], true),
+    ("t", "1000", [1, 0, 0, 0, 0, 0, 0], false),
+    ("ha", "10000", [0, 2, 0, 0, 0, 0, 0], false),
+    ("bar", "100000", [1, -1, -2, 0, 0, 0, 0], true),
+    ("atm", "101325", [1, -1, -2, 0, 0, 0, 0], false),
+    ("eV", "1.602176634e-19", [1, 2, -2, 0, 0, 0, 0], true),
+    ("Wh", "3600", [1, 2, -2, 0, 0, 0, 0], true),
+    ("cal", "4.184", [1, 2, -2, 0, 0, 0, 0], true),
+    ("min", "60", [0, 0, 1, 0, 0, 0, 0], false),
+    ("h", "3600", [0, 0, 1, 0, 0, 0, 0], false),
+    ("day", "86400", [0, 0, 1, 0, 0, 0, 0], false),
+    ("in", "0.0254", [0, 1, 0, 0, 0, 0, 0], false),
+    ("ft", "0.3048", [0, 1, 0, 0, 0, 0, 0], false),
+    ("yd", "0.9144", [0, 1, 0, 0, 0, 0, 0], false),
+    ("mi", "1609.344", [0, 1, 0, 0, 0, 0, 0], false),
+    ("nmi", "1852", [0, 1, 0, 0, 0, 0, 0], false),
+    ("ac", "4046.8564224", [0, 2, 0, 0, 0, 0, 0], false),
+    ("mph", "1609.344 / 3600", [0, 1, -1, 0, 0, 0, 0], false),
+    ("kn", "1852 / 3600", [0, 1, -1, 0, 0, 0, 0], false),
+    ("lb", "0.45359237", [1, 0, 0, 0, 0, 0, 0], false),
+    ("oz", "0.45359237 / 16", [1, 0, 0, 0, 0, 0, 0], false),
+    ("st", "0.45359237 * 14", [1, 0, 0, 0, 0, 0, 0], false),
+    ("lbf", "0.45359237 * 9.80665", [1, 1, -2, 0, 0, 0, 0], false),
+    ("psi", "0.45359237 * 9.80665 / 0.00064516", [1, -1, -2, 0, 0, 0, 0], false),
+    ("gal", "0.003785411784", [0, 3, 0, 0, 0, 0, 0], false),
+    ("qt", "0.003785411784 / 4", [0, 3, 0, 0, 0, 0, 0], false),
+    ("pt", "0.003785411784 / 8", [0, 3, 0, 0, 0, 0, 0], false),
+    ("floz", "0.003785411784 / 128", [0, 3, 0, 0, 0, 0, 0], false),
+    ("hp", "550 * 0.3048 * 0.45359237 * 9.80665", [1, 2, -3, 0, 0, 0, 0], false),
+    ("BTU", "1055.05585262", [1, 2, -2, 0, 0, 0, 0], false)
+];
+
+/// SI prefixes and their powers of ten
+const PREFIXES: &[(&str, i32)] = &[
+    ("Q", 30), ("R", 27), ("Y", 24), ("Z", 21), ("E", 18), ("P", 15), ("T", 12), ("G", 9),
+    ("M", 6), ("k", 3), ("h", 2), ("da", 1), ("d", -1), ("c", -2), ("m", -3), ("u", -6),
+    ("n", -9), ("p", -12), ("f", -15), ("a", -18), ("z", -21), ("y", -24), ("r", -27), ("q", -30)
+];
+
+fn product(a: &Rational, b: &Rational) -> Rational {
+    Rational::new(a.numer() * b.numer(), a.denom() * b.denom())
+}
+
+fn inverse(a: &Rational) -> Rational {
+    Rational::new(a.denom().clone(), a.numer().clone())
+}
+
+/// The power of ten of the leading digit of a value, or 0 for zero
+fn magnitude(x: &Rational) -> i32 {
+    let ten = BigInt::from(10u32);
+    let (mut num, mut den) = (x.numer().abs(), x.denom().abs());
+    if num.is_zero() || den.is_zero() { return 0 };
+    let mut exp = 0;
+    while num >= &den * &ten {
+        den = &den * &ten;
+        exp += 1;
+    }
+    while num < den {
+        num = &num * &ten;
+        exp -= 1;
+    }
+    exp
+}
+
+/// `a + sign * b` for each base unit
+fn combine(a: Dimension, b: Dimension, sign: i8) -> Result<Dimension, &'static str> {
+    let mut dim = DIMENSIONLESS;
+    for i in 0..dim.len() {
+        dim[i] = b[i].checked_mul(sign).and_then(|x| a[i].checked_add(x)).ok_or("Exponent out of range")?;
+    }
+    Ok(dim)
+}
+
+/// The value and dimension of a unit without exponent, custom units coming first
+fn lookup(name: &str, custom: &[(String, String)]) -> Result<(Rational, Dimension), &'static str> {
+    if let Some(index) = custom.iter().rposition(|(custom_name, _)| custom_name == name) {
+        return definition(&custom[index].1, &custom[..index])
+    };
+    let builtin = |name: &str, prefixed: bool| UNITS.iter().find(|unit| unit.0 == name && (unit.3 || !prefixed));
+    if let Some(&(_, value, dim, _)) = builtin(name, false) {
+        return Ok((exec_as(value).unwrap(), dim))
+    };
+    for &(prefix, exp) in PREFIXES {
+        if let Some(&(_, value, dim, _)) = name.strip_prefix(prefix).and_then(|rest| builtin(rest, true)) {
+            let power = BigInt::from(10u32).pow(exp.unsigned_abs());
+            let scale = match exp >= 0 {
+                true => Rational::from_int(power),
+                false => Rational::new(BigInt::one(), power)
+            };
+            return Ok((product(&exec_as(value).unwrap(), &scale), dim))
+        };
+    }
+    Err("Unknown unit")
+}
+
+/// The value and dimension of a custom unit, defined by a number and units,
+/// such as `201.168 m` or `mi/h`, which can only use earlier custom units
+fn definition(def: &str, custom: &[(String, String)]) -> Result<(Rational, Dimension), &'static str> {
+    let mut words = def.split_whitespace().peekable();
+    let mut value = match words.peek().map(|word| exec_as::<Rational>(word)) {
+        Some(Ok(number)) => {
+            words.next();
+            number
+        },
+        _ => Rational::from_int(BigInt::one())
+    };
+    let mut dim = DIMENSIONLESS;
+    for word in words {
+        let (unit_value, unit_dim) = parse_unit(word, custom)?;
+        value = product(&value, &unit_value);
+        dim = combine(dim, unit_dim, 1)?;
+    }
+    match (value.numer().is_zero(), value.numer().is_negative() != value.denom().is_negative()) {
+        (true, _) => Err("Unit of zero size"),
+        (_, true) => Err("Unit of negative size"),
+        _ => Ok((value, dim))
+    }
+}
+
+/// The value in SI base units and the dimension of a unit, such as `km/h`, `m/s^2` or `N·m`,
+/// where `/` and `·` apply from left to right
+pub fn parse_unit(unit: &str, custom: &[(String, String)]) -> Result<(Rational, Dimension), &'static str> {
+    let mut value = Rational::from_int(BigInt::one());
+    let mut dim = DIMENSIONLESS;
+    // Each part comes after a `·`, or a `/` when it divides
+    let separators = std::iter::once('·').chain(unit.chars().filter(|&c| c == '/' || c == '·'));
+    for (part, separator) in unit.split(['/', '·']).zip(separators) {
+        let (name, exp) = match part.split_once('^') {
+            Some((name, exp)) => (name, exp.parse::<i8>().map_err(|_| "Invalid exponent")?),
+            None => (part, 1)
+        };
+        let exp = match separator {
+            '/' => exp.checked_neg().ok_or("Invalid exponent")?,
+            _ => exp
+        };
+        let (part_value, part_dim) = lookup(name, custom)?;
+        let part_value = match exp >= 0 {
+            true => part_value,
+            false => inverse(&part_value)
+        };
+        for _ in 0..exp.unsigned_abs() {
+            value = product(&value, &part_value);
+        }
+        dim = combine(dim, part_dim, exp)?;
+    }
+    Ok((value, dim))
+}
+
+/// A physical quantity, stored in the units it was written in, as a value and the exact factor
+/// from them to SI base units, so that units only round where values of different units meet,
+/// and the unit it was converted to if any
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Quantity<F = Fixed> {
+    value: F,
+    scale: Rational,
+    dim: Dimension,
+    shown: Option<(String, Rational)>
+}
+
+#[allow(unused)]
+impl<F: Copy> Quantity<F> {
+    pub fn new(value: F, dim: Dimension) -> Self {
+        Quantity { value, scale: Rational::from_int(BigInt::one()), dim, shown: None }
+    }
+    pub fn value(&self) -> F { self.value }
+    pub fn dim(&self) -> Dimension { self.dim }
+}
+
+/// Whether the factor `a` is below `b` in size
+fn below(a: &Rational, b: &Rational) -> bool {
+    a.numer().abs() * b.denom().abs() < b.numer().abs() * a.denom().abs()
+}
+
+impl<const FRAC: u32, T: Repr> Quantity<Fixed<FRAC, T>> {
+    /// The value in units of `scale` times the SI base units
+    fn in_scale(&self, scale: &Rational, settings: &Settings) -> Result<Fixed<FRAC, T>, &'static str> {
+        match &self.scale == scale {
+            true => Ok(self.value),
+            false => {
+                let factor = product(&self.scale, &inverse(scale));
+                product(&Rational::from_fixed(self.value), &factor).to_fixed(settings.rounding).ok_or(OUT_OF_RANGE)
+            }
+        }
+    }
+
+    /// Applies a binary operator, with `fixed` applying the operators on the values
+    fn operate(op: &Op, a: Self, b: Self, settings: &Settings, fixed: FixedOp<FRAC, T>) -> Result<Self, &'static str> {
+        let si = Rational::from_int(BigInt::one());
+        let (x, y, scale) = match op {
+            Op::Mul | Op::ImplicitMul => (a.value, b.value, product(&a.scale, &b.scale)),
+            Op::Div => (a.value, b.value, product(&a.scale, &inverse(&b.scale))),
+            Op::Add | Op::Sub | Op::Mod | Op::EuclidMod | Op::FloorDiv
+            | Op::Lt | Op::Le | Op::Gt | Op::Ge | Op::Eq | Op::Ne => {
+                let scale = match below(&b.scale, &a.scale) {
+                    true => b.scale.clone(),
+                    false => a.scale.clone()
+                };
+                let (x, y) = (a.in_scale(&scale, settings)?, b.in_scale(&scale, settings)?);
+                match op {
+                    Op::Add | Op::Sub | Op::Mod | Op::EuclidMod => (x, y, scale),
+                    _ => (x, y, si)
+                }
+            },
+            Op::Shl | Op::Shr => (a.value, b.in_scale(&si, settings)?, a.scale.clone()),
+            _ => (a.in_scale(&si, settings)?, b.in_scale(&si, settings)?, si)
+        };
+        let dim = match op {
+            Op::Add | Op::Sub | Op::Mod | Op::EuclidMod | Op::FloorDiv
+            | Op::Lt | Op::Le | Op::Gt | Op::Ge | Op::Eq | Op::Ne if a.dim != b.dim => {
+                return Err("Mismatched units")
+            },
+            Op::Lt | Op::Le | Op::Gt | Op::Ge | Op::Eq | Op::Ne => DIMENSIONLESS,
+            Op::Pow if b.dim != DIMENSIONLESS => return Err("Exponent with units"),
+            Op::Pow if a.dim == DIMENSIONLESS => DIMENSIONLESS,
+            Op::Pow if !y.is_integer() => return Err("Fractional exponent"),
+            Op::Pow => {
+                let (neg, n) = y.whole_magnitude();
+                let n = i8::try_from(n).map_err(|_| "Exponent out of range")?;
+                combine(DIMENSIONLESS, a.dim, if neg { -n } else { n })?
+            },
+            Op::Add | Op::Sub | Op::Mod | Op::EuclidMod => a.dim,
+            Op::FloorDiv => DIMENSIONLESS,
+            Op::Mul | Op::ImplicitMul => combine(a.dim, b.dim, 1)?,
+            Op::Div => combine(a.dim, b.dim, -1)?,
+            Op::Shl | Op::Shr | Op::PlusMinus if b.dim == DIMENSIONLESS => a.dim,
+            _ if a.dim == DIMENSIONLESS && b.dim == DIMENSIONLESS => DIMENSIONLESS,
+            _ => return Err("Bitwise operation on a quantity with units")
+        };
+        Ok(Quantity { value: fixed(op, x, y, settings)?, scale, dim, shown: None })
+    }
+}
+
+/// Sums, remainders and comparisons need operands of the same dimension,
+/// and bitwise operations and integer functions need dimensionless ones,
+/// except for the shifted value
+/// Products and quotients keep the units of their operands, and sums, remainders and comparisons
+/// take them in the smaller of their units, while other operations take them in SI base units
+impl<const FRAC: u32, T: Repr> Operand for Quantity<Fixed<FRAC, T>> {
+    fn from_number(num: Number) -> Result<Self, &'static str> {
+        Ok(Quantity::new(num.into_fixed()?, DIMENSIONLESS))
+    }
+
+    fn is_true(&self) -> Result<bool, &'static str> {
+        Ok(self.value != Fixed::ZERO)
+    }
+
+    fn apply(op: &Op, a: Self, b: Self, settings: &Settings) -> Result<Self, &'static str> {
+        Quantity::operate(op, a, b, settings, Fixed::apply)
+    }
+
+    fn checked(op: &Op, a: Self, b: Self, settings: &Settings) -> Result<Self, &'static str> {
+        Quantity::operate(op, a, b, settings, Fixed::checked)
+    }
+
+    /// Sums the values in the smallest of their units, so that only the result has to be in range
+    fn sum_over(items: Vec<Self>, divisor: u32, settings: &Settings) -> Result<Self, &'static str> {
+        let first = items.first().ok_or("Empty list")?;
+        if items.iter().any(|x| x.dim != first.dim) { return Err("Mismatched units") };
+        let scale = items.iter().map(|x| &x.scale).fold(&first.scale, |min, scale| match below(scale, min) {
+            true => scale,
+            false => min
+        });
+        let values = items.iter().map(|x| x.in_scale(scale, settings)).collect::<Result<_, _>>()?;
+        let value = Fixed::sum_over(values, divisor, settings)?;
+        Ok(Quantity { value, scale: scale.clone(), dim: first.dim, shown: None })
+    }
+
+    fn call(name: &str, args: Vec<Self>, settings: &Settings) -> Result<Self, &'static str> {
+        let si = Rational::from_int(BigInt::one());
+        if INTEGER_FUNCTIONS.contains(&name) {
+            if args.iter().any(|x| x.dim != DIMENSIONLESS) { return Err("Integer function of a quantity with units") };
+            let values = args.iter().map(|x| x.in_scale(&si, settings)).collect::<Result<_, _>>()?;
+            return Ok(Quantity::new(Fixed::call(name, values, settings)?, DIMENSIONLESS))
+        };
+        let x = single(args)?;
+        match name {
+            "abs" => Ok(Quantity { value: x.value.abs(), shown: None, ..x }),
+            "sqrt" if x.dim.iter().any(|exp| exp % 2 != 0) => Err("Square root of an odd dimension"),
+            "sqrt" => {
+                let value = Fixed::call(name, vec![x.in_scale(&si, settings)?], settings)?;
+                Ok(Quantity::new(value, x.dim.map(|exp| exp / 2)))
+            },
+            _ => Err("Unknown function")
+        }
+    }
+
+    fn constant(name: &str, settings: &Settings) -> Result<Self, &'static str> {
+        Ok(Quantity::new(Fixed::constant(name, settings)?, DIMENSIONLESS))
+    }
+
+    fn unit(self, unit: &str, settings: &Settings) -> Result<Self, &'static str> {
+        let (value, dim) = parse_unit(unit, &settings.units)?;
+        let dim = combine(self.dim, dim, 1)?;
+        Ok(Quantity { scale: product(&self.scale, &value), dim, shown: None, ..self })
+    }
+
+    fn convert(self, unit: &str, settings: &Settings) -> Result<Self, &'static str> {
+        let (value, dim) = parse_unit(unit, &settings.units)?;
+        if dim != self.dim { return Err("Mismatched units") };
+        Ok(Quantity { shown: Some((String::from(unit), value)), ..self })
+    }
+}
+
+/// Writes the value in the unit it was converted to, with as many digits
+/// as the precision of the format gives in that unit,
+/// or else in SI base units, such as `kg m/s^2`
+impl<const FRAC: u32, T: Repr> Display for Quantity<Fixed<FRAC, T>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let exact = product(&Rational::from_fixed(self.value), &self.scale);
+        // The digits are those that the precision of the format gives in the units of the value
+        let decimals = |unit: &Rational| {
+            (Fixed::<FRAC, T>::DEFAULT_DECIMALS as i32 + magnitude(unit) - magnitude(&self.scale)).max(0) as usize
+        };
+        if let Some((unit, value)) = &self.shown {
+            let shown = product(&exact, &inverse(value));
+            return write!(f, "{} {unit}", shown.to_decimal_string(decimals(value), RoundingMode::NearestAway))
+        };
+        match self.scale.is_integer() && self.scale.numer() == &BigInt::one() {
+            true => write!(f, "{}", self.value)?,
+            false => write!(f, "{}", exact.to_decimal_string(decimals(&Rational::from_int(BigInt::one())), RoundingMode::NearestAway))?
+        };
+        let power = |name: &str, exp: i8| match exp {
+            1 => String::from(name),
+            exp => format!("{name}^{exp}")
+        };
+        let above: Vec<_> = (0..7).filter(|&i| self.dim[i] > 0).map(|i| power(BASE_UNITS[i], self.dim[i])).collect();
+        let below = (0..7).filter(|&i| self.dim[i] < 0);
+        match above.is_empty() {
+            true => for i in below {
+                write!(f, " {}", power(BASE_UNITS[i], self.dim[i]))?;
+            },
+            false => {
+                write!(f, " {}", above.join(" "))?;
+                for i in below {
+                    write!(f, "/{}", power(BASE_UNITS[i], -self.dim[i]))?;
+                }
+            }
+        };
+        Ok(())
+    }
+}